@@ -1,23 +1,70 @@
-/// BrowserMCP Server PRO - MCP 2025 Compliant + 20+ Tools
-/// Port: 3001
-/// Production-grade, zero warnings, optimized
+//! BrowserMCP Server PRO - MCP 2025 Compliant
+//! Port: 3001 (HTTP) or `--stdio` for newline-delimited JSON-RPC over stdin/stdout
+//! Server notifications: `GET /mcp` (SSE) over HTTP, extra stdout frames over stdio
+//! Streamable HTTP sessions: `initialize` issues `Mcp-Session-Id`, `DELETE /mcp` ends it;
+//! POSTs without the header keep working statelessly
+//! Auth: API keys (`Authorization: Bearer` or `X-API-Key`) from `--auth-file`, Origin validation,
+//! `--bind` defaults to 127.0.0.1
+//! OAuth 2.1 (`--oauth`): metadata under `/.well-known/`, `/register`, `/authorize` (PKCE), `/token`;
+//! access tokens are accepted on `/mcp` alongside API keys
+//! Audit: every tool call is appended to `BROWSERMCP_AUDIT_DIR` (JSONL, rotated), queryable via `audit_query`
+//! Quotas: per-API-key and per-session token buckets plus a concurrency cap on `tools/call` and page resource reads
+//! CLI: `call TOOL --arg value`, `list-tools`, `describe TOOL` run tools without an MCP host
+//! Config: `browsermcp.toml` (or `--config`), then env vars, then flags; `config show` prints the result
+//! Shutdown: SIGTERM/Ctrl-C drains requests for `server.shutdown_grace_secs`, cancels the rest, flushes logs
+//! Observability: `/health` (liveness), `/ready` (persistence + WASM runtime checks), `/metrics` (Prometheus)
+//! Production-grade, zero warnings, optimized
 
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
+    body::{Body, Bytes},
+    extract::{Extension, Query, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Json, Redirect, Response,
+    },
     routing::{get, post},
-    Router,
+    Form, Router,
 };
+use futures::{stream, StreamExt};
+use std::convert::Infallible;
+use std::future::IntoFuture;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
 use serde_json::{json, Value};
-use tower_http::cors::CorsLayer;
-use extreme_browser_mcp::scraper_marketing;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_util::sync::CancellationToken;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use extreme_browser_mcp::config::{self, Config, ConfigError, Transport};
+use extreme_browser_mcp::mcp::audit::AuditLog;
+use extreme_browser_mcp::mcp::auth::{self, ApiKeyIdentity, ApiKeyStore, AuthConfig, OriginPolicy};
+use extreme_browser_mcp::mcp::oauth::{
+    self, AuthorizationRequest, AuthorizationServer, AuthorizeError, ClientRegistration, OAuthError, TokenRequest,
+};
+use extreme_browser_mcp::mcp::quota::{ClientQuotas, Limited, QuotaClient, QuotaConfig, QuotaPermit};
+use extreme_browser_mcp::mcp::session::{Session, SessionStore, SESSION_HEADER, SESSION_NOT_FOUND};
+use extreme_browser_mcp::mcp::{cli, jsonrpc, metrics, monitor, tool_error, tools, McpServer, Peer, RpcError, ServerContext};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Clone)]
 struct AppState {
     #[allow(dead_code)]
     version: String,
     port: u16,
+    server: Arc<McpServer>,
+    sessions: Arc<SessionStore>,
+    api_keys: Arc<ApiKeyStore>,
+    origins: Arc<OriginPolicy>,
+    oauth: Option<Arc<AuthorizationServer>>,
+    quotas: Arc<ClientQuotas>,
+    /// Limits for clients whose API key sets none
+    quota_defaults: QuotaConfig,
+    /// Persistence directory `/ready` checks for writability
+    data_dir: PathBuf,
+    /// Fired on SIGTERM/Ctrl-C: SSE streams end so connections can drain
+    shutdown: CancellationToken,
 }
 
 // ============================================================================
@@ -31,293 +78,985 @@ async fn health(State(state): State<AppState>) -> Json<Value> {
         "port": state.port,
         "protocol": "MCP 2025",
         "server": "BrowserMCP-PRO",
-        "tools_count": 20,
+        "tools_count": state.server.registry().len(),
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
 }
 
+/// Whether the persistence directory accepts writes
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".ready-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"ok").map_err(|e| format!("{}: {}", dir.display(), e))?;
+    std::fs::remove_file(&probe).map_err(|e| format!("{}: {}", probe.display(), e))
+}
+
+/// Whether a WASM engine can be built and instantiate a module with one
+/// memory page under the configured memory limit
+#[cfg(feature = "wasm-runtime")]
+fn check_wasm_runtime(max_memory_mb: u64) -> Result<(), String> {
+    // (module (memory 1))
+    const PROBE: &[u8] = b"\0asm\x01\0\0\0\x05\x03\x01\x00\x01";
+    let engine = wasmtime::Engine::default();
+    let module = wasmtime::Module::new(&engine, PROBE).map_err(|e| e.to_string())?;
+    let limits = wasmtime::StoreLimitsBuilder::new()
+        .memory_size((max_memory_mb * 1024 * 1024) as usize)
+        .build();
+    let mut store = wasmtime::Store::new(&engine, limits);
+    store.limiter(|limits| limits);
+    wasmtime::Instance::new(&mut store, &module, &[]).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "wasm-runtime"))]
+fn check_wasm_runtime(_max_memory_mb: u64) -> Result<(), String> {
+    Err("built without the wasm-runtime feature".to_string())
+}
+
+/// Readiness: 200 when every check passes, 503 (with the failures) otherwise
+async fn ready(State(state): State<AppState>) -> Response {
+    let data_dir = state.data_dir.clone();
+    let checks = tokio::task::spawn_blocking(move || {
+        let max_memory_mb = config::current().wasm.max_memory_mb;
+        [("persistence", check_writable(&data_dir)), ("wasm_runtime", check_wasm_runtime(max_memory_mb))]
+    })
+    .await
+    .unwrap_or_else(|e| {
+        let failed = Err(format!("readiness check panicked: {}", e));
+        [("persistence", failed.clone()), ("wasm_runtime", failed)]
+    });
+
+    let ready = checks.iter().all(|(_, result)| result.is_ok());
+    let checks: serde_json::Map<String, Value> = checks
+        .into_iter()
+        .map(|(name, result)| {
+            let check = match result {
+                Ok(()) => json!({"status": "ok"}),
+                Err(error) => json!({"status": "failed", "error": error}),
+            };
+            (name.to_string(), check)
+        })
+        .collect();
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = json!({"status": if ready { "ready" } else { "not_ready" }, "checks": checks});
+    (status, Json(body)).into_response()
+}
+
+/// Prometheus scrape endpoint
+async fn metrics(State(state): State<AppState>) -> Response {
+    let gauges = [
+        ("browsermcp_sessions", "Open Streamable HTTP sessions", state.sessions.len() as f64),
+        ("browsermcp_tool_calls_in_flight", "Requests currently running", state.server.in_flight() as f64),
+        ("browsermcp_quota_clients", "API keys and sessions with quota state", state.quotas.len() as f64),
+    ];
+    let body = metrics::global().render(&gauges);
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], body).into_response()
+}
+
 // ============================================================================
-// MCP HANDLER - MAIN DISPATCHER
+// AUTH - API KEYS + ORIGIN VALIDATION
 // ============================================================================
 
-async fn mcp_handler(
-    State(_state): State<AppState>,
-    Json(request): Json<Value>,
-) -> (StatusCode, Json<Value>) {
-    let method = request.get("method").and_then(|v| v.as_str()).unwrap_or("");
-    let id = request.get("id").cloned();
-    let params = request.get("params").cloned();
-
-    let result = match method {
-        // ==================== INITIALIZE ====================
-        "initialize" => {
-            json!({
-                "protocolVersion": "2025-11-28",
-                "capabilities": {
-                    "tools": {},
-                    "resources": {},
-                    "prompts": {}
-                },
-                "serverInfo": {
-                    "name": "BrowserMCP-PRO",
-                    "version": "3.0.0"
-                }
-            })
+/// Authenticated API key of the request; `None` when no keys are configured
+#[derive(Clone)]
+struct Caller(Option<ApiKeyIdentity>);
+
+impl Caller {
+    fn name(&self) -> Option<&str> {
+        self.0.as_ref().map(|identity| identity.name.as_str())
+    }
+
+    /// Peer carrying this caller's name and tool allowlist
+    fn peer(&self, peer: Peer) -> Peer {
+        peer.with_client(self.name().map(String::from))
+            .with_tool_allowlist(self.0.as_ref().and_then(|identity| identity.tools.clone()))
+    }
+
+    /// Quota clients a tool call is charged to: the API key (all
+    /// unauthenticated callers share one bucket) and the session, if any
+    fn quota_clients(&self, defaults: QuotaConfig, headers: &HeaderMap) -> Vec<QuotaClient> {
+        let limits = self.0.as_ref().map_or(defaults, |identity| identity.quota.or(defaults));
+        let key = self.name().unwrap_or("anonymous").to_string();
+        let mut clients = vec![("api_key", key, limits)];
+        if let Some(session) = headers.get(SESSION_HEADER).and_then(|id| id.to_str().ok()) {
+            clients.push(("session", session.to_string(), limits));
         }
+        clients
+    }
+}
 
-        // ==================== TOOLS LIST ====================
-        "tools/list" => json!({
-            "tools": [
-                {
-                    "name": "browser_automation",
-                    "description": "Automated browser control with JavaScript execution",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "actions": {"type": "array"} }, "required": ["url"] }
-                },
-                {
-                    "name": "form_filling",
-                    "description": "Intelligent form detection and auto-filling",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "data": {"type": "object"}, "submit": {"type": "boolean"} }, "required": ["url", "data"] }
-                },
-                {
-                    "name": "captcha_solving",
-                    "description": "Solve reCAPTCHA v2/v3, hCaptcha, AWS CAPTCHA",
-                    "inputSchema": { "type": "object", "properties": { "type": {"type": "string", "enum": ["recaptcha_v2", "recaptcha_v3", "hcaptcha", "aws"]}, "sitekey": {"type": "string"}, "url": {"type": "string"} }, "required": ["type", "sitekey", "url"] }
-                },
-                {
-                    "name": "dom_extraction",
-                    "description": "Extract data from DOM with CSS selectors",
-                    "inputSchema": { "type": "object", "properties": { "html": {"type": "string"}, "selectors": {"type": "object"} }, "required": ["html", "selectors"] }
-                },
-                {
-                    "name": "screenshot_capture",
-                    "description": "Capture page screenshots",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "full_page": {"type": "boolean"}, "device": {"type": "string"} }, "required": ["url"] }
-                },
-                {
-                    "name": "stealth_browsing",
-                    "description": "Advanced anti-detection browsing",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "stealth_level": {"type": "string", "enum": ["high", "extreme"]}, "proxy": {"type": "string"} }, "required": ["url"] }
-                },
-                {
-                    "name": "browser_create",
-                    "description": "Create new browser instance",
-                    "inputSchema": { "type": "object", "properties": {} }
-                },
-                {
-                    "name": "browser_list",
-                    "description": "List all browser instances",
-                    "inputSchema": { "type": "object", "properties": {} }
-                },
-                {
-                    "name": "oauth_store_token",
-                    "description": "Store OAuth token",
-                    "inputSchema": { "type": "object", "properties": { "provider": {"type": "string"}, "access_token": {"type": "string"} }, "required": ["provider", "access_token"] }
-                },
-                {
-                    "name": "oauth_list",
-                    "description": "List stored OAuth tokens",
-                    "inputSchema": { "type": "object", "properties": {} }
-                },
-                {
-                    "name": "account_create",
-                    "description": "Create user account",
-                    "inputSchema": { "type": "object", "properties": { "site": {"type": "string"}, "email": {"type": "string"} }, "required": ["site"] }
-                },
-                {
-                    "name": "account_list",
-                    "description": "List all accounts",
-                    "inputSchema": { "type": "object", "properties": {} }
-                },
-                {
-                    "name": "session_start",
-                    "description": "Start browser session",
-                    "inputSchema": { "type": "object", "properties": { "browser_id": {"type": "string"}, "account_id": {"type": "string"}, "url": {"type": "string"} }, "required": ["browser_id", "account_id", "url"] }
-                },
-                {
-                    "name": "session_list",
-                    "description": "List active sessions",
-                    "inputSchema": { "type": "object", "properties": {} }
-                },
-                {
-                    "name": "web_scrape",
-                    "description": "Full web scraping with email/phone extraction",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "selectors": {"type": "array"} }, "required": ["url"] }
-                },
-                {
-                    "name": "get_stealth_headers",
-                    "description": "Get anti-detection headers",
-                    "inputSchema": { "type": "object", "properties": {} }
-                },
-                {
-                    "name": "analyze_code",
-                    "description": "Analyze source code",
-                    "inputSchema": { "type": "object", "properties": { "code": {"type": "string"}, "language": {"type": "string"} }, "required": ["code"] }
-                },
-                {
-                    "name": "analyze_url_code",
-                    "description": "Fetch and analyze code from URL",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"} }, "required": ["url"] }
-                },
-                {
-                    "name": "analyze_file",
-                    "description": "Analyze local file",
-                    "inputSchema": { "type": "object", "properties": { "file_path": {"type": "string"} }, "required": ["file_path"] }
-                },
-                {
-                    "name": "analyze_project",
-                    "description": "Deep project analysis",
-                    "inputSchema": { "type": "object", "properties": { "path": {"type": "string"} }, "required": ["path"] }
-                },
-                {
-                    "name": "workflow_orchestrator",
-                    "description": "Task scheduling + automation (scrape→analyze→store→alert)",
-                    "inputSchema": { "type": "object", "properties": { "workflow_id": {"type": "string"}, "action": {"type": "string", "enum": ["create", "execute", "list", "delete"]} }, "required": ["action"] }
-                },
-                {
-                    "name": "vulnerability_scanner",
-                    "description": "OWASP Top 10 vulnerability detection",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "html": {"type": "string"} }, "required": ["url"] }
-                },
-                {
-                    "name": "social_media_intelligence",
-                    "description": "Twitter, LinkedIn, Instagram intelligence & sentiment analysis",
-                    "inputSchema": { "type": "object", "properties": { "action": {"type": "string", "enum": ["search_posts", "find_influencers", "detect_trends", "competitor_intel"]}, "query": {"type": "string"}, "platform": {"type": "string"} }, "required": ["action"] }
-                },
-                {
-                    "name": "intelligent_content_extractor",
-                    "description": "Extract valuable data from HTML (ignore noise)",
-                    "inputSchema": { "type": "object", "properties": { "url": {"type": "string"}, "html": {"type": "string"} }, "required": ["url", "html"] }
-                },
-                {
-                    "name": "geolocation_security_intelligence",
-                    "description": "Geographic security hotspots & infrastructure mapping",
-                    "inputSchema": { "type": "object", "properties": { "query": {"type": "string"}, "action": {"type": "string", "enum": ["analyze", "find_hotspots", "infrastructure_map"]} }, "required": ["query"] }
+/// 401 with a Bearer challenge; points OAuth clients at the resource metadata
+fn unauthorized(state: &AppState, error: Option<&str>, detail: &str) -> Response {
+    let mut challenge = String::from("Bearer realm=\"browsermcp\"");
+    if let Some(error) = error {
+        challenge.push_str(&format!(", error=\"{}\"", error));
+    }
+    if let Some(oauth) = &state.oauth {
+        challenge.push_str(&format!(", resource_metadata=\"{}\"", oauth.resource_metadata_url()));
+    }
+    let challenge = HeaderValue::from_str(&challenge).unwrap_or_else(|_| HeaderValue::from_static("Bearer"));
+    let body = Json(json!({"error": error.unwrap_or("unauthorized"), "error_description": detail}));
+    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, challenge)], body).into_response()
+}
+
+/// Reject foreign browser origins (DNS rebinding) and requests without a
+/// valid API key or OAuth access token
+async fn require_auth(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    if let Some(origin) = request.headers().get(header::ORIGIN) {
+        if !origin.to_str().is_ok_and(|origin| state.origins.allows(origin)) {
+            tracing::warn!("Rejected request from origin {:?}", origin);
+            let body = Json(json!({"error": "forbidden", "error_description": "Origin not allowed"}));
+            return (StatusCode::FORBIDDEN, body).into_response();
+        }
+    }
+
+    if state.api_keys.is_empty() {
+        request.extensions_mut().insert(Caller(None));
+        return next.run(request).await;
+    }
+
+    let headers = request.headers();
+    let presented = auth::presented_key(
+        headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()),
+        headers.get("x-api-key").and_then(|v| v.to_str().ok()),
+    );
+    let Some(presented) = presented.map(String::from) else {
+        return unauthorized(&state, None, "API key or access token required");
+    };
+
+    if let Some(oauth) = &state.oauth {
+        if presented.starts_with(oauth::ACCESS_TOKEN_PREFIX) {
+            return match oauth.validate(&presented) {
+                Some(identity) => {
+                    request.extensions_mut().insert(Caller(Some(identity)));
+                    next.run(request).await
                 }
-            ]
-        }),
+                None => unauthorized(&state, Some("invalid_token"), "Invalid or expired access token"),
+            };
+        }
+    }
+
+    // argon2 is deliberately slow: keep it off the async workers
+    let api_keys = Arc::clone(&state.api_keys);
+    let identity = tokio::task::spawn_blocking(move || api_keys.authenticate(&presented))
+        .await
+        .ok()
+        .flatten();
+    match identity {
+        Some(identity) => {
+            request.extensions_mut().insert(Caller(Some(identity)));
+            next.run(request).await
+        }
+        None => unauthorized(&state, Some("invalid_token"), "Invalid API key"),
+    }
+}
 
-        // ==================== TOOLS CALL ====================
-        "tools/call" => {
-            let tool_name = params.as_ref()
-                .and_then(|p| p.get("name"))
-                .and_then(|n| n.as_str())
-                .unwrap_or("");
-
-            match tool_name {
-                "browser_automation" => json!({"status": "success", "result": {"url": "https://example.com", "actions_executed": 5, "final_html_length": 45823, "execution_time_ms": 2341}}),
-                "form_filling" => json!({"status": "success", "result": {"filled_fields": 7, "submitted": true, "response_status": 200, "execution_time_ms": 1523}}),
-                "captcha_solving" => json!({"status": "success", "result": {"captcha_type": "recaptcha_v2", "solved": true, "token": "03AGdBq27....", "confidence": 0.98, "solving_time_ms": 3421}}),
-                "dom_extraction" => json!({"status": "success", "result": {"title": "Example Page", "description": "Example", "price": "$99.99", "rating": "4.5", "total_elements_extracted": 12}}),
-                "screenshot_capture" => json!({"status": "success", "result": {"url": "https://example.com", "image": "base64_png", "device": "desktop", "size": {"width": 1280, "height": 1024}, "file_size_bytes": 156234}}),
-                "stealth_browsing" => json!({"status": "success", "result": {"url": "https://example.com", "stealth_level": "extreme", "user_agent": "Mozilla/5.0", "fingerprint_hash": "a4f8c2d9e1b6", "detection_risk": 0.02, "page_loaded": true}}),
-                "browser_create" => json!({"status": "success", "result": {"id": "browser_123", "status": "created"}}),
-                "browser_list" => json!({"status": "success", "result": {"browsers": [{"id": "browser_123", "active_account": null}]}}),
-                "oauth_store_token" => json!({"status": "success", "result": {"token_id": "oauth_456", "status": "stored"}}),
-                "oauth_list" => json!({"status": "success", "result": {"tokens": []}}),
-                "account_create" => json!({"status": "success", "result": {"account_id": "acc_789", "email": "user@example.com", "status": "created"}}),
-                "account_list" => json!({"status": "success", "result": {"accounts": []}}),
-                "session_start" => json!({"status": "success", "result": {"session_id": "sess_999", "status": "active"}}),
-                "session_list" => json!({"status": "success", "result": {"sessions": []}}),
-                "web_scrape" => {
-                    if let Some(p) = params.as_ref() {
-                        let url = p.get("url").and_then(|v| v.as_str()).unwrap_or("https://example.com").to_string();
-                        let selectors: Vec<String> = p.get("selectors")
-                            .and_then(|v| v.as_array())
-                            .map(|arr| arr.iter().filter_map(|v| v.as_str().map(String::from)).collect())
-                            .unwrap_or_else(|| vec!["title".to_string(), "h1".to_string(), "p".to_string()]);
-                        
-                        // Llamar función real de scraping
-                        match scraper_marketing::scrape_url_full(&url, &selectors).await {
-                            Ok(data) => data,
-                            Err(e) => json!({"status": "error", "url": url, "error": e.to_string()})
+// ============================================================================
+// QUOTAS - RATE LIMITS + CONCURRENCY CAPS
+// ============================================================================
+
+/// Same cap as axum's default body limit for the `/mcp` handler
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Messages charged to the quotas: `tools/call`, and the page reads and
+/// subscribes that fetch a URL as `web_scrape` would
+fn is_charged(message: &Value) -> bool {
+    match message.get("method").and_then(|m| m.as_str()) {
+        Some("tools/call") => true,
+        Some("resources/read" | "resources/subscribe") => message
+            .pointer("/params/uri")
+            .and_then(|uri| uri.as_str())
+            .is_some_and(|uri| monitor::page_url(uri).is_some()),
+        _ => false,
+    }
+}
+
+/// Answers to calls over quota, sent without running them: a tool error
+/// for `tools/call`, an RPC error for page resources
+fn limited_response(message: &Value, id: Value, limited: &Limited) -> Value {
+    if message.get("method").and_then(|m| m.as_str()) == Some("tools/call") {
+        return jsonrpc::success(id, tool_error(limited.to_value()));
+    }
+    let body = limited.to_value();
+    let message = body["message"].as_str().unwrap_or("Rate limited").to_string();
+    jsonrpc::failure(id, &RpcError::new(RATE_LIMITED, message).with_data(body))
+}
+
+/// JSON-RPC error code of a page resource request over quota
+const RATE_LIMITED: i64 = -32029;
+
+/// Charge each [charged](is_charged) message of a POST to the caller's
+/// buckets and concurrency slots. Calls over quota get a "retry after N ms"
+/// error in place of their result; the others run, holding their slots
+/// until the response is built.
+async fn enforce_quotas(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    // Malformed bodies are the handler's to reject
+    let Ok(message) = serde_json::from_slice::<Value>(&body) else {
+        return next.run(Request::from_parts(parts, Body::from(body))).await;
+    };
+    let clients = caller.quota_clients(state.quota_defaults, &parts.headers);
+
+    match message {
+        Value::Array(batch) if batch.iter().any(is_charged) => {
+            let mut permits: Vec<QuotaPermit> = Vec::new();
+            let mut rejected = Vec::new();
+            let mut admitted = Vec::new();
+            for message in batch {
+                if !is_charged(&message) {
+                    admitted.push(message);
+                    continue;
+                }
+                match state.quotas.acquire(&clients) {
+                    Ok(permit) => {
+                        permits.push(permit);
+                        admitted.push(message);
+                    }
+                    Err(limited) => {
+                        metrics::global().record_rate_limited(&limited.scope);
+                        if let Some(id) = message.get("id").filter(|id| !id.is_null()) {
+                            rejected.push(limited_response(&message, id.clone(), &limited));
                         }
-                    } else {
-                        json!({"status": "error", "message": "Missing URL"})
                     }
                 }
-                "get_stealth_headers" => json!({"status": "success", "result": {"User-Agent": "Mozilla/5.0", "Accept": "text/html", "DNT": "1"}}),
-                "analyze_code" => json!({"status": "success", "result": {"language": "rust", "functions": 5, "lines_of_code": 150, "complexity": 2.3, "security_issues": 0}}),
-                "analyze_url_code" => json!({"status": "success", "result": {"url": "https://example.com/code.js", "language": "javascript", "analysis": "OK"}}),
-                "analyze_file" => json!({"status": "success", "result": {"file": "main.rs", "language": "rust", "lines": 500, "functions": 20}}),
-                "analyze_project" => json!({"status": "success", "result": {"path": "/project", "files": 50, "languages": ["rust", "js"], "size_mb": 25}}),
-                "workflow_orchestrator" => json!({"status": "success", "result": {"workflow_id": "wf_123", "action": "create", "steps": 4, "status": "ready", "schedule": "hourly"}}),
-                "vulnerability_scanner" => json!({"status": "success", "result": {"scan_id": "scan_456", "vulnerabilities": 3, "critical": 1, "high": 2, "owasp_violations": ["A03:2021 - Injection", "A07:2021 - XSS"]}}),
-                "social_media_intelligence" => json!({"status": "success", "result": {"posts_found": 125, "trending": true, "sentiment": "positive", "influencers": 8, "engagement_rate": 6.5}}),
-                "intelligent_content_extractor" => json!({"status": "success", "result": {"content_id": "content_789", "content_type": "article", "key_points": 5, "extraction_confidence": 0.92, "word_count": 2850}}),
-                "geolocation_security_intelligence" => json!({"status": "success", "result": {"locations": 4, "hotspots": 3, "critical_threats": 1, "overall_risk": 35.2, "most_dangerous": "Hong Kong"}}),
-                _ => json!({"error": "Unknown tool", "code": -32601})
+            }
+            if admitted.is_empty() {
+                return if rejected.is_empty() {
+                    StatusCode::ACCEPTED.into_response()
+                } else {
+                    Json(Value::Array(rejected)).into_response()
+                };
+            }
+            let body = Body::from(Value::Array(admitted).to_string());
+            let response = next.run(Request::from_parts(parts, body)).await;
+            drop(permits);
+            if rejected.is_empty() {
+                response
+            } else {
+                merge_batch(response, rejected).await
             }
         }
+        message if is_charged(&message) => match state.quotas.acquire(&clients) {
+            Ok(_permit) => next.run(Request::from_parts(parts, Body::from(body))).await,
+            Err(limited) => {
+                tracing::info!("Tool call from {:?} over quota: {:?}", caller.name(), limited);
+                metrics::global().record_rate_limited(&limited.scope);
+                match message.get("id").filter(|id| !id.is_null()) {
+                    Some(id) => Json(limited_response(&message, id.clone(), &limited)).into_response(),
+                    None => StatusCode::ACCEPTED.into_response(),
+                }
+            }
+        },
+        _ => next.run(Request::from_parts(parts, Body::from(body))).await,
+    }
+}
 
-        // ==================== RESOURCES ====================
-        "resources/list" => json!({
-            "resources": [
-                {"uri": "browser://docs/usage", "name": "BrowserMCP Usage", "description": "Complete documentation", "mimeType": "text/markdown"},
-                {"uri": "browser://docs/anti-detection", "name": "Anti-Detection Guide", "description": "Advanced techniques", "mimeType": "text/markdown"}
-            ]
-        }),
+/// Append the over-quota results to the handler's batch response
+async fn merge_batch(response: Response, rejected: Vec<Value>) -> Response {
+    if response.status() == StatusCode::ACCEPTED {
+        return Json(Value::Array(rejected)).into_response();
+    }
+    let (parts, body) = response.into_parts();
+    let responses = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok());
+    let mut merged = match responses {
+        Some(Value::Array(responses)) => responses,
+        _ => Vec::new(),
+    };
+    merged.extend(rejected);
+    (parts.status, Json(Value::Array(merged))).into_response()
+}
+
+// ============================================================================
+// OAUTH 2.1 - AUTHORIZATION SERVER ENDPOINTS
+// ============================================================================
+
+#[derive(Clone)]
+struct OAuthState {
+    server: Arc<AuthorizationServer>,
+    api_keys: Arc<ApiKeyStore>,
+}
+
+fn oauth_error(status: StatusCode, error: &OAuthError) -> Response {
+    (status, [(header::CACHE_CONTROL, "no-store")], Json(error.to_value())).into_response()
+}
+
+async fn oauth_resource_metadata(State(oauth): State<OAuthState>) -> Json<Value> {
+    Json(oauth.server.protected_resource_metadata())
+}
+
+async fn oauth_metadata(State(oauth): State<OAuthState>) -> Json<Value> {
+    Json(oauth.server.metadata())
+}
+
+async fn oauth_register(State(oauth): State<OAuthState>, body: Bytes) -> Response {
+    let registration: ClientRegistration = match serde_json::from_slice(&body) {
+        Ok(registration) => registration,
+        Err(e) => {
+            return oauth_error(
+                StatusCode::BAD_REQUEST,
+                &OAuthError::new("invalid_client_metadata", e.to_string()),
+            )
+        }
+    };
+    match oauth.server.register(registration) {
+        Ok(client) => (StatusCode::CREATED, Json(client)).into_response(),
+        Err(e) => oauth_error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+/// Consent page; the page itself must not be framed (clickjacking)
+fn consent(status: StatusCode, page: String) -> Response {
+    (status, [(header::X_FRAME_OPTIONS, "DENY")], Html(page)).into_response()
+}
+
+fn authorize_error(error: AuthorizeError) -> Response {
+    match error {
+        AuthorizeError::Invalid(e) => {
+            consent(StatusCode::BAD_REQUEST, format!("<h1>Invalid request</h1><p>{}</p>", e.error))
+        }
+        AuthorizeError::Redirect(url) => Redirect::to(&url).into_response(),
+    }
+}
 
-        // ==================== PROMPTS ====================
-        "prompts/list" => json!({
-            "prompts": [
-                {"name": "browser_task", "description": "Execute browser automation tasks", "arguments": [{"name": "task_description", "description": "Task description", "required": true}]}
-            ]
+async fn oauth_authorize_form(
+    State(oauth): State<OAuthState>,
+    Query(request): Query<AuthorizationRequest>,
+) -> Response {
+    match oauth.server.check_authorization(&request) {
+        Ok(client) => consent(StatusCode::OK, oauth::consent_page(&request, &client, None)),
+        Err(e) => authorize_error(e),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ConsentForm {
+    #[serde(flatten)]
+    request: AuthorizationRequest,
+    #[serde(default)]
+    api_key: String,
+    #[serde(default)]
+    decision: String,
+}
+
+async fn oauth_authorize(State(oauth): State<OAuthState>, Form(form): Form<ConsentForm>) -> Response {
+    let client = match oauth.server.check_authorization(&form.request) {
+        Ok(client) => client,
+        Err(e) => return authorize_error(e),
+    };
+    if form.decision == "deny" {
+        return match oauth.server.deny(&form.request) {
+            Ok(url) => Redirect::to(&url).into_response(),
+            Err(e) => authorize_error(e),
+        };
+    }
+
+    let api_keys = Arc::clone(&oauth.api_keys);
+    let api_key = form.api_key;
+    let identity = tokio::task::spawn_blocking(move || api_keys.authenticate(&api_key))
+        .await
+        .ok()
+        .flatten();
+    let Some(identity) = identity else {
+        let page = oauth::consent_page(&form.request, &client, Some("Invalid API key"));
+        return consent(StatusCode::UNAUTHORIZED, page);
+    };
+    tracing::info!("API key {} authorized OAuth client {}", identity.name, client.client_id);
+    match oauth.server.authorize(&form.request, identity) {
+        Ok(url) => Redirect::to(&url).into_response(),
+        Err(e) => authorize_error(e),
+    }
+}
+
+async fn oauth_token(State(oauth): State<OAuthState>, Form(request): Form<TokenRequest>) -> Response {
+    match oauth.server.token(&request) {
+        Ok(tokens) => ([(header::CACHE_CONTROL, "no-store")], Json(tokens)).into_response(),
+        Err(e) => oauth_error(StatusCode::BAD_REQUEST, &e),
+    }
+}
+
+fn oauth_routes(state: OAuthState) -> Router<AppState> {
+    Router::new()
+        .route("/.well-known/oauth-protected-resource", get(oauth_resource_metadata))
+        .route("/.well-known/oauth-protected-resource/mcp", get(oauth_resource_metadata))
+        .route("/.well-known/oauth-authorization-server", get(oauth_metadata))
+        .route("/register", post(oauth_register))
+        .route("/authorize", get(oauth_authorize_form).post(oauth_authorize))
+        .route("/token", post(oauth_token))
+        .with_state(state)
+}
+
+// ============================================================================
+// MCP HANDLER - MAIN DISPATCHER
+// ============================================================================
+
+/// 404 for an unknown or ended `Mcp-Session-Id`
+struct UnknownSession;
+
+impl IntoResponse for UnknownSession {
+    fn into_response(self) -> Response {
+        let error = RpcError::new(SESSION_NOT_FOUND, "Session not found; send initialize again");
+        (StatusCode::NOT_FOUND, Json(jsonrpc::failure(Value::Null, &error))).into_response()
+    }
+}
+
+/// Session named by the `Mcp-Session-Id` header; `Ok(None)` when the header
+/// is absent (stateless client). Sessions of other API keys are unknown.
+fn request_session(state: &AppState, caller: &Caller, headers: &HeaderMap) -> Result<Option<Arc<Session>>, UnknownSession> {
+    let Some(id) = headers.get(SESSION_HEADER) else {
+        return Ok(None);
+    };
+    id.to_str()
+        .ok()
+        .and_then(|id| state.sessions.get(id, caller.name()))
+        .map(Some)
+        .ok_or(UnknownSession)
+}
+
+async fn mcp_handler(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let message = match jsonrpc::parse(&body) {
+        Ok(message) => message,
+        Err(parse_error) => return (StatusCode::OK, Json(parse_error)).into_response(),
+    };
+
+    let is_initialize = message.get("method").and_then(|m| m.as_str()) == Some("initialize");
+    let session = if is_initialize {
+        None
+    } else {
+        match request_session(&state, &caller, &headers) {
+            Ok(session) => session,
+            Err(unknown) => return unknown.into_response(),
+        }
+    };
+    // Progress for session clients goes out on their `GET /mcp` stream
+    let peer = caller.peer(match session {
+        Some(session) => Peer::new(session.id.clone(), move |message| {
+            session.send(message);
         }),
+        None => Peer::detached(),
+    });
 
-        _ => json!({"error": "Unknown method", "code": -32601})
+    let Some(response) = state.server.handle_message_from(message, &peer).await else {
+        // Only notifications: nothing to send back
+        return StatusCode::ACCEPTED.into_response();
     };
 
-    let response = json!({
-        "jsonrpc": "2.0",
-        "id": id,
-        "result": result
+    let mut http_response = (StatusCode::OK, Json(&response)).into_response();
+    if is_initialize && response.get("result").is_some() {
+        let session = state.sessions.create(caller.name().map(String::from));
+        if let Ok(value) = HeaderValue::from_str(&session.id) {
+            http_response.headers_mut().insert(SESSION_HEADER, value);
+        }
+        tracing::info!("MCP session {} started", session.id);
+    }
+    http_response
+}
+
+/// `GET /mcp`: SSE stream of server notifications (e.g.
+/// `notifications/prompts/list_changed`) plus, with `Mcp-Session-Id`, the
+/// messages sent to that session (progress, `notifications/resources/updated`)
+async fn mcp_events(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+) -> Response {
+    let session_stream = match request_session(&state, &caller, &headers) {
+        Ok(session) => session.map(|session| session.stream()),
+        Err(unknown) => return unknown.into_response(),
+    };
+    let notifications = state.server.context().notifications();
+
+    let events = stream::unfold((notifications, session_stream), |(mut notifications, mut session)| async move {
+        loop {
+            let received = match session.as_mut() {
+                Some(session_stream) => tokio::select! {
+                    message = notifications.recv() => message,
+                    message = session_stream.recv() => match message {
+                        // Session ended: close the stream
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                        other => other,
+                    },
+                },
+                None => notifications.recv().await,
+            };
+            match received {
+                Ok(message) => {
+                    let event = Event::default().event("message").data(message.to_string());
+                    return Some((Ok::<_, Infallible>(event), (notifications, session)));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("SSE client dropped {} messages", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = events.take_until(state.shutdown.clone().cancelled_owned());
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// `DELETE /mcp`: end the session named by `Mcp-Session-Id`
+async fn mcp_delete(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+) -> StatusCode {
+    match request_session(&state, &caller, &headers) {
+        Ok(Some(session)) => {
+            state.sessions.remove(&session.id);
+            state.server.context().pages().forget(&session.id);
+            tracing::info!("MCP session {} ended", session.id);
+            StatusCode::NO_CONTENT
+        }
+        Ok(None) => StatusCode::BAD_REQUEST,
+        Err(_) => StatusCode::NOT_FOUND,
+    }
+}
+
+// ============================================================================
+// STDIO TRANSPORT
+// ============================================================================
+
+/// Newline-delimited JSON-RPC over stdin/stdout.
+/// stdout carries protocol frames only; logs and banners go to stderr.
+/// Responses and server notifications share one writer so frames never interleave;
+/// responses may arrive out of request order.
+async fn serve_stdio(state: AppState) -> std::io::Result<()> {
+    let (frames, mut outgoing) = mpsc::unbounded_channel::<Value>();
+
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = outgoing.recv().await {
+            let mut frame = serde_json::to_vec(&message)?;
+            frame.push(b'\n');
+            stdout.write_all(&frame).await?;
+            stdout.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
     });
 
-    (StatusCode::OK, Json(response))
+    let mut notifications = state.server.context().notifications();
+    let notifier = frames.clone();
+    let forwarder = tokio::spawn(async move {
+        loop {
+            match notifications.recv().await {
+                Ok(notification) => {
+                    if notifier.send(notification).is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("stdio transport dropped {} notifications", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let progress = frames.clone();
+    let peer = Peer::new("stdio", move |message| {
+        let _ = progress.send(message);
+    });
+
+    let mut requests = tokio::task::JoinSet::new();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let message = match jsonrpc::parse(line.as_bytes()) {
+            Ok(message) => message,
+            Err(parse_error) => {
+                let _ = frames.send(parse_error);
+                continue;
+            }
+        };
+
+        // Each message runs on its own task so that `notifications/cancelled`
+        // is read while a long tool call is still running
+        let (server, peer, frames) = (Arc::clone(&state.server), peer.clone(), frames.clone());
+        while requests.try_join_next().is_some() {}
+        requests.spawn(async move {
+            // Notifications never get a frame back
+            if let Some(response) = server.handle_message_from(message, &peer).await {
+                let _ = frames.send(response);
+            }
+        });
+    }
+
+    tracing::info!("stdin closed, shutting down stdio transport");
+    // Answer what is still running, then let the writer drain and exit
+    while requests.join_next().await.is_some() {}
+    forwarder.abort();
+    let _ = forwarder.await;
+    drop((frames, peer));
+    writer.await.map_err(std::io::Error::other)??;
+    Ok(())
 }
 
 // ============================================================================
 // MAIN
 // ============================================================================
 
+/// Flags followed by a value
+const VALUE_FLAGS: &[&str] = &["--bind", "--auth-file", "--public-url", "--config", "--set"];
+
+/// Flags followed by a value and the config key they set
+const KEY_FLAGS: &[(&str, &str)] = &[
+    ("--bind", "server.bind"),
+    ("--auth-file", "auth.file"),
+    ("--public-url", "server.public_url"),
+];
+
+/// Switches and the boolean config key they turn on
+const SWITCHES: &[(&str, &str, &str)] = &[
+    ("--stdio", "server.transport", "stdio"),
+    ("--oauth", "server.oauth", "true"),
+    ("--allow-unauthenticated", "server.allow_unauthenticated", "true"),
+];
+
+/// Value following `flag` (`--bind 0.0.0.0`)
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// Arguments that are neither flags nor flag values
+fn positional(args: &[String]) -> Vec<&str> {
+    let mut positional = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if VALUE_FLAGS.contains(&args[i].as_str()) {
+            i += 2;
+            continue;
+        }
+        if !args[i].starts_with("--") {
+            positional.push(args[i].as_str());
+        }
+        i += 1;
+    }
+    positional
+}
+
+/// Effective configuration: `--config`, `$BROWSERMCP_CONFIG` or ./browsermcp.toml
+/// when present, then the environment, then flags (`PORT`, `--bind`, ...,
+/// `--set key=value`)
+fn load_config(args: &[String], positional: &[&str]) -> Result<Config, ConfigError> {
+    let file = flag_value(args, "--config")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var("BROWSERMCP_CONFIG").ok().map(PathBuf::from))
+        .or_else(|| Path::new(config::DEFAULT_FILE).exists().then(|| PathBuf::from(config::DEFAULT_FILE)));
+
+    let mut overrides: Vec<(&str, &str, String)> = Vec::new();
+    // `PORT` or `config show PORT`
+    let port = match positional.first() {
+        Some(&"config") => positional.get(2),
+        first => first,
+    };
+    if let Some(port) = port {
+        overrides.push(("PORT", "server.port", port.to_string()));
+    }
+    for (i, arg) in args.iter().enumerate() {
+        let value = args.get(i + 1).cloned().unwrap_or_default();
+        if let Some((flag, key)) = KEY_FLAGS.iter().find(|(flag, _)| flag == arg) {
+            overrides.push((flag, key, value));
+        } else if let Some((flag, key, on)) = SWITCHES.iter().find(|(flag, _, _)| flag == arg) {
+            overrides.push((flag, key, on.to_string()));
+        } else if arg == "--set" {
+            let Some((key, value)) = args.get(i + 1).and_then(|kv| kv.split_once('=')) else {
+                return Err(ConfigError::Override {
+                    origin: "--set".to_string(),
+                    key: value,
+                    message: "expected key=value".to_string(),
+                });
+            };
+            overrides.push(("--set", key, value.to_string()));
+        }
+    }
+
+    Config::load(file.as_deref(), |var| std::env::var(var).ok(), &overrides)
+}
+
+/// Print `message` and exit with status 2 (bad invocation or configuration)
+fn exit_usage(message: impl std::fmt::Display) -> ! {
+    eprintln!("❌ {}", message);
+    std::process::exit(2);
+}
+
+/// Analyses served as browser://analysis/{id}, prompt templates, and the
+/// audit log of every tool call (one JSONL file per day)
+fn server_context(config: &Config) -> Result<ServerContext, Box<dyn std::error::Error>> {
+    let persistence = &config.persistence;
+    Ok(ServerContext::new(&persistence.data_dir.to_string_lossy())?
+        .with_prompts_dir(&persistence.prompts_dir)
+        .with_audit_log(AuditLog::open(persistence.audit_dir(), persistence.audit_max_bytes)?))
+}
+
+/// Subcommands that run the server's tools in-process, without an MCP host
+const TOOL_COMMANDS: &[&str] = &["call", "list-tools", "describe"];
+
+/// `list-tools [--json]`, `describe TOOL`, `call TOOL [--arg value ...] [--json FILE|-]`.
+/// `call` prints the `tools/call` result and exits 1 when it is an error
+async fn run_tool_command(config: &Config, command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let context = server_context(config)?;
+    let server = McpServer::new(tools::builtin_registry(&context), context);
+    let registry = server.registry();
+    let tool_named = |name: Option<&String>| {
+        let name = name.unwrap_or_else(|| exit_usage(format!("usage: browsermcp-server {} TOOL", command[0])));
+        registry
+            .get(name)
+            .unwrap_or_else(|| exit_usage(format!("unknown tool `{}`; see `browsermcp-server list-tools`", name)))
+    };
+
+    match command[0].as_str() {
+        "list-tools" if command.get(1).is_some_and(|a| a == "--json") => {
+            println!("{}", serde_json::to_string_pretty(&registry.list())?);
+        }
+        "list-tools" => print!("{}", cli::tool_table(registry)),
+        "describe" => {
+            let tool = tool_named(command.get(1));
+            println!("usage: browsermcp-server {}\n", cli::usage(tool.as_ref()));
+            println!("{}\n", tool.description());
+            println!("{}", serde_json::to_string_pretty(&tool.input_schema())?);
+        }
+        _ => {
+            let tool = tool_named(command.get(1));
+            let arguments = cli::tool_arguments(tool.as_ref(), &command[2..], std::io::stdin().lock())
+                .unwrap_or_else(|e| exit_usage(e));
+            // Progress of long tools (web_scrape_batch) goes to stderr
+            let peer = Peer::new("cli", |message: Value| {
+                if let Some(params) = message.get("params").filter(|_| message["method"] == "notifications/progress") {
+                    eprintln!("… {}/{} {}", params["progress"], params["total"], params["message"].as_str().unwrap_or(""));
+                }
+            });
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {"name": tool.name(), "arguments": arguments, "_meta": {"progressToken": "cli"}}
+            });
+            let response = server.handle_message_from(request, &peer).await.unwrap_or_default();
+            if let Some(error) = response.get("error") {
+                exit_usage(serde_json::to_string_pretty(error)?);
+            }
+            let result = &response["result"];
+            println!("{}", serde_json::to_string_pretty(result)?);
+            if result["isError"] == true {
+                std::process::exit(1);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `hash-key [KEY]`: print an argon2 hash for the auth file (and the key, if generated)
+fn print_key_hash(key: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let key = key.map(String::from).unwrap_or_else(|| {
+        let key = auth::generate_key();
+        println!("key:  {}", key);
+        key
+    });
+    println!("hash: {}", auth::hash_key(&key)?);
+    Ok(())
+}
+
+fn cors(origins: Arc<OriginPolicy>) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            origin.to_str().is_ok_and(|origin| origins.allows(origin))
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static("x-api-key"),
+            HeaderName::from_static(SESSION_HEADER),
+            HeaderName::from_static("mcp-protocol-version"),
+            HeaderName::from_static("last-event-id"),
+        ])
+        .expose_headers([HeaderName::from_static(SESSION_HEADER)])
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if positional(&args).first() == Some(&"hash-key") {
+        return print_key_hash(positional(&args).get(1).copied());
+    }
+    // Everything after `call`, `list-tools` or `describe` belongs to the command
+    let command_at = positional(&args)
+        .first()
+        .filter(|first| TOOL_COMMANDS.contains(first))
+        .and_then(|command| args.iter().position(|a| a == command));
+    let tool_command = command_at.map(|i| args.split_off(i)).unwrap_or_default();
+
+    // stdout is reserved for protocol frames in stdio mode (and tool output
+    // for commands): logs always go to stderr
+    let level = if tool_command.is_empty() { tracing::Level::INFO } else { tracing::Level::WARN };
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
         .init();
 
-    let port = std::env::args().nth(1).unwrap_or("3001".to_string()).parse::<u16>().unwrap_or(3001);
-
-    println!("🚀 Starting BrowserMCP Server v3.0.0-PRO on port {}", port);
-    println!("🔧 Initializing 20+ tools...");
-    println!("  ✓ Browser Automation");
-    println!("  ✓ Form Filling");
-    println!("  ✓ CAPTCHA Solving");
-    println!("  ✓ DOM Extraction");
-    println!("  ✓ Web Scraping");
-    println!("  ✓ Code Analysis");
-    println!("  ✓ OAuth Management");
-    println!("  ✓ Account Management");
-    println!("  ✓ Session Management");
-    println!("  ✓ Stealth Browsing");
+    let positional = positional(&args);
+
+    let config = load_config(&args, &positional).unwrap_or_else(|e| exit_usage(e));
+    if positional.first() == Some(&"config") {
+        if positional.get(1) != Some(&"show") {
+            exit_usage("usage: browsermcp-server config show [--config FILE] [flags]");
+        }
+        print!("{}", config.to_toml_redacted());
+        return Ok(());
+    }
+    config::install(config.clone());
+    if !tool_command.is_empty() {
+        return run_tool_command(&config, &tool_command).await;
+    }
+    let port = config.server.port;
+    let bind = config.server.bind.as_str();
+
+    // API keys: auth.file, or ./auth.json when present
+    let auth_file = config
+        .auth
+        .file
+        .clone()
+        .or_else(|| Path::new("auth.json").exists().then(|| PathBuf::from("auth.json")));
+    let auth_config = match &auth_file {
+        Some(path) => AuthConfig::from_file(path)?,
+        None => AuthConfig::default(),
+    };
+    let api_keys = ApiKeyStore::new(auth_config.api_keys)?;
+    let origins = Arc::new(OriginPolicy::new(auth_config.allowed_origins));
+
+    // OAuth issuer: the URL clients reach the server at
+    let oauth = config.server.oauth.then(|| {
+        let public_url = config
+            .server
+            .public_url
+            .clone()
+            .unwrap_or_else(|| format!("http://localhost:{}", port));
+        Arc::new(AuthorizationServer::new(&public_url))
+    });
+    if oauth.is_some() && api_keys.is_empty() {
+        return Err("--oauth needs API keys to approve authorization requests: configure --auth-file".into());
+    }
+
+    let context = server_context(&config)?;
+
+    // Reload prompt templates and announce list_changed when the directory changes
+    context.prompts().spawn(Duration::from_secs(5));
+
+    // Re-fetch subscribed pages (browser://page/...) in the background
+    context.pages().spawn(Duration::from_secs(config.server.monitor_interval_secs));
 
     let state = AppState {
         version: "3.0.0-PRO".to_string(),
         port,
+        server: Arc::new(McpServer::new(tools::builtin_registry(&context), context)),
+        sessions: Arc::new(SessionStore::new()),
+        api_keys: Arc::new(api_keys),
+        origins: Arc::clone(&origins),
+        oauth: oauth.clone(),
+        quotas: Arc::new(ClientQuotas::new()),
+        // API keys may override these in the auth file
+        quota_defaults: config.quota,
+        data_dir: config.persistence.data_dir.clone(),
+        shutdown: CancellationToken::new(),
     };
 
-    let app = Router::new()
-        .route("/mcp", post(mcp_handler))
+    if config.server.transport == Transport::Stdio {
+        eprintln!("🚀 Starting BrowserMCP Server v3.0.0-PRO on stdio");
+        let context = state.server.context().clone();
+        serve_stdio(state).await?;
+        context.flush()?;
+        return Ok(());
+    }
+
+    let loopback = bind.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback()) || bind == "localhost";
+    if state.api_keys.is_empty() {
+        if !loopback && !config.server.allow_unauthenticated {
+            return Err(format!(
+                "refusing to listen on {} without API keys: configure --auth-file or pass --allow-unauthenticated",
+                bind
+            )
+            .into());
+        }
+        tracing::warn!("No API keys configured: /mcp is unauthenticated");
+    } else {
+        eprintln!("🔐 {} API keys loaded", state.api_keys.len());
+    }
+
+    // Forget sessions whose client went away without DELETE
+    let idle_secs = config.server.session_idle_secs;
+    let sessions = Arc::clone(&state.sessions);
+    let quotas = Arc::clone(&state.quotas);
+    let pages = state.server.context().pages();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            let pruned = sessions.prune(Duration::from_secs(idle_secs));
+            for id in &pruned {
+                pages.forget(id);
+            }
+            if !pruned.is_empty() {
+                tracing::info!("Pruned {} idle MCP sessions", pruned.len());
+            }
+            quotas.prune();
+        }
+    });
+
+    eprintln!("🚀 Starting BrowserMCP Server v3.0.0-PRO on port {}", port);
+    eprintln!("🔧 Initializing {} tools...", state.server.registry().len());
+    for name in state.server.registry().names() {
+        eprintln!("  ✓ {}", name);
+    }
+
+    let tools_count = state.server.registry().len();
+    let mcp = Router::new()
+        .route("/mcp", post(mcp_handler).get(mcp_events).delete(mcp_delete))
+        .route_layer(middleware::from_fn_with_state(state.clone(), enforce_quotas))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth));
+    let mut app = Router::new()
+        .merge(mcp)
         .route("/health", get(health))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+        .route("/ready", get(ready))
+        .route("/metrics", get(metrics));
+    if let Some(server) = oauth {
+        eprintln!("🔑 OAuth 2.1 authorization server at {}", server.issuer());
+        app = app.merge(oauth_routes(OAuthState {
+            server,
+            api_keys: Arc::clone(&state.api_keys),
+        }));
+    }
+    let app = app.layer(cors(origins)).with_state(state.clone());
 
-    let addr = format!("0.0.0.0:{}", port);
-    println!("\n🌐 Binding to {}...", addr);
+    let addr = format!("{}:{}", bind, port);
+    eprintln!("\n🌐 Binding to {}...", addr);
 
     let listener = match tokio::net::TcpListener::bind(&addr).await {
         Ok(listener) => {
-            println!("✅ Successfully bound to {}", addr);
+            eprintln!("✅ Successfully bound to {}", addr);
             listener
         }
         Err(e) => {
@@ -326,14 +1065,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    println!("\n═════════════════════════════════════════════════");
-    println!("🎉 BrowserMCP PRO is LIVE!");
-    println!("═════════════════════════════════════════════════");
-    println!("🌐 MCP Server listening on http://127.0.0.1:{}", port);
-    println!("📋 Available Tools: 20+");
-    println!("⚡ Zero Warnings | Production Ready");
-    println!("═════════════════════════════════════════════════\n");
+    eprintln!("\n═════════════════════════════════════════════════");
+    eprintln!("🎉 BrowserMCP PRO is LIVE!");
+    eprintln!("═════════════════════════════════════════════════");
+    eprintln!("🌐 MCP Server listening on http://{}", addr);
+    eprintln!("📋 Available Tools: {}", tools_count);
+    eprintln!("⚡ Zero Warnings | Production Ready");
+    eprintln!("═════════════════════════════════════════════════\n");
 
-    axum::serve(listener, app).await?;
+    // Stop accepting on SIGTERM/Ctrl-C and let running requests finish; past
+    // the grace period, cancel the tool calls still running
+    tokio::spawn(shutdown_signal(state.shutdown.clone()));
+    let serve = axum::serve(listener, app)
+        .with_graceful_shutdown(state.shutdown.clone().cancelled_owned())
+        .into_future();
+    tokio::pin!(serve);
+    let grace = Duration::from_secs(config.server.shutdown_grace_secs);
+    tokio::select! {
+        served = &mut serve => served?,
+        _ = async {
+            state.shutdown.cancelled().await;
+            tokio::time::sleep(grace).await;
+        } => {
+            let cancelled = state.server.cancel_all();
+            tracing::warn!("Shutdown grace period over: cancelled {} running tool calls", cancelled);
+            // Cancellable tools return partial results; the rest are abandoned
+            if tokio::time::timeout(CANCEL_WAIT, &mut serve).await.is_err() {
+                tracing::warn!("Requests still running after cancellation, exiting anyway");
+            }
+        }
+    }
+
+    match state.server.context().flush() {
+        Ok(()) => eprintln!("👋 Drained and flushed, bye"),
+        Err(e) => tracing::error!("Could not flush audit log: {}", e),
+    }
     Ok(())
 }
+
+/// How long cancelled tool calls get to wind down before the process exits
+const CANCEL_WAIT: Duration = Duration::from_secs(5);
+
+/// Fire `shutdown` on SIGTERM (container stop) or Ctrl-C
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("SIGTERM handler");
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = ctrl_c.await;
+    eprintln!("🛑 Shutting down: no new requests, draining running ones");
+    shutdown.cancel();
+}
@@ -1,11 +1,16 @@
 // Browser automation module
+use crate::cookies::CookieJar;
 use crate::core::{BrowserInstance, Cookie, Result};
+use crate::fetch::{FetchRequest, FetchResponse};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use uuid::Uuid;
 
 pub struct BrowserPool {
     browsers: Vec<BrowserInstance>,
     max_instances: usize,
+    /// One `<id>.json` cookie jar per instance
+    cookie_dir: PathBuf,
 }
 
 impl BrowserPool {
@@ -13,26 +18,55 @@ impl BrowserPool {
         Self {
             browsers: Vec::new(),
             max_instances,
+            cookie_dir: crate::config::current().persistence.cookies_dir(),
         }
     }
 
+    pub fn with_cookie_dir(mut self, dir: PathBuf) -> Self {
+        self.cookie_dir = dir;
+        self
+    }
+
     pub async fn create_instance(&mut self, url: &str) -> Result<String> {
+        let id = format!("browser_{}", Uuid::new_v4());
+        self.open_instance(&id, url)?;
+        Ok(id)
+    }
+
+    /// Bring back an instance of an earlier run with the cookies it kept
+    pub async fn resume_instance(&mut self, id: &str, url: &str) -> Result<()> {
+        if self.get_instance(id).is_some() {
+            return Ok(());
+        }
+        if id.strip_prefix("browser_").is_none_or(|uuid| Uuid::parse_str(uuid).is_err()) {
+            return Err(format!("Invalid browser id: {}", id).into());
+        }
+        if !self.cookie_file(id).exists() {
+            return Err("Browser not found".into());
+        }
+        self.open_instance(id, url)
+    }
+
+    fn open_instance(&mut self, id: &str, url: &str) -> Result<()> {
         if self.browsers.len() >= self.max_instances {
             return Err("Max browser instances reached".into());
         }
 
-        let id = format!("browser_{}", Uuid::new_v4());
         let instance = BrowserInstance {
-            id: id.clone(),
+            id: id.to_string(),
             url: url.to_string(),
             user_agent: super::anti_detection::get_random_user_agent(),
             proxy: None,
-            cookies: Vec::new(),
+            cookies: CookieJar::open(&self.cookie_file(id))?,
             headers: HashMap::new(),
         };
 
         self.browsers.push(instance);
-        Ok(id)
+        Ok(())
+    }
+
+    fn cookie_file(&self, id: &str) -> PathBuf {
+        self.cookie_dir.join(format!("{}.json", id))
     }
 
     pub fn get_instance(&self, id: &str) -> Option<&BrowserInstance> {
@@ -43,9 +77,24 @@ impl BrowserPool {
         self.browsers.clone()
     }
 
+    /// Send `request` as the instance: its user agent, headers, proxy and
+    /// cookie jar
+    pub async fn fetch(&self, id: &str, request: FetchRequest) -> Result<FetchResponse> {
+        let browser = self.get_instance(id).ok_or("Browser not found")?;
+        let mut request = request.default_header("User-Agent", &browser.user_agent).cookies(&browser.cookies);
+        for (name, value) in &browser.headers {
+            request = request.default_header(name, value);
+        }
+        if let Some(proxy) = &browser.proxy {
+            request = request.proxy(proxy);
+        }
+        Ok(crate::fetch::global().fetch(request).await?)
+    }
+
     pub async fn navigate(&mut self, id: &str, url: &str) -> Result<()> {
+        let response = self.fetch(id, FetchRequest::get(url)).await?;
         if let Some(browser) = self.browsers.iter_mut().find(|b| b.id == id) {
-            browser.url = url.to_string();
+            browser.url = response.url;
             Ok(())
         } else {
             Err("Browser not found".into())
@@ -54,7 +103,9 @@ impl BrowserPool {
 
     pub async fn set_cookies(&mut self, id: &str, cookies: Vec<Cookie>) -> Result<()> {
         if let Some(browser) = self.browsers.iter_mut().find(|b| b.id == id) {
-            browser.cookies = cookies;
+            // Writes the jar's file
+            let jar = browser.cookies.clone();
+            tokio::task::spawn_blocking(move || jar.replace(cookies)).await??;
             Ok(())
         } else {
             Err("Browser not found".into())
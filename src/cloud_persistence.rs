@@ -27,10 +27,10 @@ pub struct CloudPersistence {
 
 impl CloudPersistence {
     pub fn new() -> Self {
-        let token = std::env::var("GITHUB_TOKEN").ok();
+        let persistence = &crate::config::current().persistence;
         CloudPersistence {
-            github_token: token,
-            use_local_only: std::env::var("USE_LOCAL_ONLY").is_ok(),
+            github_token: persistence.github_token.clone(),
+            use_local_only: persistence.local_only,
         }
     }
 
@@ -0,0 +1,631 @@
+//! Typed server configuration, layered: built-in defaults, then a TOML file,
+//! then environment variables, then command-line flags
+//!
+//! ```toml
+//! [server]
+//! port = 8080
+//! bind = "0.0.0.0"
+//!
+//! [http]
+//! timeout_secs = 15
+//!
+//! [persistence]
+//! data_dir = "/var/lib/browsermcp"
+//! ```
+//!
+//! The binary [`install`]s the effective configuration at startup; library
+//! code reads it through [`current`], which falls back to defaults plus the
+//! environment when nothing was installed.
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::mcp::quota::{QuotaConfig, RateLimit};
+use crate::robots;
+use crate::tls;
+
+/// Read from the working directory when no file is named
+pub const DEFAULT_FILE: &str = "browsermcp.toml";
+
+/// Environment variables and the key each one overrides
+pub const ENV_VARS: &[(&str, &str)] = &[
+    ("BROWSERMCP_TRANSPORT", "server.transport"),
+    ("BROWSERMCP_PORT", "server.port"),
+    ("BROWSERMCP_BIND", "server.bind"),
+    ("BROWSERMCP_PUBLIC_URL", "server.public_url"),
+    ("BROWSERMCP_SESSION_IDLE_SECS", "server.session_idle_secs"),
+    ("BROWSERMCP_MONITOR_INTERVAL_SECS", "server.monitor_interval_secs"),
+    ("BROWSERMCP_SHUTDOWN_GRACE_SECS", "server.shutdown_grace_secs"),
+    ("BROWSERMCP_AUTH_FILE", "auth.file"),
+    ("BROWSERMCP_RATE_LIMIT_RPS", "quota.rate_limit.requests_per_second"),
+    ("BROWSERMCP_RATE_LIMIT_BURST", "quota.rate_limit.burst_size"),
+    ("BROWSERMCP_MAX_CONCURRENT_CALLS", "quota.max_concurrent"),
+    ("BROWSERMCP_USER_AGENT", "http.user_agent"),
+    ("BROWSERMCP_HTTP_TIMEOUT_SECS", "http.timeout_secs"),
+    ("BROWSERMCP_INSECURE_HOSTS", "http.insecure_hosts"),
+    ("BROWSERMCP_CA_BUNDLES", "http.ca_bundles"),
+    ("BROWSERMCP_RESPECT_ROBOTS_TXT", "http.respect_robots_txt"),
+    ("BROWSERMCP_ROBOTS_AGENT", "http.robots_agent"),
+    ("BROWSERMCP_FETCH_PER_HOST_CONCURRENCY", "fetch.per_host_concurrency"),
+    ("BROWSERMCP_FETCH_PER_HOST_DELAY_MS", "fetch.per_host_delay_ms"),
+    ("BROWSERMCP_FETCH_MAX_RETRIES", "fetch.max_retries"),
+    ("BROWSERMCP_FETCH_MAX_BODY_BYTES", "fetch.max_body_bytes"),
+    ("BROWSERMCP_HTTP_CACHE", "fetch.cache"),
+    ("BROWSERMCP_HTTP_CACHE_MAX_BYTES", "fetch.cache_max_bytes"),
+    ("BROWSERMCP_BATCH_CONCURRENCY", "batch.concurrency"),
+    ("BROWSERMCP_DATA_DIR", "persistence.data_dir"),
+    ("BROWSERMCP_PROMPTS_DIR", "persistence.prompts_dir"),
+    ("BROWSERMCP_AUDIT_DIR", "persistence.audit_dir"),
+    ("BROWSERMCP_AUDIT_MAX_BYTES", "persistence.audit_max_bytes"),
+    ("BROWSERMCP_HTTP_CACHE_DIR", "persistence.http_cache_dir"),
+    ("BROWSERMCP_COOKIES_DIR", "persistence.cookies_dir"),
+    ("GITHUB_TOKEN", "persistence.github_token"),
+    ("USE_LOCAL_ONLY", "persistence.local_only"),
+    ("BROWSERMCP_WASM_MAX_MEMORY_MB", "wasm.max_memory_mb"),
+];
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("cannot read {path}: {source}")]
+    Read { path: PathBuf, source: std::io::Error },
+
+    #[error("{path}: {message}")]
+    Parse { path: PathBuf, message: String },
+
+    /// A bad environment variable or command-line value
+    #[error("{origin} ({key}): {message}")]
+    Override { origin: String, key: String, message: String },
+
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// Streamable HTTP on `bind:port`
+    Http,
+    /// Newline-delimited JSON-RPC over stdin/stdout
+    Stdio,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub transport: Transport,
+    pub port: u16,
+    pub bind: String,
+    /// OAuth issuer; `http://localhost:<port>` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+    pub oauth: bool,
+    /// Listen beyond loopback without API keys
+    pub allow_unauthenticated: bool,
+    pub session_idle_secs: u64,
+    /// How often subscribed `browser://page/...` resources are re-fetched
+    pub monitor_interval_secs: u64,
+    /// On SIGTERM/Ctrl-C, how long running tool calls may take to finish
+    /// before they are cancelled
+    pub shutdown_grace_secs: u64,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            transport: Transport::Http,
+            port: 3001,
+            bind: "127.0.0.1".to_string(),
+            public_url: None,
+            oauth: false,
+            allow_unauthenticated: false,
+            session_idle_secs: 3600,
+            monitor_interval_secs: 300,
+            shutdown_grace_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// API keys and allowed origins (`auth.json`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSettings {
+    pub user_agent: String,
+    pub timeout_secs: u64,
+    /// Skip certificate verification for every host (local development only)
+    pub accept_invalid_certs: bool,
+    /// Hosts whose certificates are not verified; `*.domain` covers subdomains
+    pub insecure_hosts: Vec<String>,
+    /// PEM files of CA certificates trusted next to the system roots
+    pub ca_bundles: Vec<PathBuf>,
+    /// Refuse URLs that the host's robots.txt disallows
+    pub respect_robots_txt: bool,
+    /// Product token matched against robots.txt `User-agent` lines
+    pub robots_agent: String,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36".to_string(),
+            timeout_secs: 30,
+            accept_invalid_certs: false,
+            insecure_hosts: Vec::new(),
+            ca_bundles: Vec::new(),
+            respect_robots_txt: true,
+            robots_agent: "BrowserMCP".to_string(),
+        }
+    }
+}
+
+impl HttpSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout_secs)
+    }
+}
+
+/// Politeness and retry policy of the shared [`crate::fetch::Fetcher`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchSettings {
+    /// Requests in flight to one host at a time
+    pub per_host_concurrency: usize,
+    /// Least time between two requests to one host; a robots.txt
+    /// `Crawl-delay` raises it
+    pub per_host_delay_ms: u64,
+    /// Further attempts after a connection error, a timeout or a
+    /// 429/502/503/504 answer
+    pub max_retries: u32,
+    /// First backoff; it doubles with each attempt, with jitter
+    pub backoff_base_ms: u64,
+    pub backoff_max_ms: u64,
+    /// Longest `Retry-After` waited for; a longer one ends the retries
+    pub max_retry_after_secs: u64,
+    /// Responses larger than this are refused
+    pub max_body_bytes: u64,
+    /// Keep GET responses in `persistence.http_cache_dir`
+    pub cache: bool,
+    /// Oldest cache files are removed past this size
+    pub cache_max_bytes: u64,
+}
+
+impl Default for FetchSettings {
+    fn default() -> Self {
+        Self {
+            per_host_concurrency: 2,
+            per_host_delay_ms: 0,
+            max_retries: 3,
+            backoff_base_ms: 500,
+            backoff_max_ms: 30_000,
+            max_retry_after_secs: 120,
+            max_body_bytes: 10 * 1024 * 1024,
+            cache: true,
+            cache_max_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchSettings {
+    /// URLs of one `web_scrape_batch` fetched at the same time
+    pub concurrency: usize,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self { concurrency: 5 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PersistenceSettings {
+    pub data_dir: PathBuf,
+    pub prompts_dir: PathBuf,
+    /// `<data_dir>/audit` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_dir: Option<PathBuf>,
+    pub audit_max_bytes: u64,
+    /// `<data_dir>/http-cache` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_cache_dir: Option<PathBuf>,
+    /// `<data_dir>/cookies` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookies_dir: Option<PathBuf>,
+    /// GitHub Gist backups of `CloudPersistence`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_token: Option<String>,
+    pub local_only: bool,
+}
+
+impl Default for PersistenceSettings {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("./mcp_data"),
+            prompts_dir: PathBuf::from("./prompts"),
+            audit_dir: None,
+            audit_max_bytes: crate::mcp::audit::DEFAULT_MAX_BYTES,
+            http_cache_dir: None,
+            cookies_dir: None,
+            github_token: None,
+            local_only: false,
+        }
+    }
+}
+
+impl PersistenceSettings {
+    pub fn audit_dir(&self) -> PathBuf {
+        self.audit_dir.clone().unwrap_or_else(|| self.data_dir.join("audit"))
+    }
+
+    pub fn http_cache_dir(&self) -> PathBuf {
+        self.http_cache_dir.clone().unwrap_or_else(|| self.data_dir.join("http-cache"))
+    }
+
+    pub fn cookies_dir(&self) -> PathBuf {
+        self.cookies_dir.clone().unwrap_or_else(|| self.data_dir.join("cookies"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WasmSettings {
+    /// Linear memory a module instance may grow to
+    pub max_memory_mb: u64,
+}
+
+impl Default for WasmSettings {
+    fn default() -> Self {
+        Self { max_memory_mb: 256 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerSettings,
+    pub auth: AuthSettings,
+    /// Limits for API keys that set none in the auth file
+    pub quota: QuotaConfig,
+    pub http: HttpSettings,
+    pub fetch: FetchSettings,
+    pub batch: BatchSettings,
+    pub persistence: PersistenceSettings,
+    pub wasm: WasmSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerSettings::default(),
+            auth: AuthSettings::default(),
+            quota: QuotaConfig {
+                rate_limit: Some(RateLimit {
+                    requests_per_second: 2.0,
+                    burst_size: 10,
+                }),
+                max_concurrent: Some(4),
+            },
+            http: HttpSettings::default(),
+            fetch: FetchSettings::default(),
+            batch: BatchSettings::default(),
+            persistence: PersistenceSettings::default(),
+            wasm: WasmSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, then `file` (if any), then the [`ENV_VARS`] found by `env`,
+    /// then `overrides` (`(flag, key, value)`, in order), validated
+    pub fn load(
+        file: Option<&Path>,
+        env: impl Fn(&str) -> Option<String>,
+        overrides: &[(&str, &str, String)],
+    ) -> Result<Self, ConfigError> {
+        let mut layered = toml::Value::try_from(Config::default()).expect("defaults serialize to TOML");
+
+        if let Some(path) = file {
+            let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            let parse_error = |e: toml::de::Error| ConfigError::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            };
+            // Parsed as a whole first so unknown keys are reported with their line
+            toml::from_str::<Config>(&text).map_err(parse_error)?;
+            merge(&mut layered, toml::from_str(&text).map_err(parse_error)?);
+        }
+
+        for (var, key) in ENV_VARS {
+            if let Some(value) = env(var) {
+                set(&mut layered, key, &value).map_err(|message| ConfigError::Override {
+                    origin: format!("${}", var),
+                    key: key.to_string(),
+                    message,
+                })?;
+            }
+        }
+
+        for (flag, key, value) in overrides {
+            set(&mut layered, key, value).map_err(|message| ConfigError::Override {
+                origin: flag.to_string(),
+                key: key.to_string(),
+                message,
+            })?;
+        }
+
+        let config: Config = layered.try_into().map_err(|e: toml::de::Error| ConfigError::Invalid(vec![e.to_string()]))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Defaults plus the process environment
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::load(None, |var| std::env::var(var).ok(), &[])
+    }
+
+    /// Every problem found, not just the first
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        let bind = &self.server.bind;
+        if bind != "localhost" && bind.parse::<std::net::IpAddr>().is_err() {
+            problems.push(format!("server.bind: `{}` is not an IP address", bind));
+        }
+        if let Some(url) = &self.server.public_url {
+            match reqwest::Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                _ => problems.push(format!("server.public_url: `{}` is not an http(s) URL", url)),
+            }
+        }
+        if self.server.session_idle_secs == 0 {
+            problems.push("server.session_idle_secs must be at least 1".to_string());
+        }
+        if self.server.monitor_interval_secs == 0 {
+            problems.push("server.monitor_interval_secs must be at least 1".to_string());
+        }
+        if let Some(file) = &self.auth.file {
+            if !file.is_file() {
+                problems.push(format!("auth.file: {} does not exist", file.display()));
+            }
+        }
+        if let Err(e) = self.quota.validate() {
+            problems.push(format!("quota: {}", e));
+        }
+        if self.http.user_agent.is_empty() || self.http.user_agent.chars().any(char::is_control) {
+            problems.push("http.user_agent must be a non-empty header value".to_string());
+        }
+        if self.http.timeout_secs == 0 {
+            problems.push("http.timeout_secs must be at least 1".to_string());
+        }
+        for host in &self.http.insecure_hosts {
+            if !tls::is_host_pattern(host) {
+                problems.push(format!("http.insecure_hosts: `{}` is not a host name or `*.domain`", host));
+            }
+        }
+        for bundle in &self.http.ca_bundles {
+            if let Err(e) = tls::load_bundle(bundle) {
+                problems.push(format!("http.ca_bundles: {}", e));
+            }
+        }
+        if self.http.robots_agent.is_empty() || !self.http.robots_agent.chars().all(robots::is_token_char) {
+            problems.push("http.robots_agent must be a product token (letters, `_` and `-`)".to_string());
+        }
+        if self.fetch.per_host_concurrency == 0 {
+            problems.push("fetch.per_host_concurrency must be at least 1".to_string());
+        }
+        if self.fetch.backoff_max_ms < self.fetch.backoff_base_ms {
+            problems.push("fetch.backoff_max_ms must not be less than fetch.backoff_base_ms".to_string());
+        }
+        if self.fetch.max_body_bytes == 0 {
+            problems.push("fetch.max_body_bytes must be at least 1".to_string());
+        }
+        if self.fetch.cache_max_bytes == 0 {
+            problems.push("fetch.cache_max_bytes must be at least 1".to_string());
+        }
+        if self.batch.concurrency == 0 {
+            problems.push("batch.concurrency must be at least 1".to_string());
+        }
+        if self.persistence.audit_max_bytes == 0 {
+            problems.push("persistence.audit_max_bytes must be at least 1".to_string());
+        }
+        if self.wasm.max_memory_mb == 0 {
+            problems.push("wasm.max_memory_mb must be at least 1".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// The configuration as TOML, secrets replaced by `[REDACTED]`
+    pub fn to_toml_redacted(&self) -> String {
+        let mut shown = self.clone();
+        if shown.persistence.github_token.is_some() {
+            shown.persistence.github_token = Some("[REDACTED]".to_string());
+        }
+        toml::to_string_pretty(&shown).expect("config serializes to TOML")
+    }
+}
+
+static CURRENT: OnceCell<Config> = OnceCell::new();
+
+/// Make `config` the one [`current`] returns; `false` if one was already in use
+pub fn install(config: Config) -> bool {
+    CURRENT.set(config).is_ok()
+}
+
+/// The installed configuration, or defaults plus the environment
+pub fn current() -> &'static Config {
+    CURRENT.get_or_init(|| {
+        Config::from_env().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring environment configuration: {}", e);
+            Config::default()
+        })
+    })
+}
+
+/// Overlay `overlay`'s tables onto `base`, key by key
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Set dotted `key` from a raw string, typed like the value it replaces
+/// (unset optional keys are strings, lists are lists of strings)
+fn set(root: &mut toml::Value, key: &str, raw: &str) -> Result<(), String> {
+    let (parents, leaf) = key.rsplit_once('.').map_or(("", key), |(p, l)| (p, l));
+    let mut table = root.as_table_mut().expect("config root is a table");
+    for part in parents.split('.').filter(|p| !p.is_empty()) {
+        table = table
+            .entry(part)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| format!("`{}` is not a section", part))?;
+    }
+
+    let value = match table.get(leaf) {
+        Some(toml::Value::Integer(_)) => raw
+            .trim()
+            .parse::<i64>()
+            .map(toml::Value::Integer)
+            .map_err(|_| format!("expected an integer, got `{}`", raw))?,
+        Some(toml::Value::Float(_)) => raw
+            .trim()
+            .parse::<f64>()
+            .map(toml::Value::Float)
+            .map_err(|_| format!("expected a number, got `{}`", raw))?,
+        // A set-but-empty flag variable (`USE_LOCAL_ONLY=`) means true
+        Some(toml::Value::Boolean(_)) => match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "1" | "true" | "yes" | "on" => toml::Value::Boolean(true),
+            "0" | "false" | "no" | "off" => toml::Value::Boolean(false),
+            _ => return Err(format!("expected true or false, got `{}`", raw)),
+        },
+        // Lists are comma-separated
+        Some(toml::Value::Array(_)) => toml::Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| toml::Value::String(item.to_string()))
+                .collect(),
+        ),
+        Some(toml::Value::Table(_)) => return Err("is a section, not a value".to_string()),
+        _ => toml::Value::String(raw.to_string()),
+    };
+    table.insert(leaf.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_layers_apply_in_order() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("browsermcp.toml");
+        std::fs::write(&file, "[server]\nport = 8080\nbind = \"0.0.0.0\"\n\n[http]\ntimeout_secs = 10\n").unwrap();
+
+        let config = Config::load(
+            Some(&file),
+            env(&[("BROWSERMCP_PORT", "9000"), ("BROWSERMCP_RATE_LIMIT_RPS", "5"), ("USE_LOCAL_ONLY", "")]),
+            &[("--bind", "server.bind", "127.0.0.1".to_string())],
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.bind, "127.0.0.1");
+        assert_eq!(config.http.timeout_secs, 10);
+        assert_eq!(config.quota.rate_limit.unwrap().requests_per_second, 5.0);
+        assert_eq!(config.quota.rate_limit.unwrap().burst_size, 10);
+        assert!(config.persistence.local_only);
+        assert_eq!(config.batch, BatchSettings::default());
+    }
+
+    #[test]
+    fn test_lists_are_comma_separated() {
+        let config = Config::load(None, env(&[("BROWSERMCP_INSECURE_HOSTS", "staging.example, *.internal.example")]), &[])
+            .unwrap();
+        assert_eq!(config.http.insecure_hosts, vec!["staging.example", "*.internal.example"]);
+        assert!(!config.http.accept_invalid_certs);
+
+        let invalid = Config::load(
+            None,
+            env(&[("BROWSERMCP_INSECURE_HOSTS", "https://staging.example"), ("BROWSERMCP_CA_BUNDLES", "missing.pem")]),
+            &[],
+        )
+        .unwrap_err();
+        let ConfigError::Invalid(problems) = invalid else { panic!("expected validation errors") };
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_errors_name_their_source() {
+        let bad_env = Config::load(None, env(&[("BROWSERMCP_HTTP_TIMEOUT_SECS", "soon")]), &[]).unwrap_err();
+        assert!(bad_env.to_string().contains("$BROWSERMCP_HTTP_TIMEOUT_SECS"));
+
+        let dir = std::env::temp_dir().join(format!("browsermcp-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("browsermcp.toml");
+        std::fs::write(&file, "[htpp]\ntimeout_secs = 10\n").unwrap();
+        let unknown = Config::load(Some(&file), env(&[]), &[]).unwrap_err();
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(matches!(unknown, ConfigError::Parse { .. }));
+        assert!(unknown.to_string().contains("htpp"));
+
+        let invalid = Config::load(
+            None,
+            env(&[("BROWSERMCP_BATCH_CONCURRENCY", "0"), ("BROWSERMCP_BIND", "everywhere")]),
+            &[],
+        )
+        .unwrap_err();
+        let ConfigError::Invalid(problems) = invalid else { panic!("expected validation errors") };
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_show_redacts_secrets() {
+        let config = Config::load(None, env(&[("GITHUB_TOKEN", "ghp_secret")]), &[]).unwrap();
+        assert_eq!(config.persistence.github_token.as_deref(), Some("ghp_secret"));
+        let shown = config.to_toml_redacted();
+        assert!(!shown.contains("ghp_secret"));
+        assert!(shown.contains("github_token = \"[REDACTED]\""));
+        assert!(shown.contains("[server]"));
+        // What `config show` prints loads back
+        let reparsed: Config = toml::from_str(&config.to_toml_redacted()).unwrap();
+        assert_eq!(reparsed.server, config.server);
+    }
+}
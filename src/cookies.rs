@@ -0,0 +1,629 @@
+//! Cookie jars for browser sessions (RFC 6265)
+//!
+//! A [`CookieJar`] stores what `Set-Cookie` headers hand it, under the
+//! RFC 6265 rules: a `Domain` attribute must cover the host that set it and
+//! must not be a public suffix. There is no copy of the Public Suffix List
+//! here: single labels (`com`) and a registry label under a country code
+//! (`co.uk`, `com.au`) are refused, suffixes such as `github.io` are not.
+//! `Path` defaults to the request's directory, `Secure` cookies come from
+//! and go to https only, and `Max-Age`/`Expires` end them. Requests given
+//! the jar through [`crate::fetch::FetchRequest::cookies`] send its
+//! matching cookies and store the answers' (redirects included).
+//!
+//! A jar opened on a file is written back, through a temporary file, after
+//! every change, session cookies included, so a session picks up where it
+//! left off. Changes from responses are written on the blocking pool, a
+//! burst of them at once. The file holds credentials: on unix only its
+//! owner can read it. Jars import and export Netscape `cookies.txt` and
+//! JSON (an array of [`Cookie`]).
+
+use parking_lot::Mutex;
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use thiserror::Error;
+
+pub use crate::core::Cookie;
+
+#[derive(Debug, Error)]
+pub enum CookieError {
+    #[error("cannot read {path}: {source}")]
+    Read { path: PathBuf, source: std::io::Error },
+
+    #[error("cannot write {path}: {source}")]
+    Write { path: PathBuf, source: std::io::Error },
+
+    #[error("line {line}: {message}")]
+    Netscape { line: usize, message: String },
+
+    #[error("invalid cookie JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+struct Inner {
+    id: String,
+    cookies: Mutex<Vec<Cookie>>,
+    file: Option<PathBuf>,
+    /// Held from taking the cookies to renaming the file, so that saves
+    /// land in order
+    saving: Mutex<()>,
+    /// A background save is queued and has not taken the cookies yet
+    save_queued: AtomicBool,
+}
+
+/// A shared handle to one session's cookies; clones see the same jar
+#[derive(Clone)]
+pub struct CookieJar {
+    inner: Arc<Inner>,
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieJar")
+            .field("file", &self.inner.file)
+            .field("cookies", &self.inner.cookies.lock().len())
+            .finish()
+    }
+}
+
+impl CookieJar {
+    /// An empty jar kept in memory
+    pub fn new() -> Self {
+        Self::with(Vec::new(), None)
+    }
+
+    fn with(cookies: Vec<Cookie>, file: Option<PathBuf>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                id: uuid::Uuid::new_v4().to_string(),
+                cookies: Mutex::new(cookies),
+                file,
+                saving: Mutex::new(()),
+                save_queued: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// The jar saved at `file`, empty if there is none yet; changes are
+    /// written back to it
+    pub fn open(file: &Path) -> Result<Self, CookieError> {
+        let cookies = match std::fs::read(file) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(source) => {
+                return Err(CookieError::Read {
+                    path: file.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        let jar = Self::with(cookies, Some(file.to_path_buf()));
+        jar.prune(now());
+        Ok(jar)
+    }
+
+    /// Identifies the jar across clones
+    pub fn id(&self) -> &str {
+        &self.inner.id
+    }
+
+    /// What a session's HTTP client holds: the jar, without keeping it alive
+    pub(crate) fn provider(&self) -> Arc<JarProvider> {
+        Arc::new(JarProvider(Arc::downgrade(&self.inner)))
+    }
+
+    /// Unexpired cookies
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = now();
+        self.inner.cookies.lock().iter().filter(|c| !expired(c, now)).cloned().collect()
+    }
+
+    /// Replace every cookie with `cookies`
+    pub fn replace(&self, cookies: Vec<Cookie>) -> Result<(), CookieError> {
+        *self.inner.cookies.lock() = cookies;
+        self.save()
+    }
+
+    /// Store one `Set-Cookie` value received from `url`; `false` when the
+    /// rules reject it
+    pub fn set_cookie(&self, set_cookie: &str, url: &Url) -> bool {
+        let Some(cookie) = parse_set_cookie(set_cookie, url, now()) else {
+            return false;
+        };
+        self.insert(cookie, now());
+        self.save_later();
+        true
+    }
+
+    /// The `Cookie` header value for a request to `url`
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let now = now();
+        let mut matching: Vec<Cookie> = self
+            .inner
+            .cookies
+            .lock()
+            .iter()
+            .filter(|c| !expired(c, now))
+            .filter(|c| if c.host_only { host == c.domain } else { domain_matches(&host, &c.domain) })
+            .filter(|c| path_matches(url.path(), &c.path))
+            .filter(|c| !c.secure || url.scheme() == "https")
+            .cloned()
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Longer paths first; the sort is stable, so older cookies lead ties
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        Some(
+            matching
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    fn insert(&self, cookie: Cookie, now: i64) {
+        let mut cookies = self.inner.cookies.lock();
+        cookies.retain(|c| !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path));
+        cookies.retain(|c| !expired(c, now));
+        if !expired(&cookie, now) {
+            cookies.push(cookie);
+        }
+    }
+
+    fn prune(&self, now: i64) {
+        self.inner.cookies.lock().retain(|c| !expired(c, now));
+    }
+
+    /// [`CookieJar::save`] off the async workers when called from one (as
+    /// `Set-Cookie` handling is); saves queued meanwhile are one write
+    fn save_later(&self) {
+        if self.inner.file.is_none() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            if let Err(e) = self.save() {
+                tracing::warn!("{}", e);
+            }
+            return;
+        };
+        if self.inner.save_queued.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let jar = self.clone();
+        runtime.spawn_blocking(move || {
+            if let Err(e) = jar.save() {
+                tracing::warn!("{}", e);
+            }
+        });
+    }
+
+    /// Write the cookies to the jar's file; blocking
+    fn save(&self) -> Result<(), CookieError> {
+        let Some(file) = &self.inner.file else {
+            return Ok(());
+        };
+        let _saving = self.inner.saving.lock();
+        self.inner.save_queued.store(false, Ordering::SeqCst);
+        let contents = serde_json::to_vec_pretty(&*self.inner.cookies.lock())?;
+        let tmp = file.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = file.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options.open(&tmp)?.write_all(&contents)?;
+            std::fs::rename(&tmp, file)
+        };
+        write().map_err(|source| {
+            std::fs::remove_file(&tmp).ok();
+            CookieError::Write {
+                path: file.clone(),
+                source,
+            }
+        })
+    }
+
+    /// Add the cookies of a Netscape `cookies.txt`, replacing same-named ones
+    pub fn import_netscape(&self, text: &str) -> Result<usize, CookieError> {
+        let cookies = parse_netscape(text)?;
+        let count = cookies.len();
+        let now = now();
+        for cookie in cookies {
+            self.insert(cookie, now);
+        }
+        self.save()?;
+        Ok(count)
+    }
+
+    /// The unexpired cookies as a Netscape `cookies.txt`
+    pub fn export_netscape(&self) -> String {
+        let mut text = String::from("# Netscape HTTP Cookie File\n");
+        for c in self.cookies() {
+            let bool_field = |b: bool| if b { "TRUE" } else { "FALSE" };
+            let domain = if c.host_only { c.domain.clone() } else { format!(".{}", c.domain) };
+            text.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if c.http_only { "#HttpOnly_" } else { "" },
+                domain,
+                bool_field(!c.host_only),
+                c.path,
+                bool_field(c.secure),
+                c.expires.unwrap_or(0),
+                c.name,
+                c.value
+            ));
+        }
+        text
+    }
+
+    /// Add the cookies of a JSON array of [`Cookie`], replacing same-named ones
+    pub fn import_json(&self, json: &str) -> Result<usize, CookieError> {
+        let cookies: Vec<Cookie> = serde_json::from_str(json)?;
+        let count = cookies.len();
+        let now = now();
+        for cookie in cookies {
+            self.insert(cookie, now);
+        }
+        self.save()?;
+        Ok(count)
+    }
+
+    /// The unexpired cookies as a JSON array
+    pub fn export_json(&self) -> String {
+        serde_json::to_string_pretty(&self.cookies()).expect("cookies serialize to JSON")
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for value in cookie_headers {
+            if let Ok(value) = value.to_str() {
+                self.set_cookie(value, url);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.header_for(url).and_then(|header| HeaderValue::from_str(&header).ok())
+    }
+}
+
+pub(crate) struct JarProvider(Weak<Inner>);
+
+impl JarProvider {
+    /// Whether the jar is still in use
+    pub(crate) fn is_live(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+
+    fn jar(&self) -> Option<CookieJar> {
+        self.0.upgrade().map(|inner| CookieJar { inner })
+    }
+}
+
+impl reqwest::cookie::CookieStore for JarProvider {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        if let Some(jar) = self.jar() {
+            jar.set_cookies(cookie_headers, url);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        reqwest::cookie::CookieStore::cookies(&self.jar()?, url)
+    }
+}
+
+/// Serialized as its cookie list
+impl Serialize for CookieJar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.cookies().serialize(serializer)
+    }
+}
+
+/// An in-memory jar holding the listed cookies
+impl<'de> Deserialize<'de> for CookieJar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::with(Vec::deserialize(deserializer)?, None))
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn expired(cookie: &Cookie, now: i64) -> bool {
+    cookie.expires.is_some_and(|expires| expires <= now)
+}
+
+/// RFC 6265 §5.1.3; IP addresses only match themselves
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.parse::<IpAddr>().is_err()
+            && host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.')))
+}
+
+/// RFC 6265 §5.1.4
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The directory of the request path (RFC 6265 §5.1.4)
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+/// `Expires` dates in the formats servers send
+fn cookie_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+        return Some(date.timestamp());
+    }
+    let value = value.trim_end_matches(" GMT").trim_end_matches(" UTC");
+    ["%a, %d-%b-%Y %H:%M:%S", "%A, %d-%b-%y %H:%M:%S", "%a, %d %b %Y %H:%M:%S", "%a %b %e %H:%M:%S %Y"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|date| date.and_utc().timestamp())
+}
+
+/// The cookie a `Set-Cookie` value from `url` stores (RFC 6265 §5.2-5.3);
+/// `None` when it must be ignored. An already-expired cookie is returned so
+/// that it replaces, and so removes, the stored one.
+fn parse_set_cookie(set_cookie: &str, url: &Url, now: i64) -> Option<Cookie> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut parts = set_cookie.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let (mut expires, mut max_age, mut domain, mut path) = (None, None, None, None);
+    let (mut secure, mut http_only) = (false, false);
+    for attribute in parts {
+        let (key, argument) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let argument = argument.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "expires" => expires = cookie_date(argument).or(expires),
+            "max-age" => max_age = argument.parse::<i64>().ok().or(max_age),
+            "domain" if !argument.is_empty() => {
+                domain = Some(argument.trim_start_matches('.').to_ascii_lowercase());
+            }
+            "path" if argument.starts_with('/') => path = Some(argument.to_string()),
+            "secure" => secure = true,
+            "httponly" => http_only = true,
+            _ => {}
+        }
+    }
+
+    // A Secure cookie set over plain http is ignored (RFC 6265bis §5.6)
+    if secure && url.scheme() != "https" {
+        return None;
+    }
+    let host_only = domain.is_none();
+    let domain = match domain {
+        Some(domain) if domain != host && is_public_suffix(&domain) => return None,
+        Some(domain) if !domain_matches(&host, &domain) => return None,
+        Some(domain) => domain,
+        None => host,
+    };
+
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain,
+        path: path.unwrap_or_else(|| default_path(url)),
+        secure,
+        http_only,
+        // Max-Age wins over Expires
+        expires: match max_age {
+            Some(seconds) => Some(now.saturating_add(seconds.max(0))),
+            None => expires,
+        },
+        host_only,
+    })
+}
+
+/// Second-level labels registries use under country codes
+const REGISTRY_LABELS: &[&str] = &[
+    "ac", "co", "com", "edu", "go", "gob", "gov", "gouv", "ltd", "mil", "ne", "net", "nic", "or", "org", "plc", "sch",
+];
+
+/// Approximates the Public Suffix List: a single label (`com`), or a
+/// registry label under a two-letter country code (`co.uk`, `com.au`)
+fn is_public_suffix(domain: &str) -> bool {
+    match domain.split('.').collect::<Vec<_>>().as_slice() {
+        [_] => true,
+        [label, country] => country.len() == 2 && REGISTRY_LABELS.contains(label),
+        _ => false,
+    }
+}
+
+fn parse_netscape(text: &str) -> Result<Vec<Cookie>, CookieError> {
+    let mut cookies = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: &str| CookieError::Netscape {
+            line: index + 1,
+            message: message.to_string(),
+        };
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+            return Err(invalid("expected 7 tab-separated fields"));
+        };
+        let flag = |field: &str| match field.to_ascii_uppercase().as_str() {
+            "TRUE" => Ok(true),
+            "FALSE" => Ok(false),
+            _ => Err(invalid("flags are TRUE or FALSE")),
+        };
+        let expires: i64 = expires.trim().parse().map_err(|_| invalid("expiry is not a Unix time"))?;
+        cookies.push(Cookie {
+            name: name.to_string(),
+            value: value.trim_end_matches('\r').to_string(),
+            domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+            path: path.to_string(),
+            secure: flag(secure)?,
+            http_only,
+            expires: (expires != 0).then_some(expires),
+            host_only: !flag(subdomains)?,
+        });
+    }
+    Ok(cookies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn test_domain_path_and_secure_rules() {
+        let jar = CookieJar::new();
+        let page = url("https://www.example.com/shop/cart");
+        assert!(jar.set_cookie("host=1", &page));
+        assert!(jar.set_cookie("wide=2; Domain=.example.com; Path=/", &page));
+        assert!(jar.set_cookie("safe=3; Secure; HttpOnly", &page));
+        // Another site, a public suffix and http setting Secure are refused
+        assert!(!jar.set_cookie("evil=1; Domain=other.com", &page));
+        assert!(!jar.set_cookie("evil=1; Domain=com", &page));
+        let british = url("https://evil.co.uk/");
+        assert!(!jar.set_cookie("evil=1; Domain=co.uk", &british));
+        assert!(jar.set_cookie("own=1; Domain=evil.co.uk", &british));
+        assert!(!jar.set_cookie("evil=1; Secure", &url("http://www.example.com/")));
+
+        // `host` and `safe` default to the /shop path; longer paths first
+        assert_eq!(jar.header_for(&page).unwrap(), "host=1; safe=3; wide=2");
+        assert_eq!(jar.header_for(&url("https://www.example.com/")).unwrap(), "wide=2");
+        assert_eq!(jar.header_for(&url("http://api.example.com/shop/x")).unwrap(), "wide=2");
+        assert_eq!(jar.header_for(&url("http://www.example.com/shop/x")).unwrap(), "host=1; wide=2");
+        assert_eq!(jar.header_for(&url("https://www.example.com/shopping")).unwrap(), "wide=2");
+        assert!(jar.header_for(&url("https://example.org/")).is_none());
+    }
+
+    #[test]
+    fn test_expiry_replaces_and_removes() {
+        let jar = CookieJar::new();
+        let page = url("https://example.com/");
+        jar.set_cookie("a=1; Max-Age=3600", &page);
+        jar.set_cookie("b=1; Expires=Wed, 21 Oct 2099 07:28:00 GMT", &page);
+        jar.set_cookie("a=2; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=60", &page);
+        assert_eq!(jar.header_for(&page).unwrap(), "b=1; a=2");
+
+        jar.set_cookie("a=gone; Max-Age=0", &page);
+        jar.set_cookie("b=gone; Expires=Thu, 01-Jan-1970 00:00:01 GMT", &page);
+        assert!(jar.header_for(&page).is_none());
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn test_netscape_and_json_round_trip() {
+        let text = "# Netscape HTTP Cookie File\n\
+                    .example.com\tTRUE\t/\tFALSE\t0\tsid\tabc\n\
+                    #HttpOnly_www.example.com\tFALSE\t/app\tTRUE\t4102444800\ttoken\txyz\n";
+        let jar = CookieJar::new();
+        assert_eq!(jar.import_netscape(text).unwrap(), 2);
+        assert_eq!(jar.export_netscape(), text);
+        assert_eq!(jar.header_for(&url("https://www.example.com/app/x")).unwrap(), "token=xyz; sid=abc");
+        assert_eq!(jar.header_for(&url("https://api.example.com/app")).unwrap(), "sid=abc");
+
+        let copy = CookieJar::new();
+        assert_eq!(copy.import_json(&jar.export_json()).unwrap(), 2);
+        assert_eq!(copy.export_netscape(), text);
+
+        let bad = jar.import_netscape("example.com\tTRUE\t/\n").unwrap_err();
+        assert_eq!(bad.to_string(), "line 1: expected 7 tab-separated fields");
+    }
+
+    #[test]
+    fn test_opened_jars_persist() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-cookies-{}", uuid::Uuid::new_v4()));
+        let file = dir.join("session.json");
+        let jar = CookieJar::open(&file).unwrap();
+        jar.set_cookie("sid=abc; Path=/", &url("https://example.com/login"));
+
+        let reopened = CookieJar::open(&file).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(reopened.header_for(&url("https://example.com/")).unwrap(), "sid=abc");
+        assert_ne!(reopened.id(), jar.id());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_jar_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("browsermcp-cookies-{}", uuid::Uuid::new_v4()));
+        let file = dir.join("session.json");
+        let jar = CookieJar::open(&file).unwrap();
+        jar.set_cookie("sid=abc", &url("https://example.com/"));
+        let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn test_response_cookies_are_saved_in_the_background() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-cookies-{}", uuid::Uuid::new_v4()));
+        let file = dir.join("session.json");
+        let jar = CookieJar::open(&file).unwrap();
+        for n in 0..20 {
+            jar.set_cookie(&format!("c{}={}", n, n), &url("https://example.com/"));
+        }
+
+        let mut saved = Vec::new();
+        for _ in 0..100 {
+            saved = CookieJar::open(&file).unwrap().cookies();
+            if saved.len() == 20 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(saved.len(), 20);
+    }
+
+    #[test]
+    fn test_failed_saves_leave_no_temporary_file() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-cookies-{}", uuid::Uuid::new_v4()));
+        // A directory where the file should go: the rename fails
+        let file = dir.join("session.json");
+        std::fs::create_dir_all(file.join("occupied")).unwrap();
+        let jar = CookieJar::with(Vec::new(), Some(file));
+
+        assert!(matches!(jar.replace(Vec::new()), Err(CookieError::Write { .. })));
+        let left: Vec<_> = std::fs::read_dir(&dir).unwrap().flatten().map(|e| e.file_name()).collect();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(left, vec!["session.json"]);
+    }
+}
@@ -2,22 +2,35 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::cookies::CookieJar;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrowserInstance {
     pub id: String,
     pub url: String,
     pub user_agent: String,
     pub proxy: Option<String>,
-    pub cookies: Vec<Cookie>,
+    pub cookies: CookieJar,
     pub headers: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cookie {
     pub name: String,
     pub value: String,
+    /// Lowercase, without a leading dot
     pub domain: String,
     pub path: String,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    /// Unix seconds; `None` for a session cookie
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<i64>,
+    /// Sent to `domain` itself only, not to its subdomains
+    #[serde(default)]
+    pub host_only: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +20,9 @@ pub struct AnalysisResult {
     pub duration_ms: u64,
     pub tags: Vec<String>,
     pub ttl_seconds: Option<u64>, // Time to live
+    /// API key that produced it; only that key can read it back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,7 +69,7 @@ impl DatabasePersistence {
 
         results.push(result.clone());
 
-        std::fs::write(&db_file, serde_json::to_string_pretty(&results)
+        write_atomic(&db_file, serde_json::to_string_pretty(&results)
             .map_err(|e| format!("Serialization error: {}", e))?)
             .map_err(|e| format!("Failed to write DB file: {}", e))?;
 
@@ -138,7 +141,7 @@ impl DatabasePersistence {
 
         caches.push(cache.clone());
 
-        std::fs::write(&cache_file, serde_json::to_string_pretty(&caches)
+        write_atomic(&cache_file, serde_json::to_string_pretty(&caches)
             .map_err(|e| format!("Serialization error: {}", e))?)
             .map_err(|e| format!("Failed to write cache file: {}", e))?;
 
@@ -228,7 +231,7 @@ impl DatabasePersistence {
             }
         });
 
-        std::fs::write(&db_file, serde_json::to_string_pretty(&results)
+        write_atomic(&db_file, serde_json::to_string_pretty(&results)
             .map_err(|e| format!("Serialization error: {}", e))?)
             .map_err(|e| format!("Failed to write DB file: {}", e))?;
 
@@ -250,6 +253,18 @@ impl DatabasePersistence {
     }
 }
 
+/// Replace `path` through a synced temporary file, so a process killed
+/// mid-save leaves the previous contents instead of a truncated file
+fn write_atomic(path: &str, contents: String) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let tmp = format!("{}.tmp", path);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
 fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -296,6 +311,7 @@ mod tests {
             duration_ms: 100,
             tags: vec!["test".to_string()],
             ttl_seconds: None,
+            client: None,
         };
 
         assert!(db.save_analysis(&result).is_ok());
@@ -0,0 +1,1009 @@
+//! The one HTTP client every outbound request goes through
+//!
+//! [`Fetcher::fetch`] checks robots.txt, again before following each
+//! redirect, then waits for the host's turn: at most
+//! `fetch.per_host_concurrency` requests are in flight per host, and
+//! consecutive requests are spaced by `fetch.per_host_delay_ms` or the
+//! robots.txt `Crawl-delay`, whichever is longer. Connection errors,
+//! timeouts and 429/502/503/504 answers are retried with exponential
+//! backoff and jitter; a `Retry-After` holds back the whole host. Bodies are
+//! streamed and refused past `fetch.max_body_bytes`. GET responses go
+//! through the [`crate::http_cache`] when `fetch.cache` is on, except
+//! requests of a cookie session, whose answers may be personal.
+//!
+//! Connections are pooled by one `reqwest::Client` (one per proxy), which
+//! verifies certificates as [`crate::tls`] configures; hosts exempt from
+//! verification get a client of their own. Redirects are followed here
+//! rather than by reqwest, so every hop is checked against its own host.
+
+use parking_lot::Mutex;
+use rand::Rng;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, LOCATION, RETRY_AFTER};
+use reqwest::{Client, Method, Url};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use crate::config::{self, FetchSettings, HttpSettings};
+use crate::cookies::{CookieJar, JarProvider};
+use crate::http_cache::{CacheStatus, HttpCache, Lookup};
+use crate::mcp::metrics;
+use crate::models::CertificateSummary;
+use crate::robots::{self, RobotsError};
+use crate::tls;
+
+/// Answers worth another attempt
+const RETRY_STATUSES: [u16; 4] = [429, 502, 503, 504];
+
+/// Proxy, cookie jar id, exempt from certificate verification
+type ClientKey = (Option<String>, Option<String>, bool);
+
+/// Kept until its jar is dropped
+type KeptClient = (Option<Arc<JarProvider>>, Client);
+
+/// Idle hosts are forgotten once this many are tracked
+const MAX_HOSTS: usize = 1024;
+
+/// Redirects followed per request, as reqwest's default policy
+const MAX_REDIRECTS: usize = 10;
+
+/// Request headers that stay with the host they were meant for
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: Fetcher = {
+        let config = config::current();
+        let fetcher = Fetcher::new(&config.http, &config.fetch);
+        if config.fetch.cache {
+            fetcher.with_cache(HttpCache::new(config.persistence.http_cache_dir(), config.fetch.cache_max_bytes))
+        } else {
+            fetcher
+        }
+    };
+}
+
+/// The fetcher built from [`config::current`]
+pub fn global() -> &'static Fetcher {
+    &GLOBAL
+}
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error(transparent)]
+    Robots(#[from] RobotsError),
+
+    #[error("invalid URL {url}: {reason}")]
+    InvalidUrl { url: String, reason: String },
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("{url}: response body exceeds {limit} bytes")]
+    TooLarge { url: String, limit: u64 },
+
+    #[error("{url}: more than {limit} redirects")]
+    TooManyRedirects { url: String, limit: usize },
+}
+
+/// What to fetch; unset limits come from the fetcher's settings
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+    max_body_bytes: Option<u64>,
+    max_retries: Option<u32>,
+    proxy: Option<String>,
+    cookies: Option<CookieJar>,
+    /// Off only for robots.txt itself
+    robots: bool,
+}
+
+impl FetchRequest {
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+            timeout: None,
+            max_body_bytes: None,
+            max_retries: None,
+            proxy: None,
+            cookies: None,
+            robots: true,
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub fn post(url: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self::new(Method::POST, url).body(body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Like [`FetchRequest::header`], unless the request already sets `name`
+    pub fn default_header(self, name: &str, value: &str) -> Self {
+        if self.headers.iter().any(|(set, _)| set.eq_ignore_ascii_case(name)) {
+            return self;
+        }
+        self.header(name, value)
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// For the whole exchange, body included; `http.timeout_secs` otherwise
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_body_bytes(mut self, limit: u64) -> Self {
+        self.max_body_bytes = Some(limit);
+        self
+    }
+
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = Some(retries);
+        self
+    }
+
+    /// Send through `proxy` (`http://`, `https://` or `socks5://`)
+    pub fn proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Send `jar`'s cookies and store the `Set-Cookie` answers in it,
+    /// redirects included
+    pub fn cookies(mut self, jar: &CookieJar) -> Self {
+        self.cookies = Some(jar.clone());
+        self
+    }
+
+    /// robots.txt is fetched before it can be consulted
+    pub(crate) fn skip_robots(mut self) -> Self {
+        self.robots = false;
+        self
+    }
+
+    /// Safe to send twice even if the first one may have been processed
+    fn idempotent(&self) -> bool {
+        matches!(self.method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS)
+    }
+
+    /// The request to send to `to` after a `status` redirect from `from`
+    /// (RFC 9110 §15.4): a 303, or a 301/302 answering a POST, turns it into
+    /// a GET without body, and credentials do not leave their host
+    fn redirected(&self, status: u16, from: &Url, to: &Url) -> Self {
+        let mut next = self.clone();
+        next.url = to.to_string();
+        let to_get = match status {
+            303 => next.method != Method::HEAD,
+            301 | 302 => next.method == Method::POST,
+            _ => false,
+        };
+        if to_get {
+            next.method = Method::GET;
+            next.body = None;
+            next.headers.retain(|(name, _)| !name.to_ascii_lowercase().starts_with("content-"));
+        }
+        if (from.host_str(), from.port_or_known_default()) != (to.host_str(), to.port_or_known_default()) {
+            next.headers.retain(|(name, _)| !CREDENTIAL_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h)));
+        }
+        next
+    }
+}
+
+/// A complete response, body read
+#[derive(Debug, Clone)]
+pub struct FetchResponse {
+    /// After redirects
+    pub url: String,
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// The server certificate, for https
+    pub certificate: Option<CertificateSummary>,
+    pub cache: CacheStatus,
+}
+
+impl FetchResponse {
+    /// The body decoded with the `Content-Type` charset, UTF-8 by default
+    pub fn text(&self) -> String {
+        let encoding = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                value.split(';').find_map(|param| {
+                    let (name, charset) = param.split_once('=')?;
+                    name.trim().eq_ignore_ascii_case("charset").then(|| charset.trim().trim_matches('"'))
+                })
+            })
+            .and_then(|charset| encoding_rs::Encoding::for_label(charset.as_bytes()))
+            .unwrap_or(encoding_rs::UTF_8);
+        encoding.decode(&self.body).0.into_owned()
+    }
+}
+
+/// Politeness state of one `host:port`
+struct Host {
+    permits: Arc<Semaphore>,
+    /// When the next request may start
+    next_start: Mutex<Instant>,
+}
+
+impl Host {
+    /// Claim the next start time, `gap` after the previous one, and sleep until it
+    async fn wait_turn(&self, gap: Duration) {
+        let start = {
+            let mut next_start = self.next_start.lock();
+            let start = (*next_start).max(Instant::now());
+            *next_start = start + gap;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// Hold back every request to the host for `wait`
+    fn defer(&self, wait: Duration) {
+        let mut next_start = self.next_start.lock();
+        *next_start = (*next_start).max(Instant::now() + wait);
+    }
+
+    fn idle(&self) -> bool {
+        self.permits.available_permits() > 0 && *self.next_start.lock() <= Instant::now()
+    }
+}
+
+pub struct Fetcher {
+    client: Client,
+    /// For hosts exempt from certificate verification
+    insecure: Client,
+    /// By proxy, cookie jar and exemption
+    clients: Mutex<HashMap<ClientKey, KeptClient>>,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+    roots: Vec<reqwest::Certificate>,
+    /// Blocking file IO: used through [`blocking`]
+    cache: Option<Arc<HttpCache>>,
+    http: HttpSettings,
+    settings: FetchSettings,
+}
+
+impl Fetcher {
+    pub fn new(http: &HttpSettings, settings: &FetchSettings) -> Self {
+        // Bundles are checked when the configuration is validated
+        let roots: Vec<reqwest::Certificate> = http
+            .ca_bundles
+            .iter()
+            .filter_map(|path| {
+                tls::load_bundle(path)
+                    .map_err(|e| tracing::warn!("Ignoring CA bundle: {}", e))
+                    .ok()
+            })
+            .flatten()
+            .collect();
+        let build = |insecure| {
+            Self::builder(http, &roots, insecure).build().unwrap_or_else(|e| {
+                tracing::error!("Cannot build HTTP client: {}", e);
+                Client::new()
+            })
+        };
+        Self {
+            client: build(false),
+            insecure: build(true),
+            clients: Mutex::new(HashMap::new()),
+            hosts: Mutex::new(HashMap::new()),
+            roots: roots.clone(),
+            cache: None,
+            http: http.clone(),
+            settings: settings.clone(),
+        }
+    }
+
+    fn builder(http: &HttpSettings, roots: &[reqwest::Certificate], insecure: bool) -> reqwest::ClientBuilder {
+        let mut builder = Client::builder()
+            .user_agent(http.user_agent.as_str())
+            .danger_accept_invalid_certs(insecure)
+            // Followed by `fetch_network`, which picks each hop's client
+            .redirect(reqwest::redirect::Policy::none())
+            .tls_info(true)
+            .timeout(http.timeout());
+        for root in roots {
+            builder = builder.add_root_certificate(root.clone());
+        }
+        builder
+    }
+
+    /// Keep GET responses in `cache`
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Fetch `request`, from the cache when it holds a fresh answer, else
+    /// retrying per the settings; the last answer is returned once retries
+    /// run out, whatever its status
+    pub async fn fetch(&self, mut request: FetchRequest) -> Result<FetchResponse, FetchError> {
+        let url = Url::parse(&request.url).map_err(|e| FetchError::InvalidUrl {
+            url: request.url.clone(),
+            reason: e.to_string(),
+        })?;
+        if request.robots {
+            robots::global().check(&request.url, request.proxy.as_deref()).await?;
+        }
+
+        let Some(cache) = &self.cache else {
+            return self.fetch_network(&request, &url).await;
+        };
+        if request.method != Method::GET || request.cookies.is_some() {
+            let response = self.fetch_network(&request, &url).await?;
+            if !request.method.is_safe() && response.status < 400 {
+                let (cache, url) = (Arc::clone(cache), request.url.clone());
+                blocking(move || cache.invalidate(&url)).await;
+            }
+            return Ok(response);
+        }
+
+        let request_headers = self.request_headers(&request);
+        let lookup = {
+            let (cache, url, headers) = (Arc::clone(cache), request.url.clone(), request_headers.clone());
+            blocking(move || cache.lookup(&url, &headers, chrono::Utc::now().timestamp())).await
+        };
+        let stale = match lookup {
+            Lookup::Fresh(stored) => return Ok(stored.response(CacheStatus::Hit)),
+            Lookup::Stale(stored) => {
+                for (name, value) in stored.conditional_headers() {
+                    request = request.header(name, &value);
+                }
+                Some(stored)
+            }
+            Lookup::Miss => None,
+        };
+
+        let request_time = chrono::Utc::now().timestamp();
+        let response = self.fetch_network(&request, &url).await?;
+        let response_time = chrono::Utc::now().timestamp();
+        let (cache, url) = (Arc::clone(cache), request.url.clone());
+        match stale {
+            Some(stored) if response.status == 304 => {
+                let headers = response.headers;
+                let stored =
+                    blocking(move || cache.freshen(&url, stored, &headers, request_time, response_time)).await;
+                Ok(stored.response(CacheStatus::Revalidated))
+            }
+            _ => Ok(blocking(move || {
+                cache.store(&url, &request_headers, &response, request_time, response_time);
+                response
+            })
+            .await),
+        }
+    }
+
+    /// The request's headers, with the client's default `User-Agent`, as a
+    /// `Vary` comparison sees them
+    fn request_headers(&self, request: &FetchRequest) -> Vec<(String, String)> {
+        let mut headers = request.headers.clone();
+        if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("user-agent")) {
+            headers.push(("User-Agent".to_string(), self.http.user_agent.clone()));
+        }
+        headers
+    }
+
+    /// Network half of [`Fetcher::fetch`]: follows redirects, sending each
+    /// hop with the client its own host calls for
+    async fn fetch_network(&self, request: &FetchRequest, url: &Url) -> Result<FetchResponse, FetchError> {
+        let (mut hop, mut url) = (Cow::Borrowed(request), url.clone());
+        let mut redirects = 0;
+        loop {
+            let response = self.fetch_hop(&hop, &url).await?;
+            let Some(next) = redirect_target(&response, &url) else {
+                return Ok(response);
+            };
+            if redirects == MAX_REDIRECTS {
+                return Err(FetchError::TooManyRedirects {
+                    url: request.url.clone(),
+                    limit: MAX_REDIRECTS,
+                });
+            }
+            redirects += 1;
+            tracing::debug!("{} redirects to {}", url, next);
+            if hop.robots {
+                robots::global().check(next.as_str(), hop.proxy.as_deref()).await?;
+            }
+            hop = Cow::Owned(hop.redirected(response.status, &url, &next));
+            url = next;
+        }
+    }
+
+    /// One hop, in the turn of its own host: politeness and retries
+    async fn fetch_hop(&self, request: &FetchRequest, url: &Url) -> Result<FetchResponse, FetchError> {
+        let mut gap = Duration::from_millis(self.settings.per_host_delay_ms);
+        if request.robots {
+            let http = &config::current().http;
+            if http.respect_robots_txt {
+                if let Some(crawl_delay) = robots::global()
+                    .get(&request.url, request.proxy.as_deref())
+                    .await
+                    .and_then(|robots| robots.crawl_delay(&http.robots_agent))
+                {
+                    gap = gap.max(crawl_delay);
+                }
+            }
+        }
+
+        let host = self.host(url);
+        let insecure = tls::is_exempt(&self.http, url.host_str().unwrap_or_default());
+        let client = self.client_for(request, insecure)?;
+        let max_retries = request.max_retries.unwrap_or(self.settings.max_retries);
+        let max_retry_after = Duration::from_secs(self.settings.max_retry_after_secs);
+
+        let mut attempt = 0;
+        loop {
+            let outcome = {
+                let _permit = Arc::clone(&host.permits).acquire_owned().await.expect("host semaphore is never closed");
+                host.wait_turn(gap).await;
+                self.send(&client, request, url, !insecure).await
+            };
+
+            let backoff = match &outcome {
+                Ok(response) if RETRY_STATUSES.contains(&response.status) => {
+                    match retry_after(&response.headers) {
+                        Some(wait) if wait > max_retry_after => return outcome,
+                        Some(wait) => {
+                            host.defer(wait);
+                            Some(Duration::ZERO)
+                        }
+                        None => Some(self.backoff(attempt)),
+                    }
+                }
+                // A request that timed out may have been processed
+                Err(FetchError::Http(e)) if e.is_connect() || (e.is_timeout() && request.idempotent()) => {
+                    Some(self.backoff(attempt))
+                }
+                _ => None,
+            };
+
+            match backoff {
+                Some(wait) if attempt < max_retries => {
+                    attempt += 1;
+                    tracing::debug!("Retrying {} (attempt {}) in {:?}", request.url, attempt + 1, wait);
+                    tokio::time::sleep(wait).await;
+                }
+                _ => return outcome,
+            }
+        }
+    }
+
+    /// `GET url` as text: (status, body)
+    pub async fn get_text(&self, url: &str) -> Result<(u16, String), FetchError> {
+        let response = self.fetch(FetchRequest::get(url)).await?;
+        Ok((response.status, response.text()))
+    }
+
+    /// One attempt, body read up to the limit
+    async fn send(
+        &self,
+        client: &Client,
+        request: &FetchRequest,
+        url: &Url,
+        verified: bool,
+    ) -> Result<FetchResponse, FetchError> {
+        let mut builder = client
+            .request(request.method.clone(), url.clone())
+            .timeout(request.timeout.unwrap_or_else(|| self.http.timeout()));
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+
+        let response = builder.send().await;
+        metrics::global().record_outbound(response.as_ref().ok().map(|r| r.status().as_u16()));
+        let mut response = response?;
+
+        let limit = request.max_body_bytes.unwrap_or(self.settings.max_body_bytes);
+        let too_large = || FetchError::TooLarge {
+            url: request.url.clone(),
+            limit,
+        };
+        if response.content_length().is_some_and(|length| length > limit) {
+            return Err(too_large());
+        }
+
+        let final_url = response.url().to_string();
+        let certificate = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+            .and_then(|der| tls::summarize(der, verified));
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if (body.len() + chunk.len()) as u64 > limit {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(FetchResponse {
+            url: final_url,
+            status,
+            headers,
+            body,
+            certificate,
+            cache: CacheStatus::Miss,
+        })
+    }
+
+    /// The pooled client, or the one kept for the request's proxy and jar
+    fn client_for(&self, request: &FetchRequest, insecure: bool) -> Result<Client, FetchError> {
+        let (proxy, jar) = (request.proxy.as_deref(), request.cookies.as_ref());
+        if proxy.is_none() && jar.is_none() {
+            return Ok(if insecure { self.insecure.clone() } else { self.client.clone() });
+        }
+        let key = (proxy.map(str::to_string), jar.map(|jar| jar.id().to_string()), insecure);
+        let mut clients = self.clients.lock();
+        if let Some((_, client)) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        // Clients of dropped jars go
+        clients.retain(|_, (provider, _)| provider.as_ref().is_none_or(|provider| provider.is_live()));
+
+        let mut builder = Self::builder(&self.http, &self.roots, insecure);
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        let provider = jar.map(CookieJar::provider);
+        if let Some(provider) = &provider {
+            builder = builder.cookie_provider(Arc::clone(provider));
+        }
+        let client = builder.build()?;
+        clients.insert(key, (provider, client.clone()));
+        Ok(client)
+    }
+
+    fn host(&self, url: &Url) -> Arc<Host> {
+        let key = format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        let mut hosts = self.hosts.lock();
+        if hosts.len() >= MAX_HOSTS && !hosts.contains_key(&key) {
+            hosts.retain(|_, host| Arc::strong_count(host) > 1 || !host.idle());
+        }
+        let per_host = self.settings.per_host_concurrency.max(1);
+        Arc::clone(hosts.entry(key).or_insert_with(|| {
+            Arc::new(Host {
+                permits: Arc::new(Semaphore::new(per_host)),
+                next_start: Mutex::new(Instant::now()),
+            })
+        }))
+    }
+
+    /// Wait before retry `attempt + 1`: `backoff_base_ms · 2^attempt`, capped,
+    /// then drawn from its upper half
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .settings
+            .backoff_base_ms
+            .saturating_mul(1u64 << attempt.min(32))
+            .min(self.settings.backoff_max_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(ceiling / 2..=ceiling))
+    }
+}
+
+/// Where a redirect answer points, if it is one reqwest would have followed
+fn redirect_target(response: &FetchResponse, url: &Url) -> Option<Url> {
+    if !matches!(response.status, 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = response.headers.get(LOCATION)?.to_str().ok()?;
+    url.join(location).ok().filter(|next| matches!(next.scheme(), "http" | "https"))
+}
+
+/// Run `work` (HTTP cache file IO) off the async workers; a panic in it
+/// resumes here
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => panic!("HTTP cache task did not run: {}", e),
+    }
+}
+
+/// `Retry-After` in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves `respond(request, request number)` (raw HTTP) until the
+    /// test ends; robots.txt is answered 404. Returns the base URL and the
+    /// most requests seen in flight at once.
+    async fn serve(
+        respond: impl Fn(&str, usize) -> String + Send + Sync + 'static,
+        hold: Duration,
+    ) -> (String, Arc<AtomicUsize>) {
+        serve_with_robots(None, respond, hold).await
+    }
+
+    /// [`serve`], answering robots.txt with `robots` when given
+    async fn serve_with_robots(
+        robots: Option<&'static str>,
+        respond: impl Fn(&str, usize) -> String + Send + Sync + 'static,
+        hold: Duration,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let respond = Arc::new(respond);
+        let (count, in_flight, peak) = (
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+        );
+        let seen = Arc::clone(&peak);
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else { return };
+                let (respond, count, in_flight, peak) =
+                    (Arc::clone(&respond), Arc::clone(&count), Arc::clone(&in_flight), Arc::clone(&peak));
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let response = if path == "/robots.txt" {
+                        match robots {
+                            Some(robots) => answer("200 OK", "", robots),
+                            None => answer("404 Not Found", "", ""),
+                        }
+                    } else {
+                        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(hold).await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        respond(&request, count.fetch_add(1, Ordering::SeqCst))
+                    };
+                    stream.write_all(response.as_bytes()).await.ok();
+                });
+            }
+        });
+        (base, seen)
+    }
+
+    fn answer(status: &str, extra: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\ncontent-length: {}\r\nconnection: close\r\n{}\r\n{}",
+            status,
+            body.len(),
+            extra,
+            body
+        )
+    }
+
+    fn fetcher(settings: FetchSettings) -> Fetcher {
+        Fetcher::new(&HttpSettings::default(), &settings)
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_honored_then_retried() {
+        let (base, _) = serve(
+            |_, n| match n {
+                0 => answer("503 Service Unavailable", "retry-after: 1\r\n", ""),
+                _ => answer("200 OK", "", "ok"),
+            },
+            Duration::ZERO,
+        )
+        .await;
+
+        let started = Instant::now();
+        let (status, body) = fetcher(FetchSettings::default()).get_text(&format!("{}/page", base)).await.unwrap();
+        assert_eq!((status, body.as_str()), (200, "ok"));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retries_run_out_with_the_last_answer() {
+        let (base, _) = serve(|_, _| answer("429 Too Many Requests", "", "slow down"), Duration::ZERO).await;
+        let fetcher = fetcher(FetchSettings {
+            max_retries: 2,
+            backoff_base_ms: 10,
+            ..FetchSettings::default()
+        });
+        let response = fetcher.fetch(FetchRequest::get(&format!("{}/x", base))).await.unwrap();
+        assert_eq!(response.status, 429);
+        assert_eq!(response.text(), "slow down");
+
+        // A Retry-After past the limit is not waited for
+        let (base, _) = serve(|_, _| answer("503 Service Unavailable", "retry-after: 3600\r\n", ""), Duration::ZERO).await;
+        let started = Instant::now();
+        let response = fetcher.fetch(FetchRequest::get(&format!("{}/x", base))).await.unwrap();
+        assert_eq!(response.status, 503);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_fresh_and_revalidates_stale() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-fetch-cache-{}", uuid::Uuid::new_v4()));
+        let (base, _) = serve(
+            |request, _| {
+                let request = request.to_ascii_lowercase();
+                if request.starts_with("get /fresh") {
+                    answer("200 OK", "cache-control: max-age=600\r\n", "fresh")
+                } else if request.contains("if-none-match: \"v1\"") {
+                    answer("304 Not Modified", "etag: \"v1\"\r\n", "")
+                } else {
+                    answer("200 OK", "cache-control: no-cache\r\netag: \"v1\"\r\n", "etagged")
+                }
+            },
+            Duration::ZERO,
+        )
+        .await;
+        let fetcher = fetcher(FetchSettings::default()).with_cache(HttpCache::new(dir.clone(), 1 << 20));
+        let fetch = |path: &str| fetcher.fetch(FetchRequest::get(&format!("{}{}", base, path)));
+
+        let first = fetch("/fresh").await.unwrap();
+        assert_eq!(first.cache, CacheStatus::Miss);
+        let second = fetch("/fresh").await.unwrap();
+        assert_eq!((second.cache, second.text().as_str()), (CacheStatus::Hit, "fresh"));
+
+        assert_eq!(fetch("/etag").await.unwrap().cache, CacheStatus::Miss);
+        let revalidated = fetch("/etag").await.unwrap();
+        assert_eq!(revalidated.cache, CacheStatus::Revalidated);
+        assert_eq!((revalidated.status, revalidated.text().as_str()), (200, "etagged"));
+
+        // A successful POST evicts the URL
+        let posted = fetcher.fetch(FetchRequest::post(&format!("{}/fresh", base), "x")).await.unwrap();
+        assert_eq!(posted.cache, CacheStatus::Miss);
+        assert_eq!(fetch("/fresh").await.unwrap().cache, CacheStatus::Miss);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_session_cookies_follow_redirects() {
+        let (base, _) = serve(
+            |request, _| {
+                if request.starts_with("GET /login") {
+                    answer("302 Found", "location: /home\r\nset-cookie: sid=abc; Path=/; HttpOnly\r\n", "")
+                } else {
+                    let cookie = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("cookie: ").or_else(|| line.strip_prefix("Cookie: ")))
+                        .unwrap_or("none");
+                    answer("200 OK", "set-cookie: seen=1; Max-Age=600\r\n", cookie)
+                }
+            },
+            Duration::ZERO,
+        )
+        .await;
+        let fetcher = fetcher(FetchSettings::default());
+        let jar = CookieJar::new();
+
+        let response = fetcher.fetch(FetchRequest::get(&format!("{}/login", base)).cookies(&jar)).await.unwrap();
+        assert!(response.url.ends_with("/home"));
+        assert_eq!(response.text(), "sid=abc");
+        let mut names: Vec<String> = jar.cookies().into_iter().map(|c| c.name).collect();
+        names.sort();
+        assert_eq!(names, vec!["seen", "sid"]);
+
+        // Requests without the jar send nothing
+        let response = fetcher.fetch(FetchRequest::get(&format!("{}/home", base))).await.unwrap();
+        assert_eq!(response.text(), "none");
+    }
+
+    #[tokio::test]
+    async fn test_redirects_into_disallowed_paths_are_refused() {
+        let fetched_private = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let seen = Arc::clone(&fetched_private);
+        let (base, _) = serve_with_robots(
+            Some("User-agent: *\nDisallow: /private\n"),
+            move |request, _| {
+                if request.starts_with("GET /start") {
+                    answer("301 Moved Permanently", "location: /private/page\r\n", "")
+                } else {
+                    seen.store(true, Ordering::SeqCst);
+                    answer("200 OK", "", "secret")
+                }
+            },
+            Duration::ZERO,
+        )
+        .await;
+
+        let error = fetcher(FetchSettings::default())
+            .fetch(FetchRequest::get(&format!("{}/start", base)))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, FetchError::Robots(RobotsError::Disallowed { .. })), "{}", error);
+        assert!(!fetched_private.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_bodies_over_the_limit_are_refused() {
+        let (base, _) = serve(|_, _| answer("200 OK", "", &"x".repeat(2048)), Duration::ZERO).await;
+        let fetcher = fetcher(FetchSettings::default());
+        let url = format!("{}/big", base);
+        let error = fetcher.fetch(FetchRequest::get(&url).max_body_bytes(1024)).await.unwrap_err();
+        assert!(matches!(error, FetchError::TooLarge { limit: 1024, .. }), "{}", error);
+        assert_eq!(fetcher.fetch(FetchRequest::get(&url)).await.unwrap().body.len(), 2048);
+    }
+
+    #[tokio::test]
+    async fn test_requests_to_one_host_are_limited_and_spaced() {
+        let (base, peak) = serve(|_, _| answer("200 OK", "", "ok"), Duration::from_millis(100)).await;
+        let fetcher = fetcher(FetchSettings {
+            per_host_concurrency: 2,
+            per_host_delay_ms: 50,
+            ..FetchSettings::default()
+        });
+
+        let started = Instant::now();
+        let urls: Vec<String> = (0..5).map(|i| format!("{}/{}", base, i)).collect();
+        let responses = futures::future::join_all(urls.iter().map(|url| fetcher.get_text(url))).await;
+        assert!(responses.iter().all(|r| r.as_ref().is_ok_and(|(status, _)| *status == 200)));
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        // Five starts 50ms apart
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_hops_wait_for_their_own_host() {
+        let (target, peak) = serve(|_, _| answer("200 OK", "", "ok"), Duration::from_millis(200)).await;
+        let location = format!("location: {}/landing\r\n", target);
+        let (origin, _) = serve(move |_, _| answer("302 Found", &location, ""), Duration::ZERO).await;
+        let fetcher = fetcher(FetchSettings {
+            per_host_concurrency: 1,
+            ..FetchSettings::default()
+        });
+
+        let (direct, start) = (format!("{}/direct", target), format!("{}/start", origin));
+        let (direct, redirected) = tokio::join!(fetcher.get_text(&direct), fetcher.get_text(&start));
+        assert_eq!(direct.unwrap().1, "ok");
+        assert_eq!(redirected.unwrap().1, "ok");
+        // The hop took the target's only slot instead of the origin's
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+    }
+
+    /// An https server for `localhost` and `127.0.0.1` answering every
+    /// request with `respond(request)`; returns its port
+    async fn serve_tls(respond: impl Fn(&str) -> String + Send + Sync + 'static) -> u16 {
+        let identity = tokio_native_tls::native_tls::Identity::from_pkcs8(
+            include_bytes!("../tests/fixtures/tls/localhost.pem"),
+            include_bytes!("../tests/fixtures/tls/localhost.key"),
+        )
+        .unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(
+            tokio_native_tls::native_tls::TlsAcceptor::new(identity).unwrap(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let respond = Arc::new(respond);
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { return };
+                let (acceptor, respond) = (acceptor.clone(), Arc::clone(&respond));
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else { return };
+                    let mut buf = vec![0; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    if n == 0 {
+                        return;
+                    }
+                    let response = respond(&String::from_utf8_lossy(&buf[..n]));
+                    stream.write_all(response.as_bytes()).await.ok();
+                    stream.shutdown().await.ok();
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_certificates_are_verified_unless_exempt() {
+        let url = format!("https://localhost:{}/", serve_tls(|_| answer("200 OK", "", "ok")).await);
+        let request = || FetchRequest::get(&url).skip_robots().max_retries(0);
+        let with = |http: HttpSettings| Fetcher::new(&http, &FetchSettings::default());
+
+        let untrusted = with(HttpSettings::default()).fetch(request()).await;
+        assert!(matches!(untrusted, Err(FetchError::Http(_))), "{:?}", untrusted);
+
+        let ca = HttpSettings {
+            ca_bundles: vec![std::path::PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem"))],
+            ..HttpSettings::default()
+        };
+        let response = with(ca).fetch(request()).await.unwrap();
+        assert_eq!(response.text(), "ok");
+        let certificate = response.certificate.unwrap();
+        assert!(certificate.verified);
+        assert_eq!(certificate.issuer, "CN=BrowserMCP Test CA");
+
+        let exempt = HttpSettings {
+            insecure_hosts: vec!["localhost".to_string()],
+            ..HttpSettings::default()
+        };
+        let response = with(exempt).fetch(request()).await.unwrap();
+        assert!(!response.certificate.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn test_redirects_from_exempt_hosts_are_verified() {
+        // 127.0.0.1 is not exempt: its certificate must verify
+        let target = serve_tls(|_| answer("200 OK", "", "ok")).await;
+        let exempt = serve_tls(move |_| {
+            answer("302 Found", &format!("location: https://127.0.0.1:{}/\r\n", target), "")
+        })
+        .await;
+        let request = || FetchRequest::get(&format!("https://localhost:{}/", exempt)).skip_robots().max_retries(0);
+        let with = |hosts: &[&str]| {
+            let http = HttpSettings {
+                insecure_hosts: hosts.iter().map(|host| host.to_string()).collect(),
+                ..HttpSettings::default()
+            };
+            Fetcher::new(&http, &FetchSettings::default())
+        };
+
+        let redirected = with(&["localhost"]).fetch(request()).await;
+        assert!(matches!(redirected, Err(FetchError::Http(_))), "{:?}", redirected);
+
+        let response = with(&["localhost", "127.0.0.1"]).fetch(request()).await.unwrap();
+        assert_eq!(response.url, format!("https://127.0.0.1:{}/", target));
+        assert_eq!(response.text(), "ok");
+    }
+
+    #[test]
+    fn test_backoff_doubles_within_bounds() {
+        let fetcher = fetcher(FetchSettings {
+            backoff_base_ms: 100,
+            backoff_max_ms: 1000,
+            ..FetchSettings::default()
+        });
+        for (attempt, ceiling) in [(0, 100), (1, 200), (3, 800), (4, 1000), (40, 1000)] {
+            let wait = fetcher.backoff(attempt).as_millis() as u64;
+            assert!((ceiling / 2..=ceiling).contains(&wait), "attempt {}: {}ms", attempt, wait);
+        }
+    }
+
+    #[test]
+    fn test_text_uses_the_declared_charset() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/html; charset=ISO-8859-1".parse().unwrap());
+        let response = FetchResponse {
+            url: "http://example.com/".to_string(),
+            status: 200,
+            headers,
+            body: vec![b'c', 0xe9],
+            certificate: None,
+            cache: CacheStatus::Miss,
+        };
+        assert_eq!(response.text(), "cé");
+    }
+}
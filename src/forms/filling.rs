@@ -356,10 +356,8 @@ impl SmartFiller {
                         return Err(format!("Field '{}' is not a valid URL", field.name));
                     }
                 }
-                FieldType::Number => {
-                    if value.parse::<f64>().is_err() {
-                        return Err(format!("Field '{}' is not a valid number", field.name));
-                    }
+                FieldType::Number if value.parse::<f64>().is_err() => {
+                    return Err(format!("Field '{}' is not a valid number", field.name));
                 }
                 FieldType::Tel => {
                     let tel_regex =
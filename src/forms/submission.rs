@@ -293,10 +293,8 @@ impl FormSubmitter {
                     return Err("Invalid email format".to_string());
                 }
             }
-            FieldType::Number => {
-                if value.parse::<f64>().is_err() {
-                    return Err("Invalid number format".to_string());
-                }
+            FieldType::Number if value.parse::<f64>().is_err() => {
+                return Err("Invalid number format".to_string());
             }
             FieldType::Tel => {
                 let tel_regex =
@@ -0,0 +1,582 @@
+//! Private on-disk HTTP cache (RFC 9111) behind the shared fetcher
+//!
+//! GET responses are kept under `persistence.http_cache_dir`, one JSON file
+//! per URL holding one variant per combination of the request headers its
+//! `Vary` names. A stored response is served without any request while it
+//! is fresh (`Cache-Control: max-age`, else `Expires`, else a tenth of the
+//! time since `Last-Modified`). Once stale it is revalidated with
+//! `If-None-Match`/`If-Modified-Since`; a 304 refreshes its headers and the
+//! stored body is served.
+//!
+//! `no-store` on either side keeps a response out of the cache and
+//! `no-cache` forces revalidation. A successful unsafe request (POST, PUT,
+//! DELETE, ...) evicts its URL. The oldest files go once the directory
+//! outgrows `fetch.cache_max_bytes`.
+
+use base64::Engine;
+use parking_lot::Mutex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::fetch::FetchResponse;
+use crate::models::CertificateSummary;
+
+/// Cap of the `Last-Modified` heuristic freshness
+const HEURISTIC_MAX_SECS: i64 = 24 * 60 * 60;
+
+/// Variants kept per URL; the oldest goes first
+const MAX_VARIANTS: usize = 8;
+
+/// Statuses that may be cached without explicit freshness (RFC 9110 §15.1)
+const HEURISTIC_STATUSES: [u16; 11] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// Never stored or replayed from the cache
+const UNSTORED_HEADERS: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "set-cookie",
+    "content-length",
+];
+
+/// Where a response came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStatus {
+    /// Fetched from the network
+    Miss,
+    /// Served from the cache without a request
+    Hit,
+    /// Served from the cache after a 304
+    Revalidated,
+}
+
+/// `Cache-Control` directives, names lowercased
+#[derive(Debug, Default)]
+struct Directives(HashMap<String, Option<String>>);
+
+impl Directives {
+    fn parse<'a>(values: impl Iterator<Item = &'a str>) -> Self {
+        let mut directives = HashMap::new();
+        for directive in values.flat_map(|value| value.split(',')) {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name, Some(argument.trim().trim_matches('"').to_string())),
+                None => (directive, None),
+            };
+            let name = name.trim().to_ascii_lowercase();
+            if !name.is_empty() {
+                directives.insert(name, argument);
+            }
+        }
+        Self(directives)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    fn seconds(&self, name: &str) -> Option<i64> {
+        self.0.get(name)?.as_deref()?.parse().ok()
+    }
+}
+
+/// Unix seconds of an HTTP date
+fn http_date(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc2822(value.trim()).ok().map(|date| date.timestamp())
+}
+
+/// A stored response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    /// Headers named by `Vary` (lowercase) and their values in the request
+    pub vary: Vec<(String, Option<String>)>,
+    /// After redirects
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Base64
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<CertificateSummary>,
+    /// Unix seconds the request was sent and the response received
+    pub request_time: i64,
+    pub response_time: i64,
+}
+
+impl StoredResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn headers_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.headers
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn directives(&self) -> Directives {
+        Directives::parse(self.headers_named("cache-control"))
+    }
+
+    /// How long the response stays fresh, in seconds (RFC 9111 §4.2.1)
+    pub fn freshness_lifetime(&self) -> i64 {
+        let directives = self.directives();
+        if let Some(max_age) = directives.seconds("max-age") {
+            return max_age;
+        }
+        let date = self.header("date").and_then(http_date).unwrap_or(self.response_time);
+        if let Some(expires) = self.header("expires") {
+            // An invalid Expires means already expired
+            return http_date(expires).map_or(0, |expires| expires - date);
+        }
+        match self.header("last-modified").and_then(http_date) {
+            Some(modified) if HEURISTIC_STATUSES.contains(&self.status) || directives.has("public") => {
+                ((date - modified) / 10).clamp(0, HEURISTIC_MAX_SECS)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Age of the response at `now`, in seconds (RFC 9111 §4.2.3)
+    pub fn current_age(&self, now: i64) -> i64 {
+        let date = self.header("date").and_then(http_date).unwrap_or(self.response_time);
+        let age = self.header("age").and_then(|age| age.trim().parse::<i64>().ok()).unwrap_or(0);
+        let apparent_age = (self.response_time - date).max(0);
+        let corrected_age = age + (self.response_time - self.request_time);
+        apparent_age.max(corrected_age) + (now - self.response_time)
+    }
+
+    /// Servable without revalidation at `now` for a request with `request` directives
+    fn is_fresh(&self, now: i64, request: &Directives) -> bool {
+        if self.directives().has("no-cache") || request.has("no-cache") {
+            return false;
+        }
+        let age = self.current_age(now);
+        if request.seconds("max-age").is_some_and(|max_age| age > max_age) {
+            return false;
+        }
+        age < self.freshness_lifetime()
+    }
+
+    /// `If-None-Match`/`If-Modified-Since` revalidating this response
+    pub fn conditional_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = self.header("etag") {
+            headers.push(("If-None-Match", etag.to_string()));
+        }
+        if let Some(modified) = self.header("last-modified") {
+            headers.push(("If-Modified-Since", modified.to_string()));
+        }
+        headers
+    }
+
+    pub fn response(&self, cache: CacheStatus) -> FetchResponse {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value.as_str())) {
+                headers.append(name, value);
+            }
+        }
+        FetchResponse {
+            url: self.url.clone(),
+            status: self.status,
+            headers,
+            body: base64::engine::general_purpose::STANDARD.decode(&self.body).unwrap_or_default(),
+            certificate: self.certificate.clone(),
+            cache,
+        }
+    }
+
+    fn matches(&self, request_headers: &[(String, String)]) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_value(request_headers, name).as_deref() == value.as_deref())
+    }
+}
+
+/// The request's value of `name`, whitespace-normalized
+fn request_value(headers: &[(String, String)], name: &str) -> Option<String> {
+    let values: Vec<String> = headers
+        .iter()
+        .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    variants: Vec<StoredResponse>,
+}
+
+/// What the cache holds for a request
+pub enum Lookup {
+    Fresh(StoredResponse),
+    /// Needs revalidation before use
+    Stale(StoredResponse),
+    Miss,
+}
+
+/// Every method reads or writes files and may scan the whole directory:
+/// async callers run them with `tokio::task::spawn_blocking`
+pub struct HttpCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Bytes on disk, counted on the first write; writers hold the lock
+    size: Mutex<Option<u64>>,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            size: Mutex::new(None),
+        }
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", hex::encode(Sha256::digest(url.as_bytes()))))
+    }
+
+    fn read(&self, url: &str) -> Entry {
+        std::fs::read(self.path(url))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Entry>(&bytes).ok())
+            .filter(|entry| entry.url == url)
+            .unwrap_or_default()
+    }
+
+    /// The variant of `url` stored for a GET with `request_headers`
+    pub fn lookup(&self, url: &str, request_headers: &[(String, String)], now: i64) -> Lookup {
+        let directives = request_directives(request_headers);
+        if directives.has("no-store") {
+            return Lookup::Miss;
+        }
+        match self.read(url).variants.into_iter().find(|v| v.matches(request_headers)) {
+            Some(stored) if stored.is_fresh(now, &directives) => Lookup::Fresh(stored),
+            Some(stored) => Lookup::Stale(stored),
+            None => Lookup::Miss,
+        }
+    }
+
+    /// Store `response` to a GET of `url` if RFC 9111 §3 allows it and it can
+    /// be reused or revalidated later
+    pub fn store(
+        &self,
+        url: &str,
+        request_headers: &[(String, String)],
+        response: &FetchResponse,
+        request_time: i64,
+        response_time: i64,
+    ) {
+        let request = request_directives(request_headers);
+        let stored = StoredResponse {
+            vary: Vec::new(),
+            url: response.url.clone(),
+            status: response.status,
+            headers: response
+                .headers
+                .iter()
+                .filter(|(name, _)| !UNSTORED_HEADERS.contains(&name.as_str()))
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                .collect(),
+            body: base64::engine::general_purpose::STANDARD.encode(&response.body),
+            certificate: response.certificate.clone(),
+            request_time,
+            response_time,
+        };
+        let directives = stored.directives();
+        let vary: Vec<String> = stored
+            .headers_named("vary")
+            .flat_map(|value| value.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        let storable = !matches!(response.status, 100..=199 | 206 | 304)
+            && !request.has("no-store")
+            && !directives.has("no-store")
+            && !vary.iter().any(|name| name == "*")
+            && (request_value(request_headers, "authorization").is_none() || directives.has("public"));
+        let reusable = stored.freshness_lifetime() > 0 || !stored.conditional_headers().is_empty();
+        if !storable || !reusable {
+            return;
+        }
+
+        let stored = StoredResponse {
+            vary: vary
+                .into_iter()
+                .map(|name| {
+                    let value = request_value(request_headers, &name);
+                    (name, value)
+                })
+                .collect(),
+            ..stored
+        };
+        self.put(url, stored);
+    }
+
+    /// Update `stored` with the headers of a 304 answer (RFC 9111 §4.3.4)
+    /// and keep it; returns the refreshed response
+    pub fn freshen(
+        &self,
+        url: &str,
+        mut stored: StoredResponse,
+        not_modified: &HeaderMap,
+        request_time: i64,
+        response_time: i64,
+    ) -> StoredResponse {
+        for name in not_modified.keys() {
+            if UNSTORED_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            stored.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name.as_str()));
+            for value in not_modified.get_all(name) {
+                if let Ok(value) = value.to_str() {
+                    stored.headers.push((name.to_string(), value.to_string()));
+                }
+            }
+        }
+        stored.request_time = request_time;
+        stored.response_time = response_time;
+        if stored.directives().has("no-store") {
+            self.invalidate(url);
+        } else {
+            self.put(url, stored.clone());
+        }
+        stored
+    }
+
+    /// Forget every variant of `url`
+    pub fn invalidate(&self, url: &str) {
+        let mut size = self.size.lock();
+        let path = self.path(url);
+        let removed = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if std::fs::remove_file(&path).is_ok() {
+            if let Some(size) = size.as_mut() {
+                *size = size.saturating_sub(removed);
+            }
+        }
+    }
+
+    fn put(&self, url: &str, stored: StoredResponse) {
+        let mut size = self.size.lock();
+        let mut entry = self.read(url);
+        entry.url = url.to_string();
+        entry.variants.retain(|v| v.vary != stored.vary);
+        entry.variants.push(stored);
+        if entry.variants.len() > MAX_VARIANTS {
+            entry.variants.remove(0);
+        }
+
+        let path = self.path(url);
+        let previous = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let written = match write_entry(&self.dir, &path, &entry) {
+            Ok(written) => written,
+            Err(e) => {
+                tracing::warn!("Cannot write HTTP cache entry {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let total = size.get_or_insert_with(|| dir_size(&self.dir)).saturating_sub(previous) + written;
+        *size = Some(if total > self.max_bytes { evict(&self.dir, self.max_bytes) } else { total });
+    }
+}
+
+fn request_directives(headers: &[(String, String)]) -> Directives {
+    Directives::parse(
+        headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("cache-control"))
+            .map(|(_, value)| value.as_str()),
+    )
+}
+
+/// Replace `path` through a temporary file; returns the bytes written
+fn write_entry(dir: &Path, path: &Path, entry: &Entry) -> std::io::Result<u64> {
+    std::fs::create_dir_all(dir)?;
+    let contents = serde_json::to_vec(entry)?;
+    let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, &contents)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(contents.len() as u64)
+}
+
+fn entries(dir: &Path) -> Vec<(PathBuf, u64, std::time::SystemTime)> {
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    read.flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|e| e == "json"))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    entries(dir).iter().map(|(_, len, _)| len).sum()
+}
+
+/// Remove the least recently written files until the directory is back
+/// under nine tenths of `max_bytes`; returns its new size
+fn evict(dir: &Path, max_bytes: u64) -> u64 {
+    let mut files = entries(dir);
+    files.sort_by_key(|(_, _, modified)| *modified);
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    for (path, len, _) in files {
+        if size <= max_bytes / 10 * 9 {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            size -= len;
+        }
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(headers: &[(&str, &str)], response_time: i64) -> StoredResponse {
+        StoredResponse {
+            vary: Vec::new(),
+            url: "http://example.com/".to_string(),
+            status: 200,
+            headers: headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+            body: String::new(),
+            certificate: None,
+            request_time: response_time - 1,
+            response_time,
+        }
+    }
+
+    // Sun, 06 Nov 1994 08:49:37 GMT
+    const DATE: i64 = 784111777;
+
+    #[test]
+    fn test_freshness_lifetime_sources() {
+        let date = ("date", "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(stored(&[("cache-control", "public, max-age=60")], DATE).freshness_lifetime(), 60);
+        assert_eq!(stored(&[date, ("expires", "Sun, 06 Nov 1994 09:49:37 GMT")], DATE).freshness_lifetime(), 3600);
+        assert_eq!(stored(&[date, ("expires", "0")], DATE).freshness_lifetime(), 0);
+        // Max-age wins over Expires
+        assert_eq!(
+            stored(&[("cache-control", "max-age=5"), ("expires", "Sun, 06 Nov 1994 09:49:37 GMT")], DATE)
+                .freshness_lifetime(),
+            5
+        );
+        // A tenth of the time since Last-Modified
+        assert_eq!(stored(&[date, ("last-modified", "Sun, 06 Nov 1994 07:49:37 GMT")], DATE).freshness_lifetime(), 360);
+        assert_eq!(stored(&[date], DATE).freshness_lifetime(), 0);
+    }
+
+    #[test]
+    fn test_current_age_counts_age_delay_and_residence() {
+        let response = stored(&[("date", "Sun, 06 Nov 1994 08:49:37 GMT"), ("age", "30")], DATE + 2);
+        // max(apparent 2, 30 + 1 delay) + 10 resident
+        assert_eq!(response.current_age(DATE + 12), 41);
+
+        let directives = Directives::parse(["no-cache"].into_iter());
+        let fresh = stored(&[("cache-control", "max-age=60")], DATE);
+        assert!(fresh.is_fresh(DATE + 10, &Directives::default()));
+        assert!(!fresh.is_fresh(DATE + 10, &directives));
+        assert!(!fresh.is_fresh(DATE + 10, &Directives::parse(["max-age=5"].into_iter())));
+        assert!(!fresh.is_fresh(DATE + 60, &Directives::default()));
+        assert!(!stored(&[("cache-control", "no-cache, max-age=60")], DATE).is_fresh(DATE, &Directives::default()));
+    }
+
+    #[test]
+    fn test_variants_follow_vary_and_storage_rules() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-http-cache-{}", uuid::Uuid::new_v4()));
+        let cache = HttpCache::new(dir.clone(), 1 << 20);
+        let url = "http://example.com/page";
+        let response = |headers: &[(&'static str, &'static str)], body: &str| {
+            let mut map = HeaderMap::new();
+            for (name, value) in headers {
+                map.append(*name, HeaderValue::from_static(value));
+            }
+            FetchResponse {
+                url: url.to_string(),
+                status: 200,
+                headers: map,
+                body: body.as_bytes().to_vec(),
+                certificate: None,
+                cache: CacheStatus::Miss,
+            }
+        };
+        let english = vec![("Accept-Language".to_string(), "en".to_string())];
+        let spanish = vec![("Accept-Language".to_string(), "es".to_string())];
+        let now = chrono::Utc::now().timestamp();
+
+        let varying = response(&[("cache-control", "max-age=600"), ("vary", "Accept-Language")], "hello");
+        cache.store(url, &english, &varying, now, now);
+        let Lookup::Fresh(hit) = cache.lookup(url, &english, now + 1) else { panic!("expected a fresh hit") };
+        assert_eq!(hit.response(CacheStatus::Hit).body, b"hello");
+        assert!(matches!(cache.lookup(url, &spanish, now + 1), Lookup::Miss));
+
+        let stale = response(&[("cache-control", "no-cache"), ("etag", "\"v1\"")], "hola");
+        cache.store(url, &spanish, &stale, now, now);
+        let Lookup::Stale(stale) = cache.lookup(url, &[], now) else { panic!("expected a stale entry") };
+        assert_eq!(stale.conditional_headers(), vec![("If-None-Match", "\"v1\"".to_string())]);
+        // The English variant is still there
+        assert!(matches!(cache.lookup(url, &english, now + 1), Lookup::Fresh(_)));
+
+        let mut not_modified = HeaderMap::new();
+        not_modified.insert("cache-control", HeaderValue::from_static("max-age=60"));
+        let freshened = cache.freshen(url, stale, &not_modified, now, now);
+        assert_eq!(freshened.header("etag"), Some("\"v1\""));
+        assert!(matches!(cache.lookup(url, &spanish, now + 1), Lookup::Fresh(_)));
+
+        // Neither reusable nor revalidatable, or forbidden
+        cache.invalidate(url);
+        cache.store(url, &[], &response(&[], "plain"), now, now);
+        cache.store(url, &[], &response(&[("cache-control", "no-store, max-age=60")], "secret"), now, now);
+        cache.store(url, &[], &response(&[("cache-control", "max-age=60"), ("vary", "*")], "any"), now, now);
+        assert!(matches!(cache.lookup(url, &[], now), Lookup::Miss));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_oldest_entries_are_evicted() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-http-cache-{}", uuid::Uuid::new_v4()));
+        let cache = HttpCache::new(dir.clone(), 2000);
+        let now = chrono::Utc::now().timestamp();
+        for i in 0..10 {
+            let url = format!("http://example.com/{}", i);
+            let mut headers = HeaderMap::new();
+            headers.insert("cache-control", HeaderValue::from_static("max-age=600"));
+            let response = FetchResponse {
+                url: url.clone(),
+                status: 200,
+                headers,
+                body: vec![b'x'; 300],
+                certificate: None,
+                cache: CacheStatus::Miss,
+            };
+            cache.store(&url, &[], &response, now, now);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert!(dir_size(&dir) <= 2000);
+        assert!(matches!(cache.lookup("http://example.com/9", &[], now), Lookup::Fresh(_)));
+        assert!(matches!(cache.lookup("http://example.com/0", &[], now), Lookup::Miss));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
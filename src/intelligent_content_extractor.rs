@@ -1,27 +1,26 @@
-/// INTELLIGENT CONTENT EXTRACTOR - Valuable Data Finder
-/// Extrae SOLO contenido valioso e importante de páginas
-/// Ignora: ads, navs, footers, boilerplate
-
-use serde::{Deserialize, Serialize};
-
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ValuableContent {
-    pub content_id: String,
-    pub url: String,
-    pub title: String,
-    pub main_content: String,
-    pub content_type: String,
-    pub extraction_confidence: f32,
-    pub key_points: Vec<String>,
-    pub metadata: ContentMetadata,
+//! INTELLIGENT CONTENT EXTRACTOR - Valuable Data Finder
+//! Extrae SOLO contenido valioso e importante de páginas
+//! Ignora: ads, navs, footers, boilerplate
+
+crate::tool_output! {
+    pub struct ValuableContent {
+        required content_id: String = {"type": "string"},
+        required url: String = {"type": "string"},
+        required title: String = {"type": "string"},
+        required main_content: String = {"type": "string"},
+        required content_type: String = {"type": "string"},
+        required extraction_confidence: f32 = {"type": "number", "minimum": 0, "maximum": 1},
+        required key_points: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required metadata: ContentMetadata = (ContentMetadata::schema()),
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ContentMetadata {
-    pub word_count: u32,
-    pub language: String,
-    pub keywords: Vec<String>,
+crate::tool_output! {
+    pub struct ContentMetadata {
+        required word_count: u32 = {"type": "integer", "minimum": 0},
+        required language: String = {"type": "string"},
+        required keywords: Vec<String> = {"type": "array", "items": {"type": "string"}},
+    }
 }
 
 pub struct IntelligentContentExtractor;
@@ -38,12 +37,7 @@ impl IntelligentContentExtractor {
         let content_type = self.detect_content_type(html);
         let key_points = self.extract_key_points(&main_content);
         let word_count = main_content.split_whitespace().count() as u32;
-        
-        let keywords = vec![
-            "important".to_string(),
-            "security".to_string(),
-            "technology".to_string(),
-        ];
+        let keywords = self.extract_keywords(&main_content, 5);
 
         ValuableContent {
             content_id: format!("content_{}", uuid::Uuid::new_v4()),
@@ -137,6 +131,26 @@ impl IntelligentContentExtractor {
         .to_string()
     }
 
+    /// Palabras más frecuentes (sin stopwords ni palabras cortas)
+    fn extract_keywords(&self, text: &str, limit: usize) -> Vec<String> {
+        const STOPWORDS: &[&str] = &[
+            "this", "that", "with", "from", "have", "will", "your", "about", "there", "their",
+            "which", "were", "been", "into", "more", "than", "they", "them", "what", "when",
+        ];
+
+        let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+        for word in text.split(|c: char| !c.is_alphanumeric()) {
+            let word = word.to_lowercase();
+            if word.chars().count() >= 4 && !STOPWORDS.contains(&word.as_str()) {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+        }
+
+        let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.into_iter().take(limit).map(|(word, _)| word).collect()
+    }
+
     fn extract_key_points(&self, text: &str) -> Vec<String> {
         let sentences: Vec<&str> = text.split(|c| c == '.' || c == '!' || c == '?').collect();
         
@@ -163,4 +177,13 @@ mod tests {
         let result = extractor.extract_valuable_content("http://example.com", html);
         assert_eq!(result.title, "Test Title");
     }
+
+    #[test]
+    fn test_keywords_come_from_content() {
+        let extractor = IntelligentContentExtractor::new();
+        let html = "<article><p>Rust crates: rust tooling makes crates easy. Rust wins.</p></article>";
+        let result = extractor.extract_valuable_content("http://example.com", html);
+        assert_eq!(result.metadata.keywords[0], "rust");
+        assert!(result.metadata.keywords.contains(&"crates".to_string()));
+    }
 }
@@ -21,6 +21,39 @@ pub mod geolocation_security_intelligence;  // Geographic security hotspots
 pub mod database_persistence;  // RocksDB Native Rust persistence
 pub mod cloud_persistence;  // GitHub Gists PRIVADOS (gratis + seguro)
 
+pub mod runtime;  // Async locks/spawn nativo + WASM
+pub mod config;  // browsermcp.toml + env + CLI flags
+pub mod robots;  // robots.txt cache consulted before every fetch
+pub mod fetch;  // Shared HTTP client: pooling, per-host politeness, retries
+pub mod tls;  // Certificate verification policy and summaries
+pub mod http_cache;  // RFC 9111 on-disk cache behind the fetcher
+pub mod cookies;  // Per-session cookie jars (RFC 6265)
+pub mod models;  // Tool result models + outputSchema
+
+// MCP PROTOCOL
+pub mod mcp;  // JSON-RPC 2.0 envelope + tool results
+
+// SHARED TYPES (browser, low_level, testing, social_bots)
+pub mod core;  // BrowserInstance, Cookie, Result
+pub mod credentials;  // Argon2-hashed credentials
+pub mod anti_detection;  // User agents + headers
+
+// OPTIONAL TOOLKITS (cargo features, all on by default)
+#[cfg(feature = "scraping")]
+pub mod scraping;  // DomParser, DataExtractor, DataTransformer, BatchProcessor
+#[cfg(feature = "forms")]
+pub mod forms;  // FormDetector, FormFiller, FormSubmitter, CaptchaHandler
+#[cfg(feature = "browser")]
+pub mod browser;  // BrowserPool + headless automation
+#[cfg(feature = "low-level")]
+pub mod low_level;  // Raw HTTP client, sockets, request crafting
+#[cfg(feature = "testing")]
+pub mod testing;  // Web, performance and security tests
+#[cfg(feature = "api")]
+pub mod api;  // axum REST handlers (health, browsers)
+#[cfg(feature = "social-bots")]
+pub mod social_bots;  // Facebook, Instagram, LinkedIn, TikTok, Twitter
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -1,6 +1,8 @@
 // Custom HTTP Client with advanced features
 use crate::core::Result;
+use crate::fetch::{self, FetchRequest};
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct CustomHttpClient {
@@ -22,12 +24,26 @@ impl CustomHttpClient {
 
     pub async fn get(&self, url: &str) -> Result<String> {
         tracing::info!("HTTP GET: {}", url);
-        Ok(format!("Response from {}", url))
+        self.send(FetchRequest::get(url)).await
     }
 
     pub async fn post(&self, url: &str, body: &str) -> Result<String> {
         tracing::info!("HTTP POST: {} with body length: {}", url, body.len());
-        Ok(format!("Post response from {}", url))
+        self.send(FetchRequest::post(url, body)).await
+    }
+
+    /// Through the shared fetcher, with this client's headers, timeout and proxy
+    async fn send(&self, mut request: FetchRequest) -> Result<String> {
+        request = request
+            .header("User-Agent", &self.user_agent)
+            .timeout(Duration::from_millis(self.timeout_ms));
+        for (key, value) in &self.headers {
+            request = request.header(key, value);
+        }
+        if let Some(proxy) = &self.proxy {
+            request = request.proxy(proxy);
+        }
+        Ok(fetch::global().fetch(request).await?.text())
     }
 
     pub fn with_proxy(mut self, proxy: &str) -> Self {
@@ -0,0 +1,411 @@
+//! Append-only JSONL audit log of tool invocations
+//!
+//! The dispatcher writes one line per `tools/call`, and per read or
+//! subscribe of a `browser://page/` resource (which fetches the page): who
+//! called (session and API key), which tool, redacted arguments, the hosts
+//! named in them, how long it took and how it ended. Files are per UTC day
+//! (`audit-2026-01-31.jsonl`); a day's file is rotated to
+//! `audit-2026-01-31.1.jsonl`, `.2`, ... when it reaches the size limit.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::jsonrpc::RpcError;
+
+pub const DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Argument strings longer than this (page HTML, ...) are logged as a length
+const MAX_LOGGED_STRING: usize = 512;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Object keys whose values never reach the log
+const SENSITIVE_KEYS: &[&str] = &[
+    "password", "passwd", "secret", "token", "api_key", "apikey", "authorization", "cookie", "credential",
+];
+
+/// URL query parameters whose values never reach the log
+const SENSITIVE_PARAMS: &[&str] = &[
+    "password", "secret", "token", "access_token", "api_key", "apikey", "key", "sig", "signature", "code",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditStatus {
+    /// The tool ran and succeeded
+    Ok,
+    /// The tool ran and reported an error
+    Error,
+    /// The call was refused before running (unknown or disallowed tool,
+    /// invalid arguments)
+    Rejected,
+}
+
+impl AuditStatus {
+    /// How a `tools/call` ended
+    pub fn of(outcome: &Result<Value, RpcError>) -> Self {
+        match outcome {
+            Ok(result) if result.get("isError") == Some(&Value::Bool(true)) => AuditStatus::Error,
+            Ok(_) => AuditStatus::Ok,
+            Err(_) => AuditStatus::Rejected,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditStatus::Ok => "ok",
+            AuditStatus::Error => "error",
+            AuditStatus::Rejected => "rejected",
+        }
+    }
+}
+
+crate::tool_output! {
+    #[derive(PartialEq)]
+    pub struct AuditEntry {
+        required timestamp: String = {"type": "string", "format": "date-time"},
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        optional session: Option<String> = {"type": "string"},
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        optional client: Option<String> = {"type": "string", "description": "API key name"},
+        required tool: String = {"type": "string"},
+        required arguments: Value = {"description": "Arguments with secrets redacted"},
+        required hosts: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required duration_ms: u64 = {"type": "integer", "minimum": 0},
+        required status: AuditStatus = {"type": "string", "enum": ["ok", "error", "rejected"]},
+        required result_bytes: usize = {"type": "integer", "minimum": 0},
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        optional error: Option<String> = {"type": "string"},
+    }
+}
+
+impl AuditEntry {
+    /// Entry for a finished call of `tool` with `arguments`
+    pub fn new(
+        session: Option<&str>,
+        client: Option<&str>,
+        tool: &str,
+        arguments: &Value,
+        duration: Duration,
+        outcome: &Result<Value, RpcError>,
+    ) -> Self {
+        let status = AuditStatus::of(outcome);
+        let (result_bytes, error) = match outcome {
+            Ok(result) if status == AuditStatus::Error => {
+                let message = result["content"][0]["text"].as_str().map(|text| truncate(text, 200));
+                (result.to_string().len(), message)
+            }
+            Ok(result) => (result.to_string().len(), None),
+            Err(e) => (0, Some(e.message.clone())),
+        };
+        let mut hosts = Vec::new();
+        collect_hosts(arguments, &mut hosts);
+        hosts.sort();
+        hosts.dedup();
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            session: session.filter(|s| !s.is_empty()).map(String::from),
+            client: client.map(String::from),
+            tool: tool.to_string(),
+            arguments: redact(arguments),
+            hosts,
+            duration_ms: duration.as_millis() as u64,
+            status,
+            result_bytes,
+            error,
+        }
+    }
+}
+
+/// Filters of [`AuditLog::query`]
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub tool: Option<String>,
+    pub client: Option<String>,
+    pub limit: usize,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let Ok(at) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+            return false;
+        };
+        let at = at.with_timezone(&Utc);
+        self.since.is_none_or(|since| at >= since)
+            && self.until.is_none_or(|until| at <= until)
+            && self.tool.as_ref().is_none_or(|tool| &entry.tool == tool)
+            && self.client.as_ref().is_none_or(|client| entry.client.as_ref() == Some(client))
+    }
+
+    /// Could a file of `date` (`YYYY-MM-DD`) hold matching entries?
+    fn covers_day(&self, date: &str) -> bool {
+        let day = |at: &DateTime<Utc>| at.format("%Y-%m-%d").to_string();
+        self.since.as_ref().is_none_or(|since| date >= day(since).as_str())
+            && self.until.as_ref().is_none_or(|until| date <= day(until).as_str())
+    }
+}
+
+struct Writer {
+    date: String,
+    file: File,
+    size: u64,
+}
+
+pub struct AuditLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    writer: Mutex<Option<Writer>>,
+}
+
+impl AuditLog {
+    /// Log into `dir` (created if needed), rotating files at `max_bytes`
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self, String> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(Self {
+            dir,
+            max_bytes: max_bytes.max(1),
+            writer: Mutex::new(None),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn current_path(&self, date: &str) -> PathBuf {
+        self.dir.join(format!("audit-{}.jsonl", date))
+    }
+
+    /// Move the day's current file to the next free `.N` name
+    fn rotate(&self, date: &str) -> std::io::Result<()> {
+        let mut n = 1;
+        while self.dir.join(format!("audit-{}.{}.jsonl", date, n)).exists() {
+            n += 1;
+        }
+        std::fs::rename(self.current_path(date), self.dir.join(format!("audit-{}.{}.jsonl", date, n)))
+    }
+
+    fn open_writer(&self, date: &str) -> std::io::Result<Writer> {
+        let file = OpenOptions::new().create(true).append(true).open(self.current_path(date))?;
+        let size = file.metadata()?.len();
+        Ok(Writer {
+            date: date.to_string(),
+            file,
+            size,
+        })
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), String> {
+        let mut line = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        let date = Utc::now().format("%Y-%m-%d").to_string();
+
+        let mut writer = self.writer.lock();
+        if writer.as_ref().is_none_or(|w| w.date != date) {
+            *writer = Some(self.open_writer(&date).map_err(|e| e.to_string())?);
+        }
+        if let Some(current) = writer.as_ref() {
+            if current.size > 0 && current.size + line.len() as u64 > self.max_bytes {
+                *writer = None;
+                self.rotate(&date).map_err(|e| e.to_string())?;
+                *writer = Some(self.open_writer(&date).map_err(|e| e.to_string())?);
+            }
+        }
+        let Some(current) = writer.as_mut() else {
+            return Err("audit log not open".to_string());
+        };
+        current.file.write_all(&line).map_err(|e| e.to_string())?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Push appended entries to disk
+    pub fn flush(&self) -> Result<(), String> {
+        match self.writer.lock().as_mut() {
+            Some(current) => current.file.sync_data().map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /// Matching entries, newest first, at most `query.limit`; the flag is
+    /// true when more entries matched
+    pub fn query(&self, query: &AuditQuery) -> Result<(Vec<AuditEntry>, bool), String> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .map_err(|e| format!("{}: {}", self.dir.display(), e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_prefix("audit-"))
+                    .filter(|n| n.ends_with(".jsonl") && n.len() >= 10)
+                    .is_some_and(|n| query.covers_day(&n[..10]))
+            })
+            .collect();
+        files.sort();
+
+        let mut entries = Vec::new();
+        for path in files {
+            let file = File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            entries.extend(
+                BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| serde_json::from_str::<AuditEntry>(&line).ok())
+                    .filter(|entry| query.matches(entry)),
+            );
+        }
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        let truncated = entries.len() > query.limit;
+        entries.truncate(query.limit);
+        Ok((entries, truncated))
+    }
+}
+
+/// Arguments with secrets masked and long strings replaced by their length
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| {
+                    let lower = key.to_ascii_lowercase();
+                    if SENSITIVE_KEYS.iter().any(|s| lower.contains(s)) {
+                        (key.clone(), json!(REDACTED))
+                    } else {
+                        (key.clone(), redact(value))
+                    }
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        Value::String(text) => match reqwest::Url::parse(text) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => json!(redact_url(url)),
+            _ if text.len() > MAX_LOGGED_STRING => json!(format!("[{} chars]", text.len())),
+            _ => value.clone(),
+        },
+        other => other.clone(),
+    }
+}
+
+fn redact_url(mut url: reqwest::Url) -> String {
+    if url.password().is_some() {
+        let _ = url.set_password(Some(REDACTED));
+    }
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let sensitive = SENSITIVE_PARAMS.contains(&k.to_ascii_lowercase().as_str());
+                (k.into_owned(), if sensitive { REDACTED.to_string() } else { v.into_owned() })
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+/// Hosts of the http(s) URLs anywhere in the arguments
+fn collect_hosts(value: &Value, hosts: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => fields.values().for_each(|v| collect_hosts(v, hosts)),
+        Value::Array(items) => items.iter().for_each(|v| collect_hosts(v, hosts)),
+        Value::String(text) => {
+            if let Ok(url) = reqwest::Url::parse(text) {
+                if let (true, Some(host)) = (matches!(url.scheme(), "http" | "https"), url.host_str()) {
+                    hosts.push(host.to_ascii_lowercase());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("browsermcp_audit_{}", uuid::Uuid::new_v4()))
+    }
+
+    fn entry(tool: &str, client: &str) -> AuditEntry {
+        let outcome = Ok(json!({"content": [{"type": "text", "text": "{}"}], "isError": false}));
+        let arguments = json!({"url": "https://example.com/a"});
+        AuditEntry::new(Some("s1"), Some(client), tool, &arguments, Duration::from_millis(12), &outcome)
+    }
+
+    #[test]
+    fn test_redaction_and_hosts() {
+        let arguments = json!({
+            "urls": ["https://user:pw@Example.com/x?token=abc&page=2", "http://other.org"],
+            "api_key": "bmcp_secret",
+            "html": "x".repeat(2000),
+            "nested": {"Authorization": "Bearer abc"}
+        });
+        let failed = Err(RpcError::invalid_params("bad"));
+        let entry = AuditEntry::new(None, None, "web_scrape_batch", &arguments, Duration::ZERO, &failed);
+
+        assert_eq!(entry.hosts, vec!["example.com", "other.org"]);
+        assert_eq!(entry.status, AuditStatus::Rejected);
+        let logged = entry.arguments.to_string();
+        assert!(!logged.contains("bmcp_secret") && !logged.contains("token=abc") && !logged.contains(":pw@"));
+        assert!(logged.contains("page=2"));
+        assert_eq!(entry.arguments["html"], "[2000 chars]");
+        assert_eq!(entry.arguments["nested"]["Authorization"], REDACTED);
+    }
+
+    #[test]
+    fn test_append_rotate_and_query() {
+        let dir = temp_dir();
+        let log = AuditLog::open(&dir, 600).unwrap();
+        for i in 0..6 {
+            log.append(&entry(if i % 2 == 0 { "web_scrape" } else { "dom_extraction" }, "ci")).unwrap();
+        }
+        log.append(&entry("web_scrape", "admin")).unwrap();
+
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        assert!(files > 1, "expected rotation, got {} files", files);
+
+        let all = AuditQuery {
+            limit: 100,
+            ..Default::default()
+        };
+        assert_eq!(log.query(&all).unwrap().0.len(), 7);
+
+        let scrapes_by_ci = AuditQuery {
+            tool: Some("web_scrape".to_string()),
+            client: Some("ci".to_string()),
+            limit: 2,
+            ..Default::default()
+        };
+        let (entries, truncated) = log.query(&scrapes_by_ci).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(truncated);
+        assert!(entries[0].timestamp >= entries[1].timestamp);
+
+        let future = AuditQuery {
+            since: Some(Utc::now() + chrono::Duration::days(1)),
+            limit: 100,
+            ..Default::default()
+        };
+        assert!(log.query(&future).unwrap().0.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,247 @@
+//! API-key authentication and origin validation for the HTTP transport
+//!
+//! Keys are configured as argon2 PHC hashes (never plaintext), each with an
+//! optional tool allowlist:
+//!
+//! ```json
+//! {
+//!   "api_keys": [
+//!     {"name": "ci", "hash": "$argon2id$v=19$...", "tools": ["web_scrape", "dom_extraction"],
+//!      "rate_limit": {"requests_per_second": 1.0, "burst_size": 5}, "max_concurrent": 2},
+//!     {"name": "admin", "hash": "$argon2id$v=19$..."}
+//!   ],
+//!   "allowed_origins": ["https://dashboard.example.com"]
+//! }
+//! ```
+//!
+//! Generate a key and its hash with `browsermcp-server hash-key`.
+
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use parking_lot::Mutex;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::quota::QuotaConfig;
+
+/// Prefix of generated keys, so they are recognizable in configs and logs
+pub const KEY_PREFIX: &str = "bmcp_";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Browser origins allowed besides localhost
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+impl AuthConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    /// argon2 PHC string of the key
+    pub hash: String,
+    /// Tools this key may list and call; all tools when absent
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// Rate limit and concurrency cap replacing the server defaults
+    #[serde(default, flatten)]
+    pub quota: QuotaConfig,
+}
+
+/// Who an authenticated request belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyIdentity {
+    pub name: String,
+    pub tools: Option<Arc<HashSet<String>>>,
+    pub quota: QuotaConfig,
+}
+
+impl ApiKeyIdentity {
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        self.tools.as_ref().is_none_or(|tools| tools.contains(tool))
+    }
+}
+
+pub struct ApiKeyStore {
+    keys: Vec<ApiKeyIdentity>,
+    hashes: Vec<String>,
+    // SHA-256 of keys that already passed argon2, so each key pays the
+    // slow hash once per process
+    verified: Mutex<HashMap<[u8; 32], usize>>,
+}
+
+impl ApiKeyStore {
+    pub fn new(keys: Vec<ApiKeyConfig>) -> Result<Self, String> {
+        let mut identities = Vec::new();
+        let mut hashes = Vec::new();
+        for key in keys {
+            PasswordHash::new(&key.hash).map_err(|e| format!("API key {}: invalid hash: {}", key.name, e))?;
+            key.quota.validate().map_err(|e| format!("API key {}: {}", key.name, e))?;
+            identities.push(ApiKeyIdentity {
+                name: key.name,
+                tools: key.tools.map(|tools| Arc::new(tools.into_iter().collect())),
+                quota: key.quota,
+            });
+            hashes.push(key.hash);
+        }
+        Ok(Self {
+            keys: identities,
+            hashes,
+            verified: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Identity of a presented key, `None` if it matches no configured hash.
+    /// CPU-heavy on a cache miss: call from a blocking task.
+    pub fn authenticate(&self, presented: &str) -> Option<ApiKeyIdentity> {
+        let digest: [u8; 32] = Sha256::digest(presented.as_bytes()).into();
+        if let Some(&index) = self.verified.lock().get(&digest) {
+            return Some(self.keys[index].clone());
+        }
+
+        let argon2 = Argon2::default();
+        let index = self.hashes.iter().position(|hash| {
+            PasswordHash::new(hash)
+                .map(|parsed| argon2.verify_password(presented.as_bytes(), &parsed).is_ok())
+                .unwrap_or(false)
+        })?;
+        self.verified.lock().insert(digest, index);
+        Some(self.keys[index].clone())
+    }
+}
+
+/// argon2 PHC hash of `key`, for the `hash` field of the config
+pub fn hash_key(key: &str) -> Result<String, String> {
+    let salt = SaltString::generate(rand::thread_rng());
+    Argon2::default()
+        .hash_password(key.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Hash error: {}", e))
+}
+
+/// New random API key
+pub fn generate_key() -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    format!("{}{}", KEY_PREFIX, hex::encode(secret))
+}
+
+/// Key presented as `Authorization: Bearer <key>` or `X-API-Key: <key>`
+pub fn presented_key<'a>(authorization: Option<&'a str>, api_key: Option<&'a str>) -> Option<&'a str> {
+    authorization
+        .and_then(|value| value.strip_prefix("Bearer ").or_else(|| value.strip_prefix("bearer ")))
+        .or(api_key)
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+/// Browser `Origin` check against DNS rebinding: loopback origins plus the
+/// configured ones are accepted
+#[derive(Debug, Clone, Default)]
+pub struct OriginPolicy {
+    allowed: Vec<String>,
+}
+
+impl OriginPolicy {
+    pub fn new(allowed: Vec<String>) -> Self {
+        Self {
+            allowed: allowed.iter().map(|o| o.trim_end_matches('/').to_ascii_lowercase()).collect(),
+        }
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        let Ok(url) = reqwest::Url::parse(origin) else {
+            return false;
+        };
+        let loopback = matches!(
+            url.host_str(),
+            Some("localhost") | Some("127.0.0.1") | Some("[::1]")
+        );
+        loopback || self.allowed.contains(&origin.trim_end_matches('/').to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashed_keys_and_allowlists() {
+        let ci_key = generate_key();
+        let admin_key = generate_key();
+        let store = ApiKeyStore::new(vec![
+            ApiKeyConfig {
+                name: "ci".to_string(),
+                hash: hash_key(&ci_key).unwrap(),
+                tools: Some(vec!["web_scrape".to_string()]),
+                quota: QuotaConfig::default(),
+            },
+            ApiKeyConfig {
+                name: "admin".to_string(),
+                hash: hash_key(&admin_key).unwrap(),
+                tools: None,
+                quota: QuotaConfig::default(),
+            },
+        ])
+        .unwrap();
+
+        let ci = store.authenticate(&ci_key).unwrap();
+        assert_eq!(ci.name, "ci");
+        assert!(ci.allows_tool("web_scrape"));
+        assert!(!ci.allows_tool("vulnerability_scanner"));
+        // Second lookup comes from the verified cache
+        assert_eq!(store.authenticate(&ci_key), Some(ci));
+
+        assert!(store.authenticate(&admin_key).unwrap().allows_tool("anything"));
+        assert!(store.authenticate("bmcp_wrong").is_none());
+    }
+
+    #[test]
+    fn test_invalid_hash_is_rejected() {
+        let config = ApiKeyConfig {
+            name: "bad".to_string(),
+            hash: "plaintext-key".to_string(),
+            tools: None,
+            quota: QuotaConfig::default(),
+        };
+        assert!(ApiKeyStore::new(vec![config]).is_err());
+    }
+
+    #[test]
+    fn test_presented_key() {
+        assert_eq!(presented_key(Some("Bearer abc"), None), Some("abc"));
+        assert_eq!(presented_key(None, Some("xyz")), Some("xyz"));
+        assert_eq!(presented_key(Some("Basic abc"), None), None);
+        assert_eq!(presented_key(Some("Bearer "), None), None);
+    }
+
+    #[test]
+    fn test_origin_policy() {
+        let policy = OriginPolicy::new(vec!["https://dashboard.example.com/".to_string()]);
+        assert!(policy.allows("http://localhost:5173"));
+        assert!(policy.allows("http://127.0.0.1"));
+        assert!(policy.allows("https://dashboard.example.com"));
+        assert!(!policy.allows("https://evil.example.com"));
+        assert!(!policy.allows("null"));
+    }
+}
@@ -0,0 +1,232 @@
+//! Running tools from the command line, without an MCP host
+//!
+//! `browsermcp-server call web_scrape --url https://example.com --selectors h1,p`
+//! turns each `--flag value` into an argument, typed by the tool's
+//! `inputSchema`: arrays split on commas, numbers and booleans are parsed,
+//! objects are JSON. `--json FILE` (or `--json -` for stdin) supplies the
+//! whole arguments object; flags given next to it override its fields.
+
+use serde_json::{Map, Value};
+use std::io::Read;
+
+use super::registry::{McpTool, ToolRegistry};
+
+/// `tools/call` arguments for `tool` from command-line `flags`; `stdin` is
+/// read for `--json -`
+pub fn tool_arguments(tool: &dyn McpTool, flags: &[String], stdin: impl Read) -> Result<Value, String> {
+    let schema = tool.input_schema();
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    let mut arguments = Map::new();
+    let mut stdin = Some(stdin);
+
+    let mut i = 0;
+    while i < flags.len() {
+        let flag = &flags[i];
+        let Some(name) = flag.strip_prefix("--") else {
+            return Err(format!("unexpected argument `{}`: tool arguments are --name value", flag));
+        };
+        let (name, inline) = match name.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (name, None),
+        };
+        let name = name.replace('-', "_");
+        let property = properties.and_then(|p| p.get(&name));
+
+        // Booleans may be given bare (`--verbose`)
+        let bare_bool = inline.is_none()
+            && property.is_some_and(|p| primary_type(p) == Some("boolean"))
+            && flags.get(i + 1).is_none_or(|next| next.starts_with("--"));
+        let raw = match inline {
+            Some(value) => value,
+            None if bare_bool => "true".to_string(),
+            None => {
+                i += 1;
+                flags.get(i).cloned().ok_or_else(|| format!("--{} needs a value", name))?
+            }
+        };
+        i += 1;
+
+        if name == "json" && property.is_none() {
+            let text = if raw == "-" {
+                let mut text = String::new();
+                stdin
+                    .take()
+                    .ok_or("stdin can only be read once")?
+                    .read_to_string(&mut text)
+                    .map_err(|e| format!("cannot read stdin: {}", e))?;
+                text
+            } else {
+                std::fs::read_to_string(&raw).map_err(|e| format!("cannot read {}: {}", raw, e))?
+            };
+            let Value::Object(base) = serde_json::from_str(&text).map_err(|e| format!("--json {}: {}", raw, e))?
+            else {
+                return Err(format!("--json {}: arguments must be a JSON object", raw));
+            };
+            // Flags already seen win over the file
+            for (key, value) in base {
+                arguments.entry(key).or_insert(value);
+            }
+            continue;
+        }
+
+        let Some(property) = property else {
+            let known: Vec<&str> = properties.map(|p| p.keys().map(String::as_str).collect()).unwrap_or_default();
+            return Err(format!(
+                "{} has no argument `{}` (arguments: {})",
+                tool.name(),
+                name,
+                known.join(", ")
+            ));
+        };
+        let value = coerce(property, &raw).map_err(|e| format!("--{}: {}", name, e))?;
+        arguments.insert(name, value);
+    }
+
+    Ok(Value::Object(arguments))
+}
+
+/// One line per tool for `list-tools`: name, then description
+pub fn tool_table(registry: &ToolRegistry) -> String {
+    let names = registry.names();
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+    names
+        .iter()
+        .filter_map(|name| registry.get(name))
+        .map(|t| format!("{:width$}  {}\n", t.name(), t.description(), width = width))
+        .collect()
+}
+
+/// Usage line for `describe`: `call NAME --required <type> [--optional <type>]`
+pub fn usage(tool: &dyn McpTool) -> String {
+    let schema = tool.input_schema();
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(|r| r.as_array())
+        .map(|r| r.iter().filter_map(|n| n.as_str()).collect())
+        .unwrap_or_default();
+    let mut usage = format!("call {}", tool.name());
+    if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+        for (name, property) in properties {
+            let flag = format!("--{} <{}>", name, primary_type(property).unwrap_or("value"));
+            if required.contains(&name.as_str()) {
+                usage.push_str(&format!(" {}", flag));
+            } else {
+                usage.push_str(&format!(" [{}]", flag));
+            }
+        }
+    }
+    usage
+}
+
+/// The schema's `type`, ignoring `null` in type unions
+fn primary_type(schema: &Value) -> Option<&str> {
+    match schema.get("type")? {
+        Value::String(t) => Some(t),
+        Value::Array(types) => types.iter().filter_map(|t| t.as_str()).find(|t| *t != "null"),
+        _ => None,
+    }
+}
+
+fn coerce(schema: &Value, raw: &str) -> Result<Value, String> {
+    match primary_type(schema) {
+        Some("array") => {
+            // A JSON array is taken as is; otherwise comma-separated items
+            if raw.trim_start().starts_with('[') {
+                return serde_json::from_str(raw).map_err(|e| e.to_string());
+            }
+            let items = schema.get("items").cloned().unwrap_or(Value::Null);
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| coerce(&items, item))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array)
+        }
+        Some("object") => serde_json::from_str(raw).map_err(|e| format!("expected a JSON object: {}", e)),
+        Some("integer") => raw
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| format!("expected an integer, got `{}`", raw)),
+        Some("number") => raw
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("expected a number, got `{}`", raw)),
+        Some("boolean") => match raw {
+            "true" | "yes" | "1" => Ok(Value::Bool(true)),
+            "false" | "no" | "0" => Ok(Value::Bool(false)),
+            _ => Err(format!("expected true or false, got `{}`", raw)),
+        },
+        _ => Ok(Value::String(raw.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::FnTool;
+    use serde_json::json;
+
+    fn tool() -> impl McpTool {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string"},
+                "selectors": {"type": "array", "items": {"type": "string"}},
+                "limit": {"type": ["integer", "null"]},
+                "follow": {"type": "boolean"},
+                "headers": {"type": "object"}
+            },
+            "required": ["url"]
+        });
+        FnTool::new("fetch", "Fetch a page", schema, |args| std::future::ready(Ok(args)))
+    }
+
+    fn args(flags: &[&str]) -> Vec<String> {
+        flags.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_flags_are_typed_by_schema() {
+        let flags = args(&[
+            "--url", "https://example.com", "--selectors", "h1, p", "--limit=5", "--follow", "--headers", r#"{"a":"b"}"#,
+        ]);
+        let arguments = tool_arguments(&tool(), &flags, std::io::empty()).unwrap();
+        assert_eq!(
+            arguments,
+            json!({
+                "url": "https://example.com",
+                "selectors": ["h1", "p"],
+                "limit": 5,
+                "follow": true,
+                "headers": {"a": "b"}
+            })
+        );
+
+        let unknown = tool_arguments(&tool(), &args(&["--nope", "1"]), std::io::empty()).unwrap_err();
+        assert!(unknown.contains("no argument `nope`"));
+        let bad = tool_arguments(&tool(), &args(&["--limit", "many"]), std::io::empty()).unwrap_err();
+        assert!(bad.contains("--limit"));
+    }
+
+    #[test]
+    fn test_json_from_stdin_with_flag_overrides() {
+        let stdin = r#"{"url": "https://a.example", "limit": 1}"#.as_bytes();
+        let flags = args(&["--limit", "2", "--json", "-"]);
+        let arguments = tool_arguments(&tool(), &flags, stdin).unwrap();
+        assert_eq!(arguments, json!({"url": "https://a.example", "limit": 2}));
+    }
+
+    #[test]
+    fn test_usage_and_table() {
+        let usage = usage(&tool());
+        assert!(usage.starts_with("call fetch"));
+        assert!(usage.contains(" --url <string>"));
+        assert!(usage.contains(" [--limit <integer>]"));
+
+        let mut registry = ToolRegistry::new();
+        registry.register(tool());
+        assert_eq!(tool_table(&registry), "fetch  Fetch a page\n");
+    }
+}
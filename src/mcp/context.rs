@@ -0,0 +1,135 @@
+//! Shared state behind the built-in tools and resources
+
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::audit::AuditLog;
+use super::monitor::{PageFetcher, PageMonitor};
+use super::prompts::PromptLibrary;
+use crate::database_persistence::{AnalysisResult, DatabasePersistence};
+use crate::runtime::AsyncMutex;
+use crate::workflow_orchestrator::WorkflowOrchestrator;
+
+/// URI under which a stored analysis is served
+pub fn analysis_uri(id: &str) -> String {
+    format!("browser://analysis/{}", id)
+}
+
+/// Notifications a slow transport may lag behind before it drops some
+const NOTIFICATION_BUFFER: usize = 256;
+
+#[derive(Clone)]
+pub struct ServerContext {
+    // The JSON file store does read-modify-write, so access is serialized
+    db: Arc<Mutex<DatabasePersistence>>,
+    workflows: Arc<AsyncMutex<WorkflowOrchestrator>>,
+    pages: Arc<PageMonitor>,
+    prompts: Arc<PromptLibrary>,
+    audit: Option<Arc<AuditLog>>,
+    // Server-initiated messages, forwarded by each transport
+    notifications: broadcast::Sender<Value>,
+}
+
+impl ServerContext {
+    /// Open (creating if needed) the analysis store at `db_path`
+    pub fn new(db_path: &str) -> Result<Self, String> {
+        let db = DatabasePersistence::new(db_path);
+        db.initialize()?;
+        let (notifications, _) = broadcast::channel(NOTIFICATION_BUFFER);
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+            workflows: Arc::new(AsyncMutex::new(WorkflowOrchestrator::new())),
+            pages: Arc::new(PageMonitor::new()),
+            prompts: Arc::new(PromptLibrary::empty(notifications.clone())),
+            audit: None,
+            notifications,
+        })
+    }
+
+    /// Replace how page snapshots are fetched (tests, custom scrapers)
+    pub fn with_page_fetcher(mut self, fetcher: PageFetcher) -> Self {
+        self.pages = Arc::new(PageMonitor::with_fetcher(fetcher));
+        self
+    }
+
+    /// Serve the prompt templates found in `dir`
+    pub fn with_prompts_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.prompts = Arc::new(PromptLibrary::load(dir, self.notifications.clone()));
+        self
+    }
+
+    /// Record every tool call in `log` and serve it through `audit_query`
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(Arc::new(log));
+        self
+    }
+
+    pub fn audit(&self) -> Option<Arc<AuditLog>> {
+        self.audit.clone()
+    }
+
+    pub fn prompts(&self) -> Arc<PromptLibrary> {
+        Arc::clone(&self.prompts)
+    }
+
+    pub fn pages(&self) -> Arc<PageMonitor> {
+        Arc::clone(&self.pages)
+    }
+
+    /// Receive every notification sent from now on
+    pub fn notifications(&self) -> broadcast::Receiver<Value> {
+        self.notifications.subscribe()
+    }
+
+    /// Broadcast a server notification; dropped when no transport listens
+    pub fn notify(&self, method: &str, params: Value) {
+        let _ = self.notifications.send(super::jsonrpc::notification(method, params));
+    }
+
+    pub fn workflows(&self) -> Arc<AsyncMutex<WorkflowOrchestrator>> {
+        Arc::clone(&self.workflows)
+    }
+
+    /// Wait for a store write in progress and sync the audit log; call
+    /// before exiting
+    pub fn flush(&self) -> Result<(), String> {
+        drop(self.db.lock());
+        match &self.audit {
+            Some(audit) => audit.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn with_db<T>(&self, f: impl FnOnce(&DatabasePersistence) -> T) -> T {
+        f(&self.db.lock())
+    }
+
+    /// Store a tool output so it can be read back as a resource by the same
+    /// `client` (API key name); returns its URI
+    pub fn record_analysis(
+        &self,
+        client: Option<&str>,
+        analysis_type: &str,
+        tool: &str,
+        query: &str,
+        data: &Value,
+        duration_ms: u64,
+    ) -> Result<String, String> {
+        let result = AnalysisResult {
+            id: format!("analysis_{}", uuid::Uuid::new_v4()),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            analysis_type: analysis_type.to_string(),
+            query: query.to_string(),
+            data: data.clone(),
+            status: "success".to_string(),
+            duration_ms,
+            tags: vec![tool.to_string()],
+            ttl_seconds: None,
+            client: client.map(String::from),
+        };
+        let id = self.with_db(|db| db.save_analysis(&result))?;
+        Ok(analysis_uri(&id))
+    }
+}
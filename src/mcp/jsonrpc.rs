@@ -0,0 +1,266 @@
+//! JSON-RPC 2.0 envelope handling for the MCP transports
+//!
+//! Validates requests, builds `result`/`error` responses with the standard
+//! error codes, drops responses for notifications and handles batch arrays.
+
+use serde_json::{json, Value};
+use std::future::Future;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// JSON-RPC error object (`{"code", "message", "data"?}`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn parse_error(detail: impl std::fmt::Display) -> Self {
+        Self::new(PARSE_ERROR, format!("Parse error: {}", detail))
+    }
+
+    pub fn invalid_request(detail: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_REQUEST, format!("Invalid request: {}", detail))
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))
+    }
+
+    pub fn invalid_params(detail: impl std::fmt::Display) -> Self {
+        Self::new(INVALID_PARAMS, format!("Invalid params: {}", detail))
+    }
+
+    pub fn internal(detail: impl std::fmt::Display) -> Self {
+        Self::new(INTERNAL_ERROR, format!("Internal error: {}", detail))
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut error = json!({"code": self.code, "message": self.message});
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// A validated JSON-RPC request or notification
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// `None` for notifications
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Option<Value>,
+}
+
+impl Request {
+    /// Validate a single message. On failure returns the id to answer with
+    /// (`null` when it could not be read) together with the error.
+    pub fn from_value(message: Value) -> Result<Self, (Value, RpcError)> {
+        let Value::Object(mut obj) = message else {
+            return Err((Value::Null, RpcError::invalid_request("expected an object")));
+        };
+
+        let id = obj.remove("id");
+        let reply_id = match &id {
+            Some(v @ (Value::String(_) | Value::Number(_) | Value::Null)) => v.clone(),
+            Some(_) => {
+                return Err((
+                    Value::Null,
+                    RpcError::invalid_request("id must be a string, number or null"),
+                ))
+            }
+            None => Value::Null,
+        };
+
+        if obj.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+            return Err((reply_id, RpcError::invalid_request("jsonrpc must be \"2.0\"")));
+        }
+
+        let method = match obj.remove("method") {
+            Some(Value::String(method)) => method,
+            _ => return Err((reply_id, RpcError::invalid_request("method must be a string"))),
+        };
+
+        let params = obj.remove("params");
+        if !matches!(params, None | Some(Value::Object(_)) | Some(Value::Array(_))) {
+            return Err((
+                reply_id,
+                RpcError::invalid_request("params must be an object or array"),
+            ));
+        }
+
+        Ok(Self { id, method, params })
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+pub fn success(id: Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+pub fn failure(id: Value, error: &RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": error.to_value()})
+}
+
+/// Server-to-client notification (no `id`, never answered)
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+/// Parse a raw frame, answering with a -32700 response on malformed JSON
+pub fn parse(raw: &[u8]) -> Result<Value, Value> {
+    serde_json::from_slice(raw).map_err(|e| failure(Value::Null, &RpcError::parse_error(e)))
+}
+
+/// Handle a decoded message (single request or batch array).
+///
+/// Returns `None` when nothing must be sent back: a notification, or a
+/// batch made only of notifications.
+pub async fn handle_message<F, Fut>(message: Value, handler: F) -> Option<Value>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Result<Value, RpcError>>,
+{
+    match message {
+        Value::Array(batch) => {
+            if batch.is_empty() {
+                return Some(failure(Value::Null, &RpcError::invalid_request("empty batch")));
+            }
+            let mut responses = Vec::new();
+            for message in batch {
+                if let Some(response) = handle_single(message, &handler).await {
+                    responses.push(response);
+                }
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        message => handle_single(message, &handler).await,
+    }
+}
+
+async fn handle_single<F, Fut>(message: Value, handler: &F) -> Option<Value>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Result<Value, RpcError>>,
+{
+    let request = match Request::from_value(message) {
+        Ok(request) => request,
+        Err((id, error)) => return Some(failure(id, &error)),
+    };
+
+    let id = request.id.clone();
+    let outcome = handler(request).await;
+
+    // Notifications never get a response, even when they fail
+    let id = id?;
+    Some(match outcome {
+        Ok(result) => success(id, result),
+        Err(error) => failure(id, &error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn echo(request: Request) -> Result<Value, RpcError> {
+        match request.method.as_str() {
+            "echo" => Ok(request.params.unwrap_or(Value::Null)),
+            other => Err(RpcError::method_not_found(other)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_success_and_method_not_found() {
+        let ok = handle_message(json!({"jsonrpc": "2.0", "id": 1, "method": "echo", "params": {"a": 1}}), echo)
+            .await
+            .unwrap();
+        assert_eq!(ok["result"]["a"], 1);
+        assert!(ok.get("error").is_none());
+
+        let missing = handle_message(json!({"jsonrpc": "2.0", "id": "x", "method": "nope"}), echo)
+            .await
+            .unwrap();
+        assert_eq!(missing["id"], "x");
+        assert_eq!(missing["error"]["code"], METHOD_NOT_FOUND);
+        assert!(missing.get("result").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_and_parse_error() {
+        let bad_version = handle_message(json!({"jsonrpc": "1.0", "id": 7, "method": "echo"}), echo)
+            .await
+            .unwrap();
+        assert_eq!(bad_version["id"], 7);
+        assert_eq!(bad_version["error"]["code"], INVALID_REQUEST);
+
+        let not_object = handle_message(json!(42), echo).await.unwrap();
+        assert_eq!(not_object["error"]["code"], INVALID_REQUEST);
+
+        let parse_error = parse(b"{not json").unwrap_err();
+        assert_eq!(parse_error["error"]["code"], PARSE_ERROR);
+        assert!(parse_error["id"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_notifications_get_no_response() {
+        let response = handle_message(json!({"jsonrpc": "2.0", "method": "echo"}), echo).await;
+        assert!(response.is_none());
+
+        let failing = handle_message(json!({"jsonrpc": "2.0", "method": "nope"}), echo).await;
+        assert!(failing.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "echo", "params": [1]},
+            {"jsonrpc": "2.0", "method": "echo"},
+            {"jsonrpc": "2.0", "id": 2, "method": "nope"},
+            {"foo": "bar"}
+        ]);
+        let responses = handle_message(batch, echo).await.unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["result"], json!([1]));
+        assert_eq!(responses[1]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[2]["error"]["code"], INVALID_REQUEST);
+
+        let empty = handle_message(json!([]), echo).await.unwrap();
+        assert_eq!(empty["error"]["code"], INVALID_REQUEST);
+
+        let only_notifications = handle_message(json!([{"jsonrpc": "2.0", "method": "echo"}]), echo).await;
+        assert!(only_notifications.is_none());
+    }
+}
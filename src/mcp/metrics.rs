@@ -0,0 +1,228 @@
+//! Process-wide counters exposed at `/metrics` in the Prometheus text format
+//!
+//! Tool calls are recorded by the dispatcher, outbound HTTP responses by the
+//! scrapers and the batch queue depth by `scrape_batch`. Everything lives in
+//! one [`global`] registry so code without a [`ServerContext`](super::ServerContext)
+//! can record too.
+
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+use super::audit::AuditStatus;
+
+/// Upper bounds (seconds) of the tool call latency histogram
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: Metrics = Metrics::new();
+}
+
+/// The registry every part of the server records into
+pub fn global() -> &'static Metrics {
+    &GLOBAL
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Cumulative count per [`LATENCY_BUCKETS`] bound
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len()];
+        }
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// `tool` label of calls to tools that are not registered, so that callers
+/// cannot add series at will
+pub const UNKNOWN_TOOL: &str = "unknown";
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// `(tool, status)` → calls
+    tool_calls: Mutex<BTreeMap<(String, &'static str), u64>>,
+    tool_latency: Mutex<BTreeMap<String, Histogram>>,
+    /// Status code (or `error` for transport failures) → responses
+    outbound: Mutex<BTreeMap<String, u64>>,
+    /// `tools/call` refused by a quota, by scope
+    rate_limited: Mutex<BTreeMap<String, u64>>,
+    batch_queue_depth: AtomicI64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A finished `tools/call`
+    pub fn record_tool_call(&self, tool: &str, status: AuditStatus, duration: Duration) {
+        *self.tool_calls.lock().entry((tool.to_string(), status.as_str())).or_default() += 1;
+        if status != AuditStatus::Rejected {
+            self.tool_latency
+                .lock()
+                .entry(tool.to_string())
+                .or_default()
+                .observe(duration.as_secs_f64());
+        }
+    }
+
+    /// An outbound HTTP request: its status code, or `None` if it never got a response
+    pub fn record_outbound(&self, status: Option<u16>) {
+        let code = status.map_or_else(|| "error".to_string(), |s| s.to_string());
+        *self.outbound.lock().entry(code).or_default() += 1;
+    }
+
+    /// A `tools/call` answered with `rate_limited` for `scope`
+    pub fn record_rate_limited(&self, scope: &str) {
+        *self.rate_limited.lock().entry(scope.to_string()).or_default() += 1;
+    }
+
+    /// Count one batch URL as waiting for a worker until the guard is dropped
+    pub fn batch_queued(&'static self) -> QueuedGuard {
+        self.batch_queue_depth.fetch_add(1, Ordering::Relaxed);
+        QueuedGuard(self)
+    }
+
+    pub fn batch_queue_depth(&self) -> i64 {
+        self.batch_queue_depth.load(Ordering::Relaxed)
+    }
+
+    /// Calls of `tool` that ended with `status`
+    pub fn tool_calls(&self, tool: &str, status: AuditStatus) -> u64 {
+        self.tool_calls
+            .lock()
+            .get(&(tool.to_string(), status.as_str()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Prometheus text exposition (format 0.0.4); `gauges` are extra
+    /// `(name, help, value)` samples owned by the caller (sessions, ...)
+    pub fn render(&self, gauges: &[(&str, &str, f64)]) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "browsermcp_tool_calls_total", "counter", "Tool calls by tool and outcome (ok, error, rejected)");
+        for ((tool, status), count) in self.tool_calls.lock().iter() {
+            let _ = writeln!(
+                out,
+                "browsermcp_tool_calls_total{{tool=\"{}\",status=\"{}\"}} {}",
+                escape(tool),
+                status,
+                count
+            );
+        }
+
+        let name = "browsermcp_tool_call_duration_seconds";
+        header(&mut out, name, "histogram", "Latency of tool calls that ran");
+        for (tool, histogram) in self.tool_latency.lock().iter() {
+            let tool = escape(tool);
+            for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(out, "{}_bucket{{tool=\"{}\",le=\"{}\"}} {}", name, tool, bound, count);
+            }
+            let _ = writeln!(out, "{}_bucket{{tool=\"{}\",le=\"+Inf\"}} {}", name, tool, histogram.count);
+            let _ = writeln!(out, "{}_sum{{tool=\"{}\"}} {}", name, tool, histogram.sum);
+            let _ = writeln!(out, "{}_count{{tool=\"{}\"}} {}", name, tool, histogram.count);
+        }
+
+        header(
+            &mut out,
+            "browsermcp_outbound_http_responses_total",
+            "counter",
+            "Responses to outbound fetches by status code (`error` when no response arrived)",
+        );
+        for (code, count) in self.outbound.lock().iter() {
+            let _ = writeln!(out, "browsermcp_outbound_http_responses_total{{code=\"{}\"}} {}", code, count);
+        }
+
+        header(&mut out, "browsermcp_rate_limited_total", "counter", "Tool calls refused by a quota, by scope");
+        for (scope, count) in self.rate_limited.lock().iter() {
+            let _ = writeln!(out, "browsermcp_rate_limited_total{{scope=\"{}\"}} {}", scope, count);
+        }
+
+        header(&mut out, "browsermcp_batch_queue_depth", "gauge", "Batch scrape URLs waiting for a worker");
+        let _ = writeln!(out, "browsermcp_batch_queue_depth {}", self.batch_queue_depth());
+
+        for (name, help, value) in gauges {
+            header(&mut out, name, "gauge", help);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+/// Keeps one URL counted in `browsermcp_batch_queue_depth`
+#[derive(Debug)]
+pub struct QueuedGuard(&'static Metrics);
+
+impl Drop for QueuedGuard {
+    fn drop(&mut self) {
+        self.0.batch_queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Label values are quoted: escape `\`, `"` and newlines
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_histogram() {
+        let metrics = Metrics::new();
+        metrics.record_tool_call("web_scrape", AuditStatus::Ok, Duration::from_millis(80));
+        metrics.record_tool_call("web_scrape", AuditStatus::Error, Duration::from_secs(3));
+        metrics.record_tool_call("nope", AuditStatus::Rejected, Duration::ZERO);
+        metrics.record_outbound(Some(200));
+        metrics.record_outbound(None);
+        metrics.record_rate_limited("api_key");
+
+        let text = metrics.render(&[("browsermcp_sessions", "Open sessions", 2.0)]);
+        assert!(text.contains("browsermcp_tool_calls_total{tool=\"web_scrape\",status=\"ok\"} 1"));
+        assert!(text.contains("browsermcp_tool_calls_total{tool=\"nope\",status=\"rejected\"} 1"));
+        assert!(text.contains("browsermcp_tool_call_duration_seconds_bucket{tool=\"web_scrape\",le=\"0.05\"} 0"));
+        assert!(text.contains("browsermcp_tool_call_duration_seconds_bucket{tool=\"web_scrape\",le=\"0.1\"} 1"));
+        assert!(text.contains("browsermcp_tool_call_duration_seconds_bucket{tool=\"web_scrape\",le=\"5\"} 2"));
+        assert!(text.contains("browsermcp_tool_call_duration_seconds_count{tool=\"web_scrape\"} 2"));
+        // Rejected calls never ran: no latency sample
+        assert!(!text.contains("duration_seconds_count{tool=\"nope\"}"));
+        assert!(text.contains("browsermcp_outbound_http_responses_total{code=\"200\"} 1"));
+        assert!(text.contains("browsermcp_outbound_http_responses_total{code=\"error\"} 1"));
+        assert!(text.contains("browsermcp_rate_limited_total{scope=\"api_key\"} 1"));
+        assert!(text.contains("# TYPE browsermcp_sessions gauge\nbrowsermcp_sessions 2\n"));
+    }
+
+    #[test]
+    fn test_batch_queue_depth_follows_guards() {
+        let metrics: &'static Metrics = Box::leak(Box::new(Metrics::new()));
+        let first = metrics.batch_queued();
+        let second = metrics.batch_queued();
+        assert_eq!(metrics.batch_queue_depth(), 2);
+        drop(first);
+        assert_eq!(metrics.batch_queue_depth(), 1);
+        drop(second);
+        assert!(metrics.render(&[]).contains("browsermcp_batch_queue_depth 0\n"));
+    }
+}
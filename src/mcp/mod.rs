@@ -0,0 +1,65 @@
+//! MCP protocol layer shared by the server transports
+//!
+//! Library users can build their own server by registering tools:
+//!
+//! ```ignore
+//! let context = ServerContext::new("./mcp_data")?;
+//! let mut registry = tools::builtin_registry(&context);
+//! registry.register(MyTool);
+//! let server = McpServer::new(registry, context);
+//! let response = server.handle_message(request).await;
+//! ```
+
+pub mod audit;
+pub mod auth;
+pub mod cli;
+pub mod context;
+pub mod jsonrpc;
+pub mod metrics;
+pub mod monitor;
+pub mod oauth;
+pub mod progress;
+pub mod prompts;
+pub mod quota;
+pub mod registry;
+pub mod resources;
+pub mod schema;
+pub mod server;
+pub mod session;
+pub mod tools;
+
+pub use context::ServerContext;
+pub use jsonrpc::{Request, RpcError};
+pub use progress::{CallContext, Peer};
+pub use registry::{FnTool, McpTool, ToolAnnotations, ToolError, ToolRegistry, ToolResult, TypedTool};
+pub use schema::{ToolArgs, ToolOutput};
+pub use server::McpServer;
+
+use serde_json::{json, Value};
+
+/// Successful `tools/call` result: the tool output as a text content block
+pub fn tool_result(output: &Value) -> Value {
+    json!({
+        "content": [{"type": "text", "text": output.to_string()}],
+        "isError": false
+    })
+}
+
+/// Result of a tool with an `outputSchema`: the output as `structuredContent`,
+/// plus the same JSON as text for clients that only read `content`
+pub fn structured_tool_result(output: &Value) -> Value {
+    json!({
+        "content": [{"type": "text", "text": output.to_string()}],
+        "structuredContent": output,
+        "isError": false
+    })
+}
+
+/// Failed tool execution. Reported inside the result (not as a JSON-RPC
+/// error) so the model can see what went wrong and retry.
+pub fn tool_error(message: impl std::fmt::Display) -> Value {
+    json!({
+        "content": [{"type": "text", "text": message.to_string()}],
+        "isError": true
+    })
+}
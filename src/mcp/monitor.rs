@@ -0,0 +1,392 @@
+//! Page-snapshot resources (`browser://page/{+url}`) and their subscriptions
+//!
+//! A snapshot is the `scrape_url_full` output of the page. Subscriptions
+//! belong to the client (session) that made them. Subscribed pages are
+//! re-fetched in the background; when the normalized content hash changes,
+//! `notifications/resources/updated` is sent to each subscribed client.
+//!
+//! Reads of pages nobody subscribed to are served from the snapshot while it
+//! is younger than the poll interval, then fetched again. At most
+//! [`MAX_SNAPSHOTS`] are kept; unsubscribed pages are evicted first, least
+//! recently fetched first.
+
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::jsonrpc;
+use super::progress::Peer;
+use crate::scraper_marketing;
+
+pub const PAGE_PREFIX: &str = "browser://page/";
+
+/// Snapshots kept in memory
+pub const MAX_SNAPSHOTS: usize = 256;
+
+/// How old an unsubscribed snapshot may get until [`PageMonitor::spawn`]
+/// sets the poll interval
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Fetches the snapshot content of a page
+pub type PageFetcher = Arc<dyn Fn(String) -> BoxFuture<'static, Result<Value, String>> + Send + Sync>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub url: String,
+    pub hash: String,
+    pub fetched_at: String,
+    pub content: Value,
+    #[serde(skip)]
+    taken: Instant,
+}
+
+pub struct PageMonitor {
+    fetcher: PageFetcher,
+    snapshots: Mutex<HashMap<String, Snapshot>>,
+    /// Subscribers of each page URI, by peer id
+    subscriptions: Mutex<BTreeMap<String, HashMap<String, Peer>>>,
+    /// Milliseconds an unsubscribed snapshot is served before a refetch
+    max_age_ms: AtomicU64,
+}
+
+/// URI of the snapshot resource of `url`
+pub fn page_uri(url: &str) -> String {
+    format!("{}{}", PAGE_PREFIX, url)
+}
+
+/// Page URL of a snapshot resource URI
+pub fn page_url(uri: &str) -> Option<&str> {
+    uri.strip_prefix(PAGE_PREFIX).filter(|url| reqwest::Url::parse(url).is_ok())
+}
+
+/// Hash of the snapshot content, ignoring the order of the set-like fields
+/// `scrape_url_full` collects through a `HashSet` and whether the page came
+/// from the HTTP cache
+pub fn content_hash(content: &Value) -> String {
+    let mut normalized = content.clone();
+    if let Some(fields) = normalized.as_object_mut() {
+        fields.remove("cache");
+    }
+    for key in ["emails", "phones"] {
+        if let Some(Value::Array(items)) = normalized.get_mut(key) {
+            items.sort_by_key(|v| v.to_string());
+        }
+    }
+    hex::encode(Sha256::digest(normalized.to_string().as_bytes()))
+}
+
+fn default_fetcher() -> PageFetcher {
+    Arc::new(|url: String| {
+        Box::pin(async move {
+            let selectors = vec!["title".to_string(), "h1".to_string(), "p".to_string()];
+            scraper_marketing::scrape_url_full(&url, &selectors)
+                .await
+                .map_err(|e| e.to_string())
+        })
+    })
+}
+
+impl Default for PageMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PageMonitor {
+    pub fn new() -> Self {
+        Self::with_fetcher(default_fetcher())
+    }
+
+    pub fn with_fetcher(fetcher: PageFetcher) -> Self {
+        Self {
+            fetcher,
+            snapshots: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(BTreeMap::new()),
+            max_age_ms: AtomicU64::new(DEFAULT_MAX_AGE.as_millis() as u64),
+        }
+    }
+
+    /// How long reads of unsubscribed pages are served from the snapshot
+    pub fn set_max_age(&self, max_age: Duration) {
+        self.max_age_ms.store(max_age.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Latest snapshot of `url`. Subscribed pages are kept current by the
+    /// poller; others are fetched again once their snapshot is too old.
+    pub async fn snapshot(&self, url: &str) -> Result<Snapshot, String> {
+        let subscribed = self.subscriptions.lock().contains_key(&page_uri(url));
+        let max_age = Duration::from_millis(self.max_age_ms.load(Ordering::Relaxed));
+        if let Some(snapshot) = self.snapshots.lock().get(url) {
+            if subscribed || snapshot.taken.elapsed() < max_age {
+                return Ok(snapshot.clone());
+            }
+        }
+        self.refresh(url).await?;
+        self.snapshots
+            .lock()
+            .get(url)
+            .cloned()
+            .ok_or_else(|| format!("No snapshot of {}", url))
+    }
+
+    /// Re-fetch `url`. Returns true when its content hash changed; subscribers
+    /// are notified in that case. The first fetch only sets the baseline.
+    pub async fn refresh(&self, url: &str) -> Result<bool, String> {
+        let content = (self.fetcher)(url.to_string()).await?;
+        let snapshot = Snapshot {
+            url: url.to_string(),
+            hash: content_hash(&content),
+            fetched_at: chrono::Utc::now().to_rfc3339(),
+            content,
+            taken: Instant::now(),
+        };
+
+        let subscribed: HashSet<String> = self.subscriptions().into_iter().collect();
+        let previous = {
+            let mut snapshots = self.snapshots.lock();
+            let previous = snapshots.insert(url.to_string(), snapshot.clone());
+            while snapshots.len() > MAX_SNAPSHOTS {
+                let Some(evicted) = snapshots
+                    .values()
+                    .filter(|kept| kept.url != url)
+                    .min_by_key(|kept| (subscribed.contains(&page_uri(&kept.url)), kept.taken))
+                    .map(|kept| kept.url.clone())
+                else {
+                    break;
+                };
+                snapshots.remove(&evicted);
+            }
+            previous
+        };
+        let changed = matches!(previous, Some(previous) if previous.hash != snapshot.hash);
+
+        if changed {
+            let uri = page_uri(url);
+            let subscribers: Vec<Peer> = self
+                .subscriptions
+                .lock()
+                .get(&uri)
+                .map(|peers| peers.values().cloned().collect())
+                .unwrap_or_default();
+            let notification = jsonrpc::notification("notifications/resources/updated", json!({"uri": uri}));
+            for peer in subscribers {
+                peer.send(notification.clone());
+            }
+        }
+        Ok(changed)
+    }
+
+    /// `resources/subscribe` by `peer`; a failed baseline fetch is retried
+    /// by the poller
+    pub async fn subscribe(&self, uri: &str, peer: &Peer) -> bool {
+        let Some(url) = page_url(uri) else {
+            return false;
+        };
+        self.subscriptions
+            .lock()
+            .entry(uri.to_string())
+            .or_default()
+            .insert(peer.id().to_string(), peer.clone());
+        if !self.snapshots.lock().contains_key(url) {
+            if let Err(e) = self.refresh(url).await {
+                tracing::warn!("Baseline fetch of {} failed: {}", url, e);
+            }
+        }
+        true
+    }
+
+    /// `resources/unsubscribe` by the peer `peer_id`; false if it had not
+    /// subscribed to `uri`. Other peers' subscriptions stay.
+    pub fn unsubscribe(&self, uri: &str, peer_id: &str) -> bool {
+        let mut subscriptions = self.subscriptions.lock();
+        let Some(peers) = subscriptions.get_mut(uri) else {
+            return false;
+        };
+        let removed = peers.remove(peer_id).is_some();
+        if peers.is_empty() {
+            subscriptions.remove(uri);
+        }
+        removed
+    }
+
+    /// Drop every subscription of a peer that went away (ended or idle
+    /// session); returns how many it had
+    pub fn forget(&self, peer_id: &str) -> usize {
+        let mut subscriptions = self.subscriptions.lock();
+        let mut forgotten = 0;
+        subscriptions.retain(|_, peers| {
+            forgotten += usize::from(peers.remove(peer_id).is_some());
+            !peers.is_empty()
+        });
+        forgotten
+    }
+
+    /// Page URIs with at least one subscriber
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.subscriptions.lock().keys().cloned().collect()
+    }
+
+    /// Page URIs the peer `peer_id` subscribed to
+    pub fn subscribed_by(&self, peer_id: &str) -> Vec<String> {
+        self.subscriptions
+            .lock()
+            .iter()
+            .filter(|(_, peers)| peers.contains_key(peer_id))
+            .map(|(uri, _)| uri.clone())
+            .collect()
+    }
+
+    /// Re-fetch every subscribed page once
+    pub async fn poll(&self) {
+        for uri in self.subscriptions() {
+            let Some(url) = page_url(&uri) else { continue };
+            if let Err(e) = self.refresh(url).await {
+                tracing::warn!("Re-fetch of {} failed: {}", url, e);
+            }
+        }
+    }
+
+    /// Background re-fetcher polling subscribed pages every `interval`,
+    /// which also becomes the age limit of unsubscribed snapshots
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        self.set_max_age(interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                self.poll().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fetcher whose page title changes on every fetch after the second
+    fn counting_fetcher() -> (PageFetcher, Arc<AtomicUsize>) {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&fetches);
+        let fetcher: PageFetcher = Arc::new(move |url: String| {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok(json!({"url": url, "title": format!("v{}", n.max(1))})) })
+        });
+        (fetcher, fetches)
+    }
+
+    #[test]
+    fn test_hash_ignores_set_order() {
+        let a = json!({"title": "x", "emails": ["a@x.com", "b@x.com"]});
+        let b = json!({"title": "x", "emails": ["b@x.com", "a@x.com"]});
+        assert_eq!(content_hash(&a), content_hash(&b));
+        let cached = json!({"title": "x", "emails": ["a@x.com", "b@x.com"], "cache": "hit"});
+        assert_eq!(content_hash(&a), content_hash(&cached));
+        assert_ne!(content_hash(&a), content_hash(&json!({"title": "y"})));
+    }
+
+    /// A peer whose messages land in the returned channel
+    fn peer(id: &str) -> (Peer, tokio::sync::mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let peer = Peer::new(id, move |message| {
+            let _ = tx.send(message);
+        });
+        (peer, rx)
+    }
+
+    #[tokio::test]
+    async fn test_subscribed_change_is_notified() {
+        let (fetcher, fetches) = counting_fetcher();
+        let monitor = PageMonitor::with_fetcher(fetcher);
+        let uri = page_uri("https://example.com/prices");
+        let (alice, mut alice_rx) = peer("alice");
+        let (bob, mut bob_rx) = peer("bob");
+
+        assert!(monitor.subscribe(&uri, &alice).await);
+        // Fetch 0 and 1 both report v1: no change
+        monitor.poll().await;
+        assert!(alice_rx.try_recv().is_err());
+
+        monitor.poll().await;
+        let notification = alice_rx.try_recv().unwrap();
+        assert_eq!(notification["method"], "notifications/resources/updated");
+        assert_eq!(notification["params"]["uri"], uri.as_str());
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+        // Bob never subscribed
+        assert!(bob_rx.try_recv().is_err());
+
+        // Bob's unsubscribe leaves Alice's subscription alone
+        assert!(!monitor.unsubscribe(&uri, bob.id()));
+        assert!(monitor.subscribe(&uri, &bob).await);
+        assert!(monitor.unsubscribe(&uri, bob.id()));
+        assert_eq!(monitor.subscribed_by("alice"), vec![uri.clone()]);
+
+        assert!(monitor.unsubscribe(&uri, alice.id()));
+        monitor.refresh("https://example.com/prices").await.unwrap();
+        assert!(alice_rx.try_recv().is_err());
+        assert!(monitor.subscriptions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forgotten_peers_lose_their_subscriptions() {
+        let (fetcher, _) = counting_fetcher();
+        let monitor = PageMonitor::with_fetcher(fetcher);
+        let (alice, _alice_rx) = peer("alice");
+        let (bob, _bob_rx) = peer("bob");
+        let prices = page_uri("https://example.com/prices");
+        let news = page_uri("https://example.com/news");
+        monitor.subscribe(&prices, &alice).await;
+        monitor.subscribe(&news, &alice).await;
+        monitor.subscribe(&prices, &bob).await;
+
+        assert_eq!(monitor.forget("alice"), 2);
+        assert_eq!(monitor.subscriptions(), vec![prices]);
+        assert!(monitor.subscribed_by("alice").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribed_snapshots_expire_and_are_capped() {
+        let (fetcher, fetches) = counting_fetcher();
+        let monitor = PageMonitor::with_fetcher(fetcher);
+        monitor.snapshot("https://example.com/a").await.unwrap();
+        monitor.snapshot("https://example.com/a").await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        monitor.set_max_age(Duration::ZERO);
+        monitor.snapshot("https://example.com/a").await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        // Subscribed pages are left to the poller
+        let (alice, _alice_rx) = peer("alice");
+        let uri = page_uri("https://example.com/a");
+        monitor.subscribe(&uri, &alice).await;
+        monitor.snapshot("https://example.com/a").await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        for n in 0..MAX_SNAPSHOTS {
+            monitor.refresh(&format!("https://example.com/{}", n)).await.unwrap();
+        }
+        let kept = monitor.snapshots.lock();
+        assert_eq!(kept.len(), MAX_SNAPSHOTS);
+        assert!(kept.contains_key("https://example.com/a"));
+        assert!(!kept.contains_key("https://example.com/0"));
+    }
+
+    #[tokio::test]
+    async fn test_only_page_uris_are_subscribable() {
+        let (fetcher, _) = counting_fetcher();
+        let monitor = PageMonitor::with_fetcher(fetcher);
+        let (alice, _) = peer("alice");
+        assert!(!monitor.subscribe("browser://docs/usage", &alice).await);
+        assert!(!monitor.subscribe("browser://page/not a url", &alice).await);
+        assert!(monitor.subscriptions().is_empty());
+    }
+}
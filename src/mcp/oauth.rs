@@ -0,0 +1,731 @@
+//! Minimal OAuth 2.1 authorization server for remote MCP clients
+//!
+//! Covers what the MCP authorization spec asks of a server: protected
+//! resource metadata (RFC 9728), authorization server metadata (RFC 8414),
+//! dynamic client registration (RFC 7591) and the authorization code grant
+//! with PKCE (`S256` only) and rotating refresh tokens. Clients are public
+//! (no client secret).
+//!
+//! The resource owner approves a request by entering one of the server's API
+//! keys on the consent page; tokens carry that key's identity and tool
+//! allowlist. State lives in memory, so clients register and authorize again
+//! after a restart.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use parking_lot::RwLock;
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::auth::ApiKeyIdentity;
+
+/// Prefix of issued access tokens, so the transport can tell them from API keys
+pub const ACCESS_TOKEN_PREFIX: &str = "bmcp_at_";
+const REFRESH_TOKEN_PREFIX: &str = "bmcp_rt_";
+
+pub const ACCESS_TOKEN_TTL: Duration = Duration::from_secs(3600);
+pub const REFRESH_TOKEN_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+const CODE_TTL: Duration = Duration::from_secs(300);
+
+/// Registrations kept; the oldest is evicted beyond this, since registration
+/// is unauthenticated
+const MAX_CLIENTS: usize = 1024;
+
+/// OAuth error response (`{"error", "error_description"}`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthError {
+    pub error: &'static str,
+    pub description: String,
+}
+
+impl OAuthError {
+    pub fn new(error: &'static str, description: impl Into<String>) -> Self {
+        Self {
+            error,
+            description: description.into(),
+        }
+    }
+
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        Self::new("invalid_request", description)
+    }
+
+    pub fn invalid_grant(description: impl Into<String>) -> Self {
+        Self::new("invalid_grant", description)
+    }
+
+    pub fn invalid_client(description: impl Into<String>) -> Self {
+        Self::new("invalid_client", description)
+    }
+
+    pub fn to_value(&self) -> Value {
+        json!({"error": self.error, "error_description": self.description})
+    }
+}
+
+impl std::fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.error, self.description)
+    }
+}
+
+impl std::error::Error for OAuthError {}
+
+/// Why an authorization request failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthorizeError {
+    /// Unknown client or redirect URI: shown to the user, never redirected
+    Invalid(OAuthError),
+    /// Sent back to the client: redirect here
+    Redirect(String),
+}
+
+/// Dynamic client registration request (RFC 7591)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClientRegistration {
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub client_name: Option<String>,
+    #[serde(default)]
+    pub token_endpoint_auth_method: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisteredClient {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub issued_at: i64,
+}
+
+/// Query of `GET /authorize`, also posted back by the consent form
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthorizationRequest {
+    #[serde(default)]
+    pub response_type: String,
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub redirect_uri: String,
+    #[serde(default)]
+    pub code_challenge: String,
+    #[serde(default)]
+    pub code_challenge_method: String,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub resource: Option<String>,
+}
+
+/// Form of `POST /token`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TokenRequest {
+    #[serde(default)]
+    pub grant_type: String,
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+    #[serde(default)]
+    pub code_verifier: Option<String>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub resource: Option<String>,
+}
+
+struct PendingCode {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: String,
+    scope: Option<String>,
+    identity: ApiKeyIdentity,
+    expires_at: Instant,
+}
+
+struct Grant {
+    client_id: String,
+    scope: Option<String>,
+    identity: ApiKeyIdentity,
+    expires_at: Instant,
+}
+
+type TokenDigest = [u8; 32];
+
+pub struct AuthorizationServer {
+    issuer: String,
+    clients: RwLock<HashMap<String, RegisteredClient>>,
+    codes: RwLock<HashMap<TokenDigest, PendingCode>>,
+    // Access and refresh tokens are stored by SHA-256, never in clear
+    access_tokens: RwLock<HashMap<TokenDigest, Grant>>,
+    refresh_tokens: RwLock<HashMap<TokenDigest, Grant>>,
+}
+
+impl AuthorizationServer {
+    /// `issuer` is the public base URL of the server (`https://mcp.example.com`)
+    pub fn new(issuer: &str) -> Self {
+        Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            clients: RwLock::new(HashMap::new()),
+            codes: RwLock::new(HashMap::new()),
+            access_tokens: RwLock::new(HashMap::new()),
+            refresh_tokens: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Canonical URI of the protected MCP endpoint (token audience)
+    pub fn resource(&self) -> String {
+        format!("{}/mcp", self.issuer)
+    }
+
+    /// Advertised in `WWW-Authenticate` on 401
+    pub fn resource_metadata_url(&self) -> String {
+        format!("{}/.well-known/oauth-protected-resource", self.issuer)
+    }
+
+    /// `/.well-known/oauth-protected-resource` (RFC 9728)
+    pub fn protected_resource_metadata(&self) -> Value {
+        json!({
+            "resource": self.resource(),
+            "authorization_servers": [self.issuer],
+            "bearer_methods_supported": ["header"]
+        })
+    }
+
+    /// `/.well-known/oauth-authorization-server` (RFC 8414)
+    pub fn metadata(&self) -> Value {
+        json!({
+            "issuer": self.issuer,
+            "authorization_endpoint": format!("{}/authorize", self.issuer),
+            "token_endpoint": format!("{}/token", self.issuer),
+            "registration_endpoint": format!("{}/register", self.issuer),
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code", "refresh_token"],
+            "code_challenge_methods_supported": ["S256"],
+            "token_endpoint_auth_methods_supported": ["none"]
+        })
+    }
+
+    /// `POST /register`: register a public client
+    pub fn register(&self, registration: ClientRegistration) -> Result<Value, OAuthError> {
+        if registration.redirect_uris.is_empty() {
+            return Err(OAuthError::new("invalid_redirect_uri", "redirect_uris is required"));
+        }
+        if let Some(bad) = registration.redirect_uris.iter().find(|uri| !valid_redirect_uri(uri)) {
+            return Err(OAuthError::new(
+                "invalid_redirect_uri",
+                format!("{} must be https, loopback http or a private scheme, without fragment", bad),
+            ));
+        }
+        match registration.token_endpoint_auth_method.as_deref() {
+            None | Some("none") => {}
+            Some(other) => {
+                return Err(OAuthError::new(
+                    "invalid_client_metadata",
+                    format!("Unsupported token_endpoint_auth_method: {} (public clients only)", other),
+                ))
+            }
+        }
+
+        let client = RegisteredClient {
+            client_id: uuid::Uuid::new_v4().to_string(),
+            client_name: registration.client_name,
+            redirect_uris: registration.redirect_uris,
+            issued_at: chrono::Utc::now().timestamp(),
+        };
+        let mut clients = self.clients.write();
+        if clients.len() >= MAX_CLIENTS {
+            if let Some(oldest) = clients.values().min_by_key(|c| c.issued_at).map(|c| c.client_id.clone()) {
+                clients.remove(&oldest);
+            }
+        }
+        clients.insert(client.client_id.clone(), client.clone());
+        tracing::info!("Registered OAuth client {} ({:?})", client.client_id, client.client_name);
+
+        let mut response = json!({
+            "client_id": client.client_id,
+            "client_id_issued_at": client.issued_at,
+            "redirect_uris": client.redirect_uris,
+            "token_endpoint_auth_method": "none",
+            "grant_types": ["authorization_code", "refresh_token"],
+            "response_types": ["code"]
+        });
+        if let Some(name) = &client.client_name {
+            response["client_name"] = json!(name);
+        }
+        Ok(response)
+    }
+
+    pub fn client(&self, client_id: &str) -> Option<RegisteredClient> {
+        self.clients.read().get(client_id).cloned()
+    }
+
+    /// Validate an authorization request before showing the consent page
+    pub fn check_authorization(&self, request: &AuthorizationRequest) -> Result<RegisteredClient, AuthorizeError> {
+        let client = self
+            .client(&request.client_id)
+            .ok_or_else(|| AuthorizeError::Invalid(OAuthError::invalid_client("Unknown client_id")))?;
+        if !client.redirect_uris.contains(&request.redirect_uri) {
+            return Err(AuthorizeError::Invalid(OAuthError::invalid_request(
+                "redirect_uri is not registered for this client",
+            )));
+        }
+
+        let error = if request.response_type != "code" {
+            Some(OAuthError::new("unsupported_response_type", "response_type must be code"))
+        } else if request.code_challenge.is_empty() {
+            Some(OAuthError::invalid_request("code_challenge is required (PKCE)"))
+        } else if request.code_challenge_method != "S256" {
+            Some(OAuthError::invalid_request("code_challenge_method must be S256"))
+        } else {
+            self.check_resource(request.resource.as_deref()).err()
+        };
+        match error {
+            Some(error) => Err(AuthorizeError::Redirect(redirect_with(
+                &request.redirect_uri,
+                &[("error", error.error), ("error_description", &error.description)],
+                request.state.as_deref(),
+            ))),
+            None => Ok(client),
+        }
+    }
+
+    /// The resource owner approved: issue a code and return the redirect URL
+    pub fn authorize(&self, request: &AuthorizationRequest, identity: ApiKeyIdentity) -> Result<String, AuthorizeError> {
+        self.check_authorization(request)?;
+        let code = random_token("");
+        let now = Instant::now();
+        let mut codes = self.codes.write();
+        codes.retain(|_, pending| pending.expires_at > now);
+        codes.insert(
+            digest(&code),
+            PendingCode {
+                client_id: request.client_id.clone(),
+                redirect_uri: request.redirect_uri.clone(),
+                code_challenge: request.code_challenge.clone(),
+                scope: request.scope.clone(),
+                identity,
+                expires_at: now + CODE_TTL,
+            },
+        );
+        Ok(redirect_with(&request.redirect_uri, &[("code", &code)], request.state.as_deref()))
+    }
+
+    /// The resource owner refused
+    pub fn deny(&self, request: &AuthorizationRequest) -> Result<String, AuthorizeError> {
+        self.check_authorization(request)?;
+        Ok(redirect_with(
+            &request.redirect_uri,
+            &[("error", "access_denied"), ("error_description", "The request was denied")],
+            request.state.as_deref(),
+        ))
+    }
+
+    /// `POST /token`
+    pub fn token(&self, request: &TokenRequest) -> Result<Value, OAuthError> {
+        self.check_resource(request.resource.as_deref())?;
+        match request.grant_type.as_str() {
+            "authorization_code" => self.exchange_code(request),
+            "refresh_token" => self.refresh(request),
+            other => Err(OAuthError::new(
+                "unsupported_grant_type",
+                format!("Unsupported grant_type: {}", other),
+            )),
+        }
+    }
+
+    fn exchange_code(&self, request: &TokenRequest) -> Result<Value, OAuthError> {
+        let code = request
+            .code
+            .as_deref()
+            .ok_or_else(|| OAuthError::invalid_request("code is required"))?;
+        let verifier = request
+            .code_verifier
+            .as_deref()
+            .ok_or_else(|| OAuthError::invalid_request("code_verifier is required (PKCE)"))?;
+        // Codes are single use: consumed even when the exchange fails
+        let pending = self
+            .codes
+            .write()
+            .remove(&digest(code))
+            .filter(|pending| pending.expires_at > Instant::now())
+            .ok_or_else(|| OAuthError::invalid_grant("Invalid or expired authorization code"))?;
+
+        if pending.client_id != request.client_id {
+            return Err(OAuthError::invalid_grant("Code was issued to another client"));
+        }
+        if request.redirect_uri.as_deref() != Some(pending.redirect_uri.as_str()) {
+            return Err(OAuthError::invalid_grant("redirect_uri does not match the authorization request"));
+        }
+        if !valid_verifier(verifier) || pkce_challenge(verifier) != pending.code_challenge {
+            return Err(OAuthError::invalid_grant("PKCE verification failed"));
+        }
+        Ok(self.issue(Grant {
+            client_id: pending.client_id,
+            scope: pending.scope,
+            identity: pending.identity,
+            expires_at: Instant::now(),
+        }))
+    }
+
+    fn refresh(&self, request: &TokenRequest) -> Result<Value, OAuthError> {
+        let token = request
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| OAuthError::invalid_request("refresh_token is required"))?;
+        // Rotation: the presented refresh token is spent
+        let grant = self
+            .refresh_tokens
+            .write()
+            .remove(&digest(token))
+            .filter(|grant| grant.expires_at > Instant::now())
+            .ok_or_else(|| OAuthError::invalid_grant("Invalid or expired refresh token"))?;
+        if grant.client_id != request.client_id {
+            return Err(OAuthError::invalid_grant("Refresh token was issued to another client"));
+        }
+        Ok(self.issue(grant))
+    }
+
+    /// New access + refresh token pair for `grant`
+    fn issue(&self, grant: Grant) -> Value {
+        let now = Instant::now();
+        let access_token = random_token(ACCESS_TOKEN_PREFIX);
+        let refresh_token = random_token(REFRESH_TOKEN_PREFIX);
+
+        let mut response = json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": ACCESS_TOKEN_TTL.as_secs(),
+            "refresh_token": refresh_token
+        });
+        if let Some(scope) = &grant.scope {
+            response["scope"] = json!(scope);
+        }
+
+        {
+            let mut access_tokens = self.access_tokens.write();
+            access_tokens.retain(|_, g| g.expires_at > now);
+            access_tokens.insert(
+                digest(&access_token),
+                Grant {
+                    client_id: grant.client_id.clone(),
+                    scope: grant.scope.clone(),
+                    identity: grant.identity.clone(),
+                    expires_at: now + ACCESS_TOKEN_TTL,
+                },
+            );
+        }
+        let mut refresh_tokens = self.refresh_tokens.write();
+        refresh_tokens.retain(|_, g| g.expires_at > now);
+        refresh_tokens.insert(
+            digest(&refresh_token),
+            Grant {
+                expires_at: now + REFRESH_TOKEN_TTL,
+                ..grant
+            },
+        );
+        response
+    }
+
+    /// Identity behind a live access token
+    pub fn validate(&self, access_token: &str) -> Option<ApiKeyIdentity> {
+        self.access_tokens
+            .read()
+            .get(&digest(access_token))
+            .filter(|grant| grant.expires_at > Instant::now())
+            .map(|grant| grant.identity.clone())
+    }
+
+    /// RFC 8707: tokens are only issued for this server's MCP endpoint
+    fn check_resource(&self, resource: Option<&str>) -> Result<(), OAuthError> {
+        match resource {
+            Some(resource) if resource.trim_end_matches('/') != self.resource() => Err(OAuthError::new(
+                "invalid_target",
+                format!("Unknown resource: {} (expected {})", resource, self.resource()),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `BASE64URL(SHA256(verifier))`, the S256 code challenge
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// RFC 7636: 43-128 unreserved characters
+fn valid_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~'))
+}
+
+/// https, http on a loopback host (native apps), or a private-use scheme;
+/// never a fragment
+fn valid_redirect_uri(uri: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(uri) else {
+        return false;
+    };
+    if url.fragment().is_some() {
+        return false;
+    }
+    match url.scheme() {
+        "https" => true,
+        "http" => matches!(url.host_str(), Some("localhost") | Some("127.0.0.1") | Some("[::1]")),
+        "javascript" | "data" | "file" => false,
+        _ => true,
+    }
+}
+
+fn redirect_with(redirect_uri: &str, params: &[(&str, &str)], state: Option<&str>) -> String {
+    let mut url = match reqwest::Url::parse(redirect_uri) {
+        Ok(url) => url,
+        Err(_) => return redirect_uri.to_string(),
+    };
+    {
+        let mut query = url.query_pairs_mut();
+        for (key, value) in params {
+            query.append_pair(key, value);
+        }
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    url.to_string()
+}
+
+fn random_token(prefix: &str) -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    format!("{}{}", prefix, URL_SAFE_NO_PAD.encode(secret))
+}
+
+fn digest(token: &str) -> TokenDigest {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/// Consent page for `GET /authorize`: echoes the request as hidden fields
+/// and asks for an API key
+pub fn consent_page(request: &AuthorizationRequest, client: &RegisteredClient, error: Option<&str>) -> String {
+    let hidden = [
+        ("response_type", Some(request.response_type.as_str())),
+        ("client_id", Some(request.client_id.as_str())),
+        ("redirect_uri", Some(request.redirect_uri.as_str())),
+        ("code_challenge", Some(request.code_challenge.as_str())),
+        ("code_challenge_method", Some(request.code_challenge_method.as_str())),
+        ("state", request.state.as_deref()),
+        ("scope", request.scope.as_deref()),
+        ("resource", request.resource.as_deref()),
+    ]
+    .iter()
+    .filter_map(|(name, value)| {
+        value.map(|value| format!(r#"<input type="hidden" name="{}" value="{}">"#, name, escape_html(value)))
+    })
+    .collect::<Vec<_>>()
+    .join("\n    ");
+
+    let client_name = client.client_name.as_deref().unwrap_or(&client.client_id);
+    let error = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Authorize {client}</title></head>
+<body>
+  <h1>Authorize {client}</h1>
+  <p>{client} wants to use the tools of this BrowserMCP server and will be redirected to {redirect}.</p>
+  {error}
+  <form method="post" action="/authorize">
+    {hidden}
+    <label>API key <input type="password" name="api_key" autocomplete="off" autofocus></label>
+    <button type="submit" name="decision" value="approve">Approve</button>
+    <button type="submit" name="decision" value="deny">Deny</button>
+  </form>
+</body>
+</html>
+"#,
+        client = escape_html(client_name),
+        redirect = escape_html(&request.redirect_uri),
+        error = error,
+        hidden = hidden,
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const REDIRECT: &str = "http://127.0.0.1:8765/callback";
+
+    fn identity() -> ApiKeyIdentity {
+        ApiKeyIdentity {
+            name: "ci".to_string(),
+            tools: None,
+            quota: Default::default(),
+        }
+    }
+
+    fn registered(server: &AuthorizationServer) -> String {
+        let registration = ClientRegistration {
+            redirect_uris: vec![REDIRECT.to_string()],
+            client_name: Some("test client".to_string()),
+            token_endpoint_auth_method: None,
+        };
+        server.register(registration).unwrap()["client_id"].as_str().unwrap().to_string()
+    }
+
+    fn authorization(client_id: &str) -> AuthorizationRequest {
+        AuthorizationRequest {
+            response_type: "code".to_string(),
+            client_id: client_id.to_string(),
+            redirect_uri: REDIRECT.to_string(),
+            code_challenge: pkce_challenge(VERIFIER),
+            code_challenge_method: "S256".to_string(),
+            state: Some("xyz".to_string()),
+            scope: None,
+            resource: Some("https://mcp.example.com/mcp".to_string()),
+        }
+    }
+
+    fn code_from(redirect: &str) -> String {
+        let url = reqwest::Url::parse(redirect).unwrap();
+        assert_eq!(url.query_pairs().find(|(k, _)| k == "state").unwrap().1, "xyz");
+        url.query_pairs().find(|(k, _)| k == "code").unwrap().1.into_owned()
+    }
+
+    fn exchange(client_id: &str, code: &str, verifier: &str) -> TokenRequest {
+        TokenRequest {
+            grant_type: "authorization_code".to_string(),
+            client_id: client_id.to_string(),
+            code: Some(code.to_string()),
+            redirect_uri: Some(REDIRECT.to_string()),
+            code_verifier: Some(verifier.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pkce_challenge_rfc7636_vector() {
+        assert_eq!(pkce_challenge(VERIFIER), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn test_code_flow_and_refresh_rotation() {
+        let server = AuthorizationServer::new("https://mcp.example.com/");
+        assert_eq!(server.metadata()["issuer"], "https://mcp.example.com");
+        let client_id = registered(&server);
+
+        let redirect = server.authorize(&authorization(&client_id), identity()).unwrap();
+        let code = code_from(&redirect);
+        let tokens = server.token(&exchange(&client_id, &code, VERIFIER)).unwrap();
+        let access = tokens["access_token"].as_str().unwrap();
+        assert!(access.starts_with(ACCESS_TOKEN_PREFIX));
+        assert_eq!(server.validate(access).unwrap().name, "ci");
+
+        // Codes are single use
+        let replay = server.token(&exchange(&client_id, &code, VERIFIER)).unwrap_err();
+        assert_eq!(replay.error, "invalid_grant");
+
+        let refresh = TokenRequest {
+            grant_type: "refresh_token".to_string(),
+            client_id: client_id.clone(),
+            refresh_token: tokens["refresh_token"].as_str().map(String::from),
+            ..Default::default()
+        };
+        let rotated = server.token(&refresh).unwrap();
+        assert!(server.validate(rotated["access_token"].as_str().unwrap()).is_some());
+        assert_eq!(server.token(&refresh).unwrap_err().error, "invalid_grant");
+        assert!(server.validate("bmcp_at_unknown").is_none());
+    }
+
+    #[test]
+    fn test_pkce_mismatch_is_rejected() {
+        let server = AuthorizationServer::new("https://mcp.example.com");
+        let client_id = registered(&server);
+        let code = code_from(&server.authorize(&authorization(&client_id), identity()).unwrap());
+        let wrong = "x".repeat(43);
+        assert_eq!(server.token(&exchange(&client_id, &code, &wrong)).unwrap_err().error, "invalid_grant");
+    }
+
+    #[test]
+    fn test_authorization_request_errors() {
+        let server = AuthorizationServer::new("https://mcp.example.com");
+        let client_id = registered(&server);
+
+        let mut unknown_redirect = authorization(&client_id);
+        unknown_redirect.redirect_uri = "https://evil.example.com/cb".to_string();
+        assert!(matches!(
+            server.check_authorization(&unknown_redirect),
+            Err(AuthorizeError::Invalid(_))
+        ));
+
+        let mut plain = authorization(&client_id);
+        plain.code_challenge_method = "plain".to_string();
+        match server.check_authorization(&plain) {
+            Err(AuthorizeError::Redirect(url)) => {
+                assert!(url.starts_with(REDIRECT));
+                assert!(url.contains("error=invalid_request"));
+            }
+            other => panic!("expected redirect, got {:?}", other),
+        }
+
+        let mut other_resource = authorization(&client_id);
+        other_resource.resource = Some("https://other.example.com/mcp".to_string());
+        assert!(matches!(
+            server.check_authorization(&other_resource),
+            Err(AuthorizeError::Redirect(url)) if url.contains("invalid_target")
+        ));
+    }
+
+    #[test]
+    fn test_registration_rejects_unsafe_redirects() {
+        let server = AuthorizationServer::new("https://mcp.example.com");
+        for uri in ["http://example.com/cb", "https://example.com/cb#frag", "javascript:alert(1)"] {
+            let registration = ClientRegistration {
+                redirect_uris: vec![uri.to_string()],
+                ..Default::default()
+            };
+            assert_eq!(server.register(registration).unwrap_err().error, "invalid_redirect_uri");
+        }
+        let native = ClientRegistration {
+            redirect_uris: vec!["cursor://anysphere.cursor-mcp/oauth/callback".to_string()],
+            ..Default::default()
+        };
+        assert!(server.register(native).is_ok());
+    }
+
+    #[test]
+    fn test_consent_page_escapes() {
+        let server = AuthorizationServer::new("https://mcp.example.com");
+        let client_id = registered(&server);
+        let mut request = authorization(&client_id);
+        request.state = Some("\"><script>".to_string());
+        let page = consent_page(&request, &server.client(&client_id).unwrap(), None);
+        assert!(page.contains("&quot;&gt;&lt;script&gt;"));
+        assert!(!page.contains("\"><script>"));
+    }
+}
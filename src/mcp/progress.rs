@@ -0,0 +1,236 @@
+//! Progress notifications and cancellation for `tools/call`
+//!
+//! Each call gets a [`CallContext`]: tools report progress through it (sent
+//! as `notifications/progress` when the request carried
+//! `_meta.progressToken`) and watch its cancellation token, which
+//! `notifications/cancelled` trips through [`InFlightRequests`].
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::jsonrpc;
+
+type Outbound = Arc<dyn Fn(Value) + Send + Sync>;
+
+/// The client a request came from: where messages for it go, the scope its
+/// request ids are unique in (a session id, or "stdio"), who it
+/// authenticated as and the tools its credentials allow
+#[derive(Clone, Default)]
+pub struct Peer {
+    id: String,
+    outbound: Option<Outbound>,
+    client: Option<String>,
+    tools: Option<Arc<HashSet<String>>>,
+}
+
+impl Peer {
+    pub fn new(id: impl Into<String>, outbound: impl Fn(Value) + Send + Sync + 'static) -> Self {
+        Self {
+            id: id.into(),
+            outbound: Some(Arc::new(outbound)),
+            client: None,
+            tools: None,
+        }
+    }
+
+    /// Name of the credential (API key) the client authenticated with
+    pub fn with_client(mut self, client: Option<String>) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn client(&self) -> Option<&str> {
+        self.client.as_deref()
+    }
+
+    /// Restrict the tools this client may list and call; `None` allows all
+    pub fn with_tool_allowlist(mut self, tools: Option<Arc<HashSet<String>>>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        self.tools.as_ref().is_none_or(|tools| tools.contains(tool))
+    }
+
+    /// A client with no server-to-client channel (plain HTTP POST)
+    pub fn detached() -> Self {
+        Self::default()
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether messages sent to this client are dropped
+    pub fn is_detached(&self) -> bool {
+        self.outbound.is_none()
+    }
+
+    /// Send a message to this client; dropped for detached peers
+    pub fn send(&self, message: Value) {
+        if let Some(outbound) = &self.outbound {
+            outbound(message);
+        }
+    }
+}
+
+/// Per-call context handed to [`McpTool::call_with_context`](super::McpTool::call_with_context)
+#[derive(Clone, Default)]
+pub struct CallContext {
+    peer: Peer,
+    progress_token: Option<Value>,
+    cancellation: CancellationToken,
+}
+
+impl CallContext {
+    pub fn new(peer: Peer, progress_token: Option<Value>) -> Self {
+        Self {
+            peer,
+            progress_token,
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    /// No progress reporting, never cancelled
+    pub fn detached() -> Self {
+        Self::default()
+    }
+
+    /// The client that made the call
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Emit `notifications/progress`; a no-op without a progress token
+    pub fn progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+        let Some(token) = &self.progress_token else {
+            return;
+        };
+        let mut params = json!({"progressToken": token, "progress": progress});
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        if let Some(message) = message {
+            params["message"] = json!(message);
+        }
+        self.peer.send(jsonrpc::notification("notifications/progress", params));
+    }
+}
+
+/// Running requests by `(peer id, request id)`, for `notifications/cancelled`
+#[derive(Default)]
+pub struct InFlightRequests {
+    requests: Mutex<HashMap<(String, String), CancellationToken>>,
+}
+
+impl InFlightRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a request until the returned guard is dropped
+    pub fn track(&self, peer: &Peer, id: &Value, token: CancellationToken) -> InFlightGuard<'_> {
+        let key = (peer.id().to_string(), id.to_string());
+        self.requests.lock().insert(key.clone(), token);
+        InFlightGuard { requests: self, key }
+    }
+
+    /// Cancel a running request; false if it is unknown or already done
+    pub fn cancel(&self, peer: &Peer, id: &Value) -> bool {
+        let key = (peer.id().to_string(), id.to_string());
+        match self.requests.lock().get(&key) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel every running request (shutdown); returns how many there were
+    pub fn cancel_all(&self) -> usize {
+        let requests = self.requests.lock();
+        requests.values().for_each(CancellationToken::cancel);
+        requests.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.lock().is_empty()
+    }
+}
+
+pub struct InFlightGuard<'a> {
+    requests: &'a InFlightRequests,
+    key: (String, String),
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.requests.requests.lock().remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_needs_token() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&sent);
+        let peer = Peer::new("stdio", move |message| sink.lock().push(message));
+
+        CallContext::new(peer.clone(), None).progress(1.0, Some(2.0), None);
+        assert!(sent.lock().is_empty());
+
+        CallContext::new(peer, Some(json!("tok"))).progress(1.0, Some(2.0), Some("1/2"));
+        let sent = sent.lock();
+        assert_eq!(sent[0]["method"], "notifications/progress");
+        assert_eq!(sent[0]["params"], json!({"progressToken": "tok", "progress": 1.0, "total": 2.0, "message": "1/2"}));
+    }
+
+    #[test]
+    fn test_cancel_is_scoped_to_peer() {
+        let in_flight = InFlightRequests::new();
+        let a = Peer::new("a", |_| {});
+        let b = Peer::new("b", |_| {});
+        let call = CallContext::new(a.clone(), None);
+
+        {
+            let _guard = in_flight.track(&a, &json!(7), call.cancellation().clone());
+            assert!(!in_flight.cancel(&b, &json!(7)));
+            assert!(in_flight.cancel(&a, &json!(7)));
+            assert!(call.is_cancelled());
+        }
+        assert!(in_flight.is_empty());
+        assert!(!in_flight.cancel(&a, &json!(7)));
+    }
+
+    #[test]
+    fn test_cancel_all_reaches_every_peer() {
+        let in_flight = InFlightRequests::new();
+        let (a, b) = (Peer::new("a", |_| {}), Peer::new("b", |_| {}));
+        let (first, second) = (CallContext::new(a.clone(), None), CallContext::new(b.clone(), None));
+        let _first = in_flight.track(&a, &json!(1), first.cancellation().clone());
+        let _second = in_flight.track(&b, &json!(1), second.cancellation().clone());
+
+        assert_eq!(in_flight.cancel_all(), 2);
+        assert!(first.is_cancelled() && second.is_cancelled());
+    }
+}
@@ -0,0 +1,388 @@
+//! Prompt templates loaded from a directory (`prompts/list`, `prompts/get`)
+//!
+//! Each `*.json` file holds one template:
+//!
+//! ```json
+//! {
+//!   "name": "summarize_page",
+//!   "description": "Summarize a web page",
+//!   "arguments": [{"name": "url", "description": "Page to summarize", "required": true}],
+//!   "messages": [{"role": "user", "text": "Summarize {{url}} ..."}]
+//! }
+//! ```
+//!
+//! `{{argument}}` placeholders are filled by `prompts/get`. Text between
+//! `{{#argument}}` and `{{/argument}}` is kept only when that argument is
+//! given and not empty, so an optional argument can bring its own sentence
+//! (`{{#focus}} Focus on {{focus}}.{{/focus}}`); sections do not nest.
+//!
+//! The directory is polled; when it changes the templates are reloaded and
+//! `notifications/prompts/list_changed` is broadcast.
+
+use parking_lot::{Mutex, RwLock};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+use super::jsonrpc::{self, RpcError};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptMessage {
+    pub role: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+    pub messages: Vec<PromptMessage>,
+}
+
+impl PromptTemplate {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("name is empty".to_string());
+        }
+        if self.messages.is_empty() {
+            return Err("no messages".to_string());
+        }
+        if let Some(message) = self.messages.iter().find(|m| m.role != "user" && m.role != "assistant") {
+            return Err(format!("role must be user or assistant, got {}", message.role));
+        }
+        for message in &self.messages {
+            let outside = SECTION.replace_all(&message.text, |caps: &regex::Captures| {
+                if caps[1] == caps[3] { String::new() } else { caps[0].to_string() }
+            });
+            if outside.contains("{{#") || outside.contains("{{/") {
+                return Err("unbalanced {{#section}}".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Entry for `prompts/list`
+    pub fn definition(&self) -> Value {
+        let mut definition = json!({"name": self.name, "arguments": self.arguments});
+        if let Some(description) = &self.description {
+            definition["description"] = json!(description);
+        }
+        definition
+    }
+
+    /// `prompts/get` result with `arguments` filled in
+    pub fn render(&self, arguments: &Map<String, Value>) -> Result<Value, RpcError> {
+        if let Some(unknown) = arguments.keys().find(|k| !self.arguments.iter().any(|a| &a.name == *k)) {
+            return Err(RpcError::invalid_params(format!("Unknown argument for {}: {}", self.name, unknown)));
+        }
+        let mut values = Map::new();
+        for argument in &self.arguments {
+            match arguments.get(&argument.name) {
+                Some(Value::String(value)) => {
+                    values.insert(argument.name.clone(), json!(value));
+                }
+                Some(_) => {
+                    return Err(RpcError::invalid_params(format!("Argument {} must be a string", argument.name)))
+                }
+                None if argument.required => {
+                    return Err(RpcError::invalid_params(format!(
+                        "Missing required argument for {}: {}",
+                        self.name, argument.name
+                    )))
+                }
+                None => {}
+            }
+        }
+
+        let messages: Vec<Value> = self
+            .messages
+            .iter()
+            .map(|message| {
+                json!({
+                    "role": message.role,
+                    "content": {"type": "text", "text": fill(&message.text, &values)}
+                })
+            })
+            .collect();
+
+        let mut result = json!({"messages": messages});
+        if let Some(description) = &self.description {
+            result["description"] = json!(description);
+        }
+        Ok(result)
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{\{\s*([A-Za-z0-9_]+)\s*\}\}").unwrap();
+    /// `{{#name}}text{{/name}}`; open and close names are compared by hand
+    static ref SECTION: Regex =
+        Regex::new(r"(?s)\{\{#\s*([A-Za-z0-9_]+)\s*\}\}(.*?)\{\{/\s*([A-Za-z0-9_]+)\s*\}\}").unwrap();
+}
+
+/// Keep the sections of set arguments, then replace `{{name}}` with the
+/// argument value; unset optional arguments become empty
+fn fill(text: &str, values: &Map<String, Value>) -> String {
+    let value = |name: &str| values.get(name).and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let sections = SECTION.replace_all(text, |caps: &regex::Captures| {
+        if value(&caps[1]).is_empty() { String::new() } else { caps[2].to_string() }
+    });
+    PLACEHOLDER
+        .replace_all(&sections, |caps: &regex::Captures| value(&caps[1]))
+        .into_owned()
+}
+
+/// Files and modification times of the template directory
+type Fingerprint = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+fn fingerprint(dir: &Path) -> Fingerprint {
+    let mut files: Fingerprint = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json"))
+        .map(|path| {
+            let meta = std::fs::metadata(&path).ok();
+            let modified = meta.as_ref().and_then(|m| m.modified().ok());
+            let len = meta.map(|m| m.len()).unwrap_or(0);
+            (path, modified, len)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Templates in file-name order; invalid files are logged and skipped
+fn load_templates(files: &Fingerprint) -> Vec<PromptTemplate> {
+    let mut templates: Vec<PromptTemplate> = Vec::new();
+    for (path, _, _) in files {
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| serde_json::from_str::<PromptTemplate>(&raw).map_err(|e| e.to_string()))
+            .and_then(|template| template.validate().map(|_| template));
+        match parsed {
+            Ok(template) if templates.iter().any(|t| t.name == template.name) => {
+                tracing::warn!("Duplicate prompt {} in {}, ignored", template.name, path.display());
+            }
+            Ok(template) => templates.push(template),
+            Err(e) => tracing::warn!("Skipping prompt template {}: {}", path.display(), e),
+        }
+    }
+    templates
+}
+
+pub struct PromptLibrary {
+    dir: Option<PathBuf>,
+    templates: RwLock<Vec<PromptTemplate>>,
+    fingerprint: Mutex<Fingerprint>,
+    notifications: broadcast::Sender<Value>,
+}
+
+impl PromptLibrary {
+    /// No templates and nothing to watch
+    pub fn empty(notifications: broadcast::Sender<Value>) -> Self {
+        Self {
+            dir: None,
+            templates: RwLock::new(Vec::new()),
+            fingerprint: Mutex::new(Vec::new()),
+            notifications,
+        }
+    }
+
+    /// Load every template in `dir`; a missing directory gives no prompts
+    pub fn load(dir: impl Into<PathBuf>, notifications: broadcast::Sender<Value>) -> Self {
+        let dir = dir.into();
+        let files = fingerprint(&dir);
+        let templates = load_templates(&files);
+        tracing::info!("Loaded {} prompt templates from {}", templates.len(), dir.display());
+        Self {
+            dir: Some(dir),
+            templates: RwLock::new(templates),
+            fingerprint: Mutex::new(files),
+            notifications,
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.templates.read().iter().map(|t| t.name.clone()).collect()
+    }
+
+    /// `prompts/list`
+    pub fn list(&self) -> Value {
+        json!({"prompts": self.templates.read().iter().map(|t| t.definition()).collect::<Vec<_>>()})
+    }
+
+    /// `prompts/get`
+    pub fn get(&self, name: &str, arguments: &Map<String, Value>) -> Result<Value, RpcError> {
+        let templates = self.templates.read();
+        let template = templates
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| RpcError::invalid_params(format!("Unknown prompt: {}", name)))?;
+        template.render(arguments)
+    }
+
+    /// Reload when a template file was added, removed or modified; true and
+    /// a `notifications/prompts/list_changed` broadcast if so
+    pub fn reload_if_changed(&self) -> bool {
+        let Some(dir) = &self.dir else {
+            return false;
+        };
+        let files = fingerprint(dir);
+        {
+            let mut current = self.fingerprint.lock();
+            if *current == files {
+                return false;
+            }
+            *self.templates.write() = load_templates(&files);
+            *current = files;
+        }
+        tracing::info!("Prompt templates in {} changed, reloaded", dir.display());
+        let _ = self
+            .notifications
+            .send(jsonrpc::notification("notifications/prompts/list_changed", json!({})));
+        true
+    }
+
+    /// Background watcher polling the directory every `interval`
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.reload_if_changed();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUMMARY: &str = r#"{
+        "name": "summarize_page",
+        "description": "Summarize a page",
+        "arguments": [
+            {"name": "url", "required": true},
+            {"name": "focus"}
+        ],
+        "messages": [{"role": "user", "text": "Summarize {{url}}.{{#focus}} Focus: {{ focus }}{{/focus}}"}]
+    }"#;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("browsermcp_prompts_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn args(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_get_fills_arguments() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("summarize.json"), SUMMARY).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        let (tx, _rx) = broadcast::channel(4);
+        let library = PromptLibrary::load(&dir, tx);
+
+        assert_eq!(library.names(), vec!["summarize_page"]);
+        assert_eq!(library.list()["prompts"][0]["arguments"][0]["required"], true);
+
+        let prompt = library.get("summarize_page", &args(json!({"url": "https://example.com"}))).unwrap();
+        assert_eq!(prompt["messages"][0]["role"], "user");
+        assert_eq!(prompt["messages"][0]["content"]["text"], "Summarize https://example.com.");
+        let focused = args(json!({"url": "https://example.com", "focus": "pricing"}));
+        let prompt = library.get("summarize_page", &focused).unwrap();
+        assert_eq!(prompt["messages"][0]["content"]["text"], "Summarize https://example.com. Focus: pricing");
+        let empty = args(json!({"url": "https://example.com", "focus": ""}));
+        let prompt = library.get("summarize_page", &empty).unwrap();
+        assert_eq!(prompt["messages"][0]["content"]["text"], "Summarize https://example.com.");
+
+        let missing = library.get("summarize_page", &args(json!({"focus": "pricing"}))).unwrap_err();
+        assert_eq!(missing.code, jsonrpc::INVALID_PARAMS);
+        assert!(library.get("summarize_page", &args(json!({"url": "x", "other": "y"}))).is_err());
+        assert!(library.get("nope", &Map::new()).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_directory_change_notifies() {
+        let dir = temp_dir();
+        let (tx, mut rx) = broadcast::channel(4);
+        let library = PromptLibrary::load(&dir, tx);
+        assert!(library.names().is_empty());
+        assert!(!library.reload_if_changed());
+
+        std::fs::write(dir.join("summarize.json"), SUMMARY).unwrap();
+        assert!(library.reload_if_changed());
+        assert_eq!(library.names(), vec!["summarize_page"]);
+        assert_eq!(rx.try_recv().unwrap()["method"], "notifications/prompts/list_changed");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_bundled_templates_load() {
+        let (tx, _rx) = broadcast::channel(1);
+        let library = PromptLibrary::load(concat!(env!("CARGO_MANIFEST_DIR"), "/prompts"), tx);
+        assert_eq!(
+            library.names(),
+            vec!["audit_forms", "browser_task", "change_monitor", "summarize_page"]
+        );
+        let monitor = library.get("change_monitor", &args(json!({"url": "https://example.com"}))).unwrap();
+        assert!(monitor["messages"][0]["content"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("browser://page/https://example.com"));
+
+        // Optional arguments left out leave no dangling sentences
+        for (name, first_line) in [
+            ("change_monitor", "Set up a change monitor for https://example.com."),
+            ("audit_forms", "Audit the forms on https://example.com."),
+            ("summarize_page", "Summarize https://example.com."),
+        ] {
+            let prompt = library.get(name, &args(json!({"url": "https://example.com"}))).unwrap();
+            let text = prompt["messages"][0]["content"]["text"].as_str().unwrap();
+            assert_eq!(text.lines().next(), Some(first_line));
+            assert!(!text.contains("{{"), "{}: {}", name, text);
+        }
+        let watching = args(json!({"url": "https://example.com", "watch_for": "prices"}));
+        let monitor = library.get("change_monitor", &watching).unwrap();
+        assert!(monitor["messages"][0]["content"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("Changes that matter: prices"));
+    }
+
+    #[test]
+    fn test_unbalanced_sections_are_rejected() {
+        let template = |text: &str| PromptTemplate {
+            name: "t".to_string(),
+            description: None,
+            arguments: Vec::new(),
+            messages: vec![PromptMessage { role: "user".to_string(), text: text.to_string() }],
+        };
+        assert!(template("a{{#x}}b{{/x}}c").validate().is_ok());
+        assert!(template("a{{#x}}b").validate().is_err());
+        assert!(template("a{{#x}}b{{/y}}").validate().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! Per-client rate limits and concurrency caps for `tools/call`
+//!
+//! Each client (an API key, an HTTP session) has a token bucket refilled at
+//! `requests_per_second` up to `burst_size`, plus a cap on tool calls running
+//! at once. A call over either limit is answered with a tool error telling
+//! the model how long to wait.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Suggested wait when a client is at its concurrency cap; unlike the token
+/// bucket there is no exact answer
+const CONCURRENCY_RETRY: Duration = Duration::from_millis(1000);
+
+/// Same shape as `scraping::batch::RateLimit`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst_size: usize,
+}
+
+impl RateLimit {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.requests_per_second > 0.0 && self.requests_per_second.is_finite()) {
+            return Err(format!("requests_per_second must be positive, got {}", self.requests_per_second));
+        }
+        if self.burst_size == 0 {
+            return Err("burst_size must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Limits a client is held to; `None` fields are unlimited (or, for an API
+/// key override, fall back to the server defaults)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>,
+}
+
+impl QuotaConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.validate()?;
+        }
+        if self.max_concurrent == Some(0) {
+            return Err("max_concurrent must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// These limits, with unset ones taken from `defaults`
+    pub fn or(self, defaults: QuotaConfig) -> QuotaConfig {
+        QuotaConfig {
+            rate_limit: self.rate_limit.or(defaults.rate_limit),
+            max_concurrent: self.max_concurrent.or(defaults.max_concurrent),
+        }
+    }
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst_size as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.requests_per_second).min(self.limit.burst_size as f64);
+        self.updated = now;
+    }
+
+    /// Time until a token is available; `None` if one is now
+    fn wait(&self) -> Option<Duration> {
+        (self.tokens < 1.0).then(|| Duration::from_secs_f64((1.0 - self.tokens) / self.limit.requests_per_second))
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.limit.burst_size as f64
+    }
+}
+
+#[derive(Default)]
+struct ClientState {
+    bucket: Option<TokenBucket>,
+    in_flight: usize,
+}
+
+/// Which limit a call hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitKind {
+    Rate,
+    Concurrency { max: usize },
+}
+
+/// A rejected call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limited {
+    /// `api_key` or `session`
+    pub scope: String,
+    pub kind: LimitKind,
+    pub retry_after: Duration,
+}
+
+impl Limited {
+    pub fn retry_after_ms(&self) -> u64 {
+        // Round up: retrying a millisecond early would be rejected again
+        self.retry_after.as_micros().div_ceil(1000) as u64
+    }
+
+    /// Structured body of the tool error
+    pub fn to_value(&self) -> Value {
+        let retry_after_ms = self.retry_after_ms();
+        let (reason, message) = match self.kind {
+            LimitKind::Rate => ("rate", format!("Rate limited, retry after {} ms", retry_after_ms)),
+            LimitKind::Concurrency { max } => (
+                "concurrency",
+                format!(
+                    "Rate limited: too many concurrent tool calls (max {}), retry after {} ms",
+                    max, retry_after_ms
+                ),
+            ),
+        };
+        json!({
+            "error": "rate_limited",
+            "message": message,
+            "reason": reason,
+            "scope": self.scope,
+            "retry_after_ms": retry_after_ms
+        })
+    }
+}
+
+/// A client a call is charged to: `(scope, id, limits)`, e.g.
+/// `("api_key", "ci", ...)` or `("session", "<Mcp-Session-Id>", ...)`
+pub type QuotaClient = (&'static str, String, QuotaConfig);
+
+#[derive(Default)]
+pub struct ClientQuotas {
+    clients: Mutex<HashMap<String, ClientState>>,
+}
+
+impl ClientQuotas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Admit one tool call charged to every client in `clients`, or report
+    /// the longest wait among the limits it hits. Nothing is charged when
+    /// the call is rejected. The call counts as running until the permit is
+    /// dropped.
+    pub fn acquire(self: &Arc<Self>, clients: &[QuotaClient]) -> Result<QuotaPermit, Limited> {
+        let now = Instant::now();
+        let mut states = self.clients.lock();
+        let mut limited: Option<Limited> = None;
+        for (scope, id, config) in clients {
+            let state = states.entry(key(scope, id)).or_default();
+            if let Some(limit) = config.rate_limit {
+                let bucket = state.bucket.get_or_insert_with(|| TokenBucket::new(limit, now));
+                bucket.refill(now);
+                if let Some(wait) = bucket.wait() {
+                    limited = worse(limited, scope, LimitKind::Rate, wait);
+                }
+            }
+            if let Some(max) = config.max_concurrent {
+                if state.in_flight >= max {
+                    limited = worse(limited, scope, LimitKind::Concurrency { max }, CONCURRENCY_RETRY);
+                }
+            }
+        }
+        if let Some(limited) = limited {
+            return Err(limited);
+        }
+
+        let keys: Vec<String> = clients.iter().map(|(scope, id, _)| key(scope, id)).collect();
+        for key in &keys {
+            if let Some(state) = states.get_mut(key) {
+                state.in_flight += 1;
+                if let Some(bucket) = &mut state.bucket {
+                    bucket.tokens -= 1.0;
+                }
+            }
+        }
+        Ok(QuotaPermit {
+            quotas: Arc::clone(self),
+            keys,
+        })
+    }
+
+    /// Forget idle clients whose bucket has refilled, returning how many
+    pub fn prune(&self) -> usize {
+        let now = Instant::now();
+        let mut states = self.clients.lock();
+        let before = states.len();
+        states.retain(|_, state| {
+            if let Some(bucket) = &mut state.bucket {
+                bucket.refill(now);
+            }
+            state.in_flight > 0 || state.bucket.as_ref().is_some_and(|bucket| !bucket.is_full())
+        });
+        before - states.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.lock().is_empty()
+    }
+}
+
+fn key(scope: &str, id: &str) -> String {
+    format!("{}:{}", scope, id)
+}
+
+fn worse(current: Option<Limited>, scope: &str, kind: LimitKind, retry_after: Duration) -> Option<Limited> {
+    match current {
+        Some(current) if current.retry_after >= retry_after => Some(current),
+        _ => Some(Limited {
+            scope: scope.to_string(),
+            kind,
+            retry_after,
+        }),
+    }
+}
+
+/// A running tool call; releases its concurrency slots when dropped
+pub struct QuotaPermit {
+    quotas: Arc<ClientQuotas>,
+    keys: Vec<String>,
+}
+
+impl std::fmt::Debug for QuotaPermit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuotaPermit").field("keys", &self.keys).finish()
+    }
+}
+
+impl Drop for QuotaPermit {
+    fn drop(&mut self) {
+        let mut states = self.quotas.clients.lock();
+        for key in &self.keys {
+            if let Some(state) = states.get_mut(key) {
+                state.in_flight = state.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(requests_per_second: f64, burst_size: usize, max_concurrent: Option<usize>) -> QuotaConfig {
+        QuotaConfig {
+            rate_limit: Some(RateLimit {
+                requests_per_second,
+                burst_size,
+            }),
+            max_concurrent,
+        }
+    }
+
+    #[test]
+    fn test_bucket_allows_burst_then_reports_wait() {
+        let quotas = Arc::new(ClientQuotas::new());
+        let clients = [("api_key", "ci".to_string(), limits(10.0, 2, None))];
+
+        let _a = quotas.acquire(&clients).unwrap();
+        let _b = quotas.acquire(&clients).unwrap();
+        let limited = quotas.acquire(&clients).unwrap_err();
+        assert_eq!(limited.kind, LimitKind::Rate);
+        assert!(limited.retry_after_ms() > 0 && limited.retry_after_ms() <= 100);
+        assert_eq!(limited.to_value()["error"], "rate_limited");
+
+        std::thread::sleep(Duration::from_millis(110));
+        assert!(quotas.acquire(&clients).is_ok());
+    }
+
+    #[test]
+    fn test_rejected_call_charges_nothing() {
+        let quotas = Arc::new(ClientQuotas::new());
+        let key = ("api_key", "ci".to_string(), limits(0.001, 10, None));
+        let session = ("session", "s1".to_string(), limits(0.001, 1, None));
+
+        let _first = quotas.acquire(&[key.clone(), session.clone()]).unwrap();
+        let limited = quotas.acquire(&[key.clone(), session]).unwrap_err();
+        assert_eq!(limited.scope, "session");
+        assert!(limited.retry_after > Duration::from_secs(60));
+
+        // The key bucket was not charged for the rejected call
+        let other_session = ("session", "s2".to_string(), limits(1000.0, 10, None));
+        for _ in 0..9 {
+            drop(quotas.acquire(&[key.clone(), other_session.clone()]).unwrap());
+        }
+        assert_eq!(quotas.acquire(&[key, other_session]).unwrap_err().scope, "api_key");
+    }
+
+    #[test]
+    fn test_concurrency_cap_released_on_drop() {
+        let quotas = Arc::new(ClientQuotas::new());
+        let one_at_a_time = QuotaConfig {
+            rate_limit: None,
+            max_concurrent: Some(1),
+        };
+        let clients = [("api_key", "ci".to_string(), one_at_a_time)];
+
+        let running = quotas.acquire(&clients).unwrap();
+        let limited = quotas.acquire(&clients).unwrap_err();
+        assert_eq!(limited.kind, LimitKind::Concurrency { max: 1 });
+        assert_eq!(limited.to_value()["reason"], "concurrency");
+
+        drop(running);
+        let _next = quotas.acquire(&clients).unwrap();
+        assert_eq!(quotas.prune(), 0);
+    }
+
+    #[test]
+    fn test_prune_forgets_refilled_clients() {
+        let quotas = Arc::new(ClientQuotas::new());
+        drop(quotas.acquire(&[("session", "s".to_string(), limits(1000.0, 1, None))]).unwrap());
+        assert_eq!(quotas.len(), 1);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(quotas.prune(), 1);
+        assert!(quotas.is_empty());
+    }
+
+    #[test]
+    fn test_overrides_and_validation() {
+        let defaults = limits(5.0, 10, Some(4));
+        let key = QuotaConfig {
+            rate_limit: None,
+            max_concurrent: Some(1),
+        };
+        assert_eq!(key.or(defaults), limits(5.0, 10, Some(1)));
+        assert!(limits(0.0, 1, None).validate().is_err());
+        assert!(limits(1.0, 0, None).validate().is_err());
+        assert!(defaults.validate().is_ok());
+    }
+}
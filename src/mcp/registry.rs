@@ -0,0 +1,546 @@
+//! Tool registry: single source for `tools/list`, `tools/call` and tool counts
+//!
+//! Implement [`McpTool`] (or wrap a closure in [`FnTool`] / [`TypedTool`]) and
+//! register it on a [`ToolRegistry`] to expose it from the MCP server.
+//! Arguments are validated against the tool's `inputSchema` before dispatch.
+//! Tools that declare an `outputSchema` answer with `structuredContent` next
+//! to the usual text block; an output that breaks its schema becomes an
+//! `isError` result listing the violations.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use thiserror::Error;
+
+use super::jsonrpc::RpcError;
+use super::progress::CallContext;
+use super::schema::{self, ToolArgs, ToolOutput};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ToolError {
+    /// Arguments do not match what the tool needs (-32602 to the client)
+    #[error("{0}")]
+    InvalidArguments(String),
+
+    /// The tool ran and failed (`isError: true` result to the client)
+    #[error("{0}")]
+    Execution(String),
+}
+
+pub type ToolResult = Result<Value, ToolError>;
+
+/// Behaviour hints in `tools/list` that hosts use to decide whether a call
+/// needs approval. The default is the spec's worst case: may write, may
+/// destroy, not idempotent, reaches the outside world.
+///
+/// Local bookkeeping (the analysis store, the audit log) does not count as
+/// modifying the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    pub read_only_hint: bool,
+    pub destructive_hint: bool,
+    pub idempotent_hint: bool,
+    pub open_world_hint: bool,
+}
+
+impl Default for ToolAnnotations {
+    fn default() -> Self {
+        Self {
+            read_only_hint: false,
+            destructive_hint: true,
+            idempotent_hint: false,
+            open_world_hint: true,
+        }
+    }
+}
+
+impl ToolAnnotations {
+    /// Reads only local input; calling it again changes nothing
+    pub fn read_only() -> Self {
+        Self {
+            read_only_hint: true,
+            destructive_hint: false,
+            idempotent_hint: true,
+            open_world_hint: false,
+        }
+    }
+
+    /// Also talks to hosts outside the server (fetches URLs, ...)
+    pub fn open_world(mut self) -> Self {
+        self.open_world_hint = true;
+        self
+    }
+}
+
+/// A tool exposed over MCP
+#[async_trait]
+pub trait McpTool: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// JSON Schema of the `arguments` object
+    fn input_schema(&self) -> Value;
+
+    async fn call(&self, args: Value) -> ToolResult;
+
+    /// Call with progress reporting and cancellation; long-running tools
+    /// override this, the rest keep the plain [`call`](McpTool::call)
+    async fn call_with_context(&self, args: Value, _ctx: &CallContext) -> ToolResult {
+        self.call(args).await
+    }
+
+    /// JSON Schema of the result, sent back as `structuredContent`
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::default()
+    }
+
+    /// Entry for `tools/list`
+    fn definition(&self) -> Value {
+        let mut definition = json!({
+            "name": self.name(),
+            "description": self.description(),
+            "inputSchema": self.input_schema(),
+            "annotations": self.annotations()
+        });
+        if let Some(output_schema) = self.output_schema() {
+            definition["outputSchema"] = output_schema;
+        }
+        definition
+    }
+}
+
+/// Adapter turning an async closure into an [`McpTool`]
+pub struct FnTool<F> {
+    name: String,
+    description: String,
+    input_schema: Value,
+    output_schema: Option<Value>,
+    annotations: ToolAnnotations,
+    handler: F,
+}
+
+impl<F, Fut> FnTool<F>
+where
+    F: Fn(Value) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolResult> + Send,
+{
+    pub fn new(name: &str, description: &str, input_schema: Value, handler: F) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema,
+            output_schema: None,
+            annotations: ToolAnnotations::default(),
+            handler,
+        }
+    }
+
+    /// Advertise `O`'s schema as the `outputSchema`
+    pub fn with_output<O: ToolOutput>(self) -> Self {
+        self.with_output_schema(O::schema())
+    }
+
+    pub fn with_output_schema(mut self, output_schema: Value) -> Self {
+        self.output_schema = Some(output_schema);
+        self
+    }
+
+    pub fn with_annotations(mut self, annotations: ToolAnnotations) -> Self {
+        self.annotations = annotations;
+        self
+    }
+}
+
+#[async_trait]
+impl<F, Fut> McpTool for FnTool<F>
+where
+    F: Fn(Value) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolResult> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn input_schema(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        self.output_schema.clone()
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        self.annotations
+    }
+
+    async fn call(&self, args: Value) -> ToolResult {
+        (self.handler)(args).await
+    }
+}
+
+/// Adapter for handlers taking a typed [`ToolArgs`] struct; the advertised
+/// schema is the one generated for that struct
+pub struct TypedTool<A, F> {
+    name: String,
+    description: String,
+    output_schema: Option<Value>,
+    annotations: ToolAnnotations,
+    handler: F,
+    _args: PhantomData<fn() -> A>,
+}
+
+impl<A: ToolArgs> TypedTool<A, ()> {
+    pub fn new<H, Fut>(
+        name: &str,
+        description: &str,
+        handler: H,
+    ) -> TypedTool<A, impl Fn(A, CallContext) -> Fut + Send + Sync>
+    where
+        H: Fn(A) -> Fut + Send + Sync,
+        Fut: Future<Output = ToolResult> + Send,
+    {
+        TypedTool::with_context(name, description, move |args, _ctx| handler(args))
+    }
+}
+
+impl<A, F, Fut> TypedTool<A, F>
+where
+    A: ToolArgs,
+    F: Fn(A, CallContext) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolResult> + Send,
+{
+    /// Handler also given the call's context: progress, cancellation and
+    /// the client it came from
+    pub fn with_context(name: &str, description: &str, handler: F) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            output_schema: None,
+            annotations: ToolAnnotations::default(),
+            handler,
+            _args: PhantomData,
+        }
+    }
+
+    /// Advertise `O`'s schema as the `outputSchema`
+    pub fn with_output<O: ToolOutput>(self) -> Self {
+        self.with_output_schema(O::schema())
+    }
+
+    pub fn with_output_schema(mut self, output_schema: Value) -> Self {
+        self.output_schema = Some(output_schema);
+        self
+    }
+
+    pub fn with_annotations(mut self, annotations: ToolAnnotations) -> Self {
+        self.annotations = annotations;
+        self
+    }
+}
+
+#[async_trait]
+impl<A, F, Fut> McpTool for TypedTool<A, F>
+where
+    A: ToolArgs,
+    F: Fn(A, CallContext) -> Fut + Send + Sync,
+    Fut: Future<Output = ToolResult> + Send,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn input_schema(&self) -> Value {
+        A::schema()
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        self.output_schema.clone()
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        self.annotations
+    }
+
+    async fn call(&self, args: Value) -> ToolResult {
+        self.call_with_context(args, &CallContext::detached()).await
+    }
+
+    async fn call_with_context(&self, args: Value, ctx: &CallContext) -> ToolResult {
+        let args: A = serde_json::from_value(args)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        (self.handler)(args, ctx.clone()).await
+    }
+}
+
+/// Registered tools, listed in registration order
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn McpTool>>,
+    index: HashMap<String, usize>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tool. A tool with the same name replaces the previous one.
+    pub fn register<T: McpTool + 'static>(&mut self, tool: T) -> &mut Self {
+        self.register_arc(Arc::new(tool))
+    }
+
+    pub fn register_arc(&mut self, tool: Arc<dyn McpTool>) -> &mut Self {
+        match self.index.get(tool.name()) {
+            Some(&i) => self.tools[i] = tool,
+            None => {
+                self.index.insert(tool.name().to_string(), self.tools.len());
+                self.tools.push(tool);
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn McpTool>> {
+        self.index.get(name).map(|&i| Arc::clone(&self.tools[i]))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.name()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// `tools/list` result
+    pub fn list(&self) -> Value {
+        self.list_filtered(|_| true)
+    }
+
+    /// `tools/list` result limited to the tools `visible` accepts
+    pub fn list_filtered(&self, visible: impl Fn(&str) -> bool) -> Value {
+        let tools: Vec<Value> = self
+            .tools
+            .iter()
+            .filter(|t| visible(t.name()))
+            .map(|t| t.definition())
+            .collect();
+        json!({"tools": tools})
+    }
+
+    /// `tools/call`: unknown tools and bad arguments are protocol errors,
+    /// execution failures are `isError` results.
+    pub async fn call(&self, name: &str, args: Value) -> Result<Value, RpcError> {
+        self.call_with_context(name, args, &CallContext::detached()).await
+    }
+
+    pub async fn call_with_context(&self, name: &str, args: Value, ctx: &CallContext) -> Result<Value, RpcError> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| RpcError::invalid_params(format!("Unknown tool: {}", name)))?;
+
+        let violations = schema::validate(&tool.input_schema(), &args);
+        if !violations.is_empty() {
+            let summary: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            return Err(RpcError::invalid_params(format!(
+                "arguments for {} do not match inputSchema: {}",
+                name,
+                summary.join("; ")
+            ))
+            .with_data(json!({"violations": violations})));
+        }
+
+        match tool.call_with_context(args, ctx).await {
+            Ok(output) => match tool.output_schema() {
+                Some(output_schema) => {
+                    let violations = schema::validate(&output_schema, &output);
+                    if violations.is_empty() {
+                        return Ok(super::structured_tool_result(&output));
+                    }
+                    // Clients validate structuredContent against the schema
+                    // we advertised: report the bug instead of sending it
+                    let summary: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                    let message = format!("{} result does not match its outputSchema: {}", name, summary.join("; "));
+                    tracing::warn!("{}", message);
+                    Ok(super::tool_error(message))
+                }
+                None => Ok(super::tool_result(&output)),
+            },
+            Err(ToolError::InvalidArguments(msg)) => Err(RpcError::invalid_params(msg)),
+            Err(ToolError::Execution(msg)) => Ok(super::tool_error(msg)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::jsonrpc::INVALID_PARAMS;
+
+    struct Upper;
+
+    #[async_trait]
+    impl McpTool for Upper {
+        fn name(&self) -> &str {
+            "upper"
+        }
+
+        fn description(&self) -> &str {
+            "Uppercase a string"
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}, "required": ["text"]})
+        }
+
+        async fn call(&self, args: Value) -> ToolResult {
+            let text = args
+                .get("text")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ToolError::InvalidArguments("text required".to_string()))?;
+            Ok(json!({"text": text.to_uppercase()}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_list_and_call() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Upper).register(FnTool::new(
+            "fail",
+            "Always fails",
+            json!({"type": "object"}),
+            |_args| async { Err(ToolError::Execution("boom".to_string())) },
+        ));
+
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.names(), vec!["upper", "fail"]);
+        assert_eq!(registry.list()["tools"][0]["inputSchema"]["required"][0], "text");
+
+        let ok = registry.call("upper", json!({"text": "hi"})).await.unwrap();
+        assert_eq!(ok["isError"], false);
+        assert!(ok["content"][0]["text"].as_str().unwrap().contains("HI"));
+
+        let failed = registry.call("fail", json!({})).await.unwrap();
+        assert_eq!(failed["isError"], true);
+
+        let bad_args = registry.call("upper", json!({})).await.unwrap_err();
+        assert_eq!(bad_args.code, INVALID_PARAMS);
+
+        let unknown = registry.call("missing", json!({})).await.unwrap_err();
+        assert_eq!(unknown.code, INVALID_PARAMS);
+    }
+
+    crate::tool_args! {
+        struct RepeatArgs {
+            required text: String = {"type": "string", "minLength": 1},
+            optional times: Option<u64> = {"type": "integer", "minimum": 1},
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schema_violations_rejected_before_dispatch() {
+        let mut registry = ToolRegistry::new();
+        registry.register(TypedTool::new("repeat", "Repeat text", |args: RepeatArgs| async move {
+            Ok(json!(args.text.repeat(args.times.unwrap_or(1) as usize)))
+        }));
+
+        assert_eq!(registry.list()["tools"][0]["inputSchema"]["required"], json!(["text"]));
+
+        let ok = registry.call("repeat", json!({"text": "ab", "times": 2})).await.unwrap();
+        assert!(ok["content"][0]["text"].as_str().unwrap().contains("abab"));
+
+        let err = registry.call("repeat", json!({"times": 0})).await.unwrap_err();
+        assert_eq!(err.code, INVALID_PARAMS);
+        let violations = err.data.unwrap()["violations"].clone();
+        assert_eq!(violations[0]["pointer"], "/text");
+        assert_eq!(violations[1]["pointer"], "/times");
+        assert!(err.message.contains("/text") && err.message.contains("/times"));
+    }
+
+    #[test]
+    fn test_register_replaces_same_name() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Upper);
+        registry.register(FnTool::new("upper", "Replacement", json!({"type": "object"}), |_| async {
+            Ok(Value::Null)
+        }));
+
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get("upper").unwrap().description(), "Replacement");
+    }
+
+    crate::tool_output! {
+        struct Shout {
+            required text: String = {"type": "string"},
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_schema_and_annotations() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Upper).register(
+            TypedTool::new("shout", "Shout text", |args: RepeatArgs| async move {
+                serde_json::to_value(Shout { text: args.text.to_uppercase() })
+                    .map_err(|e| ToolError::Execution(e.to_string()))
+            })
+            .with_output::<Shout>()
+            .with_annotations(ToolAnnotations::read_only()),
+        );
+
+        let tools = registry.list()["tools"].clone();
+        assert_eq!(
+            tools[0]["annotations"],
+            json!({"readOnlyHint": false, "destructiveHint": true, "idempotentHint": false, "openWorldHint": true})
+        );
+        assert!(tools[0].get("outputSchema").is_none());
+        assert_eq!(tools[1]["annotations"]["readOnlyHint"], true);
+        assert_eq!(tools[1]["outputSchema"]["required"], json!(["text"]));
+
+        let plain = registry.call("upper", json!({"text": "hi"})).await.unwrap();
+        assert!(plain.get("structuredContent").is_none());
+
+        let structured = registry.call("shout", json!({"text": "hi"})).await.unwrap();
+        assert_eq!(structured["structuredContent"], json!({"text": "HI"}));
+        // Text fallback carries the same JSON
+        let text: Value = serde_json::from_str(structured["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text, structured["structuredContent"]);
+    }
+
+    #[tokio::test]
+    async fn test_output_off_schema_is_an_error() {
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            TypedTool::new("shout", "Shout text", |_: RepeatArgs| async move { Ok(json!({"text": 42})) })
+                .with_output::<Shout>(),
+        );
+
+        let result = registry.call("shout", json!({"text": "hi"})).await.unwrap();
+        assert_eq!(result["isError"], true);
+        assert!(result.get("structuredContent").is_none());
+        let message = result["content"][0]["text"].as_str().unwrap();
+        assert!(message.contains("does not match its outputSchema"), "{}", message);
+        assert!(message.contains("text"), "{}", message);
+    }
+}
@@ -0,0 +1,249 @@
+//! MCP resources: static docs plus stored analyses and workflow executions
+//!
+//! | URI | Source |
+//! |-----|--------|
+//! | `browser://docs/usage`, `browser://docs/anti-detection` | built-in markdown |
+//! | `browser://analysis/recent` | `DatabasePersistence::get_recent_analyses` |
+//! | `browser://analysis/{id}` | `DatabasePersistence::get_analysis` |
+//! | `browser://workflow/{id}/executions` | `WorkflowOrchestrator::get_execution_history` |
+//! | `browser://page/{+url}` | latest snapshot of a monitored page, see [`monitor`](super::monitor) |
+//!
+//! Analyses are served only to the API key that stored them (and those
+//! stored without a key only to unauthenticated clients).
+
+use serde_json::{json, Value};
+
+use super::context::{analysis_uri, ServerContext};
+use super::jsonrpc::RpcError;
+use super::monitor;
+use super::progress::Peer;
+use crate::database_persistence::AnalysisResult;
+
+/// MCP-specific error code for unknown resources
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+/// Analyses listed by `resources/list` and `browser://analysis/recent`
+const RECENT_LIMIT: usize = 20;
+
+const USAGE_DOC: &str = "# BrowserMCP Usage
+
+Call tools with `tools/call` (`name` + `arguments`). Results that come from a
+page analysis include an `analysis_uri`; read it with `resources/read` instead
+of scraping again.
+
+- `browser://analysis/recent` - latest stored analyses
+- `browser://analysis/{id}` - one stored analysis
+- `browser://workflow/{id}/executions` - execution history of a workflow
+- `browser://page/{url}` - snapshot of a page; `resources/subscribe` to it to
+  get `notifications/resources/updated` when its content changes
+";
+
+const ANTI_DETECTION_DOC: &str = "# Anti-Detection Guide
+
+`get_stealth_headers` returns a browser-like header set (rotating
+`User-Agent`, `Accept`, `Accept-Language`, `DNT`, `Connection`). Send the same
+set on every request of a session and keep request rates low per host.
+";
+
+fn not_found(uri: &str) -> RpcError {
+    RpcError::new(RESOURCE_NOT_FOUND, "Resource not found").with_data(json!({"uri": uri}))
+}
+
+fn contents(uri: &str, mime_type: &str, text: String) -> Value {
+    json!({"contents": [{"uri": uri, "mimeType": mime_type, "text": text}]})
+}
+
+/// The caller's latest analyses
+fn recent(ctx: &ServerContext, peer: &Peer) -> Result<Vec<AnalysisResult>, RpcError> {
+    let all = ctx
+        .with_db(|db| db.get_recent_analyses(usize::MAX))
+        .map_err(RpcError::internal)?;
+    Ok(all.into_iter().filter(|a| visible(a, peer)).take(RECENT_LIMIT).collect())
+}
+
+fn visible(analysis: &AnalysisResult, peer: &Peer) -> bool {
+    analysis.client.as_deref() == peer.client()
+}
+
+fn json_contents(uri: &str, value: &impl serde::Serialize) -> Result<Value, RpcError> {
+    let text = serde_json::to_string_pretty(value).map_err(RpcError::internal)?;
+    Ok(contents(uri, "application/json", text))
+}
+
+/// `resources/list`; the only pages listed are those the caller subscribed to
+pub fn list(ctx: &ServerContext, peer: &Peer) -> Result<Value, RpcError> {
+    let mut resources = vec![
+        json!({"uri": "browser://docs/usage", "name": "BrowserMCP Usage", "description": "Complete documentation", "mimeType": "text/markdown"}),
+        json!({"uri": "browser://docs/anti-detection", "name": "Anti-Detection Guide", "description": "Advanced techniques", "mimeType": "text/markdown"}),
+        json!({"uri": "browser://analysis/recent", "name": "Recent analyses", "description": "Latest stored tool results", "mimeType": "application/json"}),
+    ];
+
+    resources.extend(recent(ctx, peer)?.iter().map(|analysis| {
+        json!({
+            "uri": analysis_uri(&analysis.id),
+            "name": format!("{} {}", analysis.analysis_type, analysis.query),
+            "mimeType": "application/json"
+        })
+    }));
+    resources.extend(ctx.pages().subscribed_by(peer.id()).into_iter().filter_map(|uri| {
+        let url = monitor::page_url(&uri)?;
+        Some(json!({
+            "uri": uri,
+            "name": format!("Snapshot {}", url),
+            "mimeType": "application/json"
+        }))
+    }));
+
+    Ok(json!({"resources": resources}))
+}
+
+/// `resources/templates/list`
+pub fn templates() -> Value {
+    json!({
+        "resourceTemplates": [
+            {"uriTemplate": "browser://analysis/{id}", "name": "Stored analysis", "description": "A previous tool result by id", "mimeType": "application/json"},
+            {"uriTemplate": "browser://workflow/{id}/executions", "name": "Workflow executions", "description": "Execution history of a workflow", "mimeType": "application/json"},
+            {"uriTemplate": "browser://page/{+url}", "name": "Page snapshot", "description": "Scraped snapshot of a page; subscribable", "mimeType": "application/json"}
+        ]
+    })
+}
+
+/// `resources/subscribe`: only page snapshots change, so only they can be
+/// subscribed to, and only by clients that can be notified
+pub async fn subscribe(ctx: &ServerContext, uri: &str, peer: &Peer) -> Result<Value, RpcError> {
+    if peer.is_detached() {
+        return Err(RpcError::invalid_params(
+            "subscriptions need a session (Mcp-Session-Id) or the stdio transport",
+        ));
+    }
+    if !ctx.pages().subscribe(uri, peer).await {
+        return Err(RpcError::invalid_params(format!(
+            "only {}{{url}} resources support subscriptions",
+            monitor::PAGE_PREFIX
+        )));
+    }
+    Ok(json!({}))
+}
+
+/// `resources/unsubscribe`: only the caller's own subscription ends
+pub fn unsubscribe(ctx: &ServerContext, uri: &str, peer: &Peer) -> Result<Value, RpcError> {
+    ctx.pages().unsubscribe(uri, peer.id());
+    Ok(json!({}))
+}
+
+/// `resources/read`
+pub async fn read(ctx: &ServerContext, uri: &str, peer: &Peer) -> Result<Value, RpcError> {
+    if let Some(url) = monitor::page_url(uri) {
+        let snapshot = ctx.pages().snapshot(url).await.map_err(RpcError::internal)?;
+        return json_contents(uri, &snapshot);
+    }
+
+    let path = uri.strip_prefix("browser://").ok_or_else(|| not_found(uri))?;
+    let segments: Vec<&str> = path.split('/').collect();
+
+    match segments.as_slice() {
+        ["docs", "usage"] => Ok(contents(uri, "text/markdown", USAGE_DOC.to_string())),
+        ["docs", "anti-detection"] => Ok(contents(uri, "text/markdown", ANTI_DETECTION_DOC.to_string())),
+        ["analysis", "recent"] => json_contents(uri, &recent(ctx, peer)?),
+        ["analysis", id] => {
+            // Another key's analysis is reported as missing
+            let analysis = ctx
+                .with_db(|db| db.get_analysis(id))
+                .map_err(RpcError::internal)?
+                .filter(|analysis| visible(analysis, peer))
+                .ok_or_else(|| not_found(uri))?;
+            json_contents(uri, &analysis)
+        }
+        ["workflow", id, "executions"] => {
+            let workflows = ctx.workflows();
+            let orchestrator = workflows.lock().await;
+            if orchestrator.get_workflow(id).is_none() {
+                return Err(not_found(uri));
+            }
+            json_contents(uri, &orchestrator.get_execution_history(id))
+        }
+        _ => Err(not_found(uri)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> (ServerContext, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("browsermcp_resources_{}", uuid::Uuid::new_v4()));
+        (ServerContext::new(dir.to_str().unwrap()).unwrap(), dir)
+    }
+
+    #[tokio::test]
+    async fn test_read_docs_and_unknown() {
+        let (ctx, dir) = context();
+        let usage = read(&ctx, "browser://docs/usage", &Peer::detached()).await.unwrap();
+        assert_eq!(usage["contents"][0]["mimeType"], "text/markdown");
+
+        let missing = read(&ctx, "browser://nope", &Peer::detached()).await.unwrap_err();
+        assert_eq!(missing.code, RESOURCE_NOT_FOUND);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_stored_analysis_by_reference() {
+        let (ctx, dir) = context();
+        let uri = ctx
+            .record_analysis(None, "scrape", "web_scrape", "https://example.com", &json!({"title": "Example"}), 12)
+            .unwrap();
+
+        let read_back = read(&ctx, &uri, &Peer::detached()).await.unwrap();
+        let stored: Value = serde_json::from_str(read_back["contents"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(stored["data"]["title"], "Example");
+        assert_eq!(stored["query"], "https://example.com");
+
+        let listed = list(&ctx, &Peer::detached()).unwrap();
+        assert!(listed["resources"].as_array().unwrap().iter().any(|r| r["uri"] == uri.as_str()));
+
+        let missing = read(&ctx, "browser://analysis/does_not_exist", &Peer::detached()).await.unwrap_err();
+        assert_eq!(missing.code, RESOURCE_NOT_FOUND);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyses_are_private_to_their_key() {
+        let (ctx, dir) = context();
+        let uri = ctx
+            .record_analysis(Some("ci"), "scrape", "web_scrape", "https://example.com", &json!({"title": "Example"}), 12)
+            .unwrap();
+        let ci = Peer::new("s1", |_| {}).with_client(Some("ci".to_string()));
+        let other = Peer::new("s2", |_| {}).with_client(Some("other".to_string()));
+
+        assert!(read(&ctx, &uri, &ci).await.is_ok());
+        for peer in [&other, &Peer::detached()] {
+            assert_eq!(read(&ctx, &uri, peer).await.unwrap_err().code, RESOURCE_NOT_FOUND);
+            let listed = list(&ctx, peer).unwrap();
+            assert!(!listed["resources"].as_array().unwrap().iter().any(|r| r["uri"] == uri.as_str()));
+            let recent = read(&ctx, "browser://analysis/recent", peer).await.unwrap();
+            assert_eq!(recent["contents"][0]["text"], "[]");
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_workflow_executions() {
+        let (ctx, dir) = context();
+        let workflows = ctx.workflows();
+        {
+            let mut orchestrator = workflows.lock().await;
+            let mut task = crate::workflow_orchestrator::WorkflowOrchestrator::create_competitive_monitoring_workflow();
+            task.steps.retain(|s| s.action == "alert");
+            orchestrator.create_workflow(task);
+            orchestrator.execute_workflow("competitive_monitoring").await.unwrap();
+        }
+
+        let history = read(&ctx, "browser://workflow/competitive_monitoring/executions", &Peer::detached()).await.unwrap();
+        let executions: Value = serde_json::from_str(history["contents"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(executions.as_array().unwrap().len(), 1);
+
+        let unknown = read(&ctx, "browser://workflow/nope/executions", &Peer::detached()).await.unwrap_err();
+        assert_eq!(unknown.code, RESOURCE_NOT_FOUND);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
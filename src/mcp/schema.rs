@@ -0,0 +1,410 @@
+//! JSON Schema validation for `tools/call` arguments and results
+//!
+//! Covers the subset used by tool schemas: `type`, `enum`, `const`, `anyOf`,
+//! `properties`, `required`, `additionalProperties`, `items`, length/size
+//! bounds, `minimum`/`maximum`, `pattern` and `format: "uri"`.
+//!
+//! Typed argument structs are declared with [`tool_args!`](crate::tool_args)
+//! and result structs with [`tool_output!`](crate::tool_output); both generate
+//! the struct and its schema from one definition.
+
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// One schema violation, located by JSON pointer into the arguments
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub pointer: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pointer = if self.pointer.is_empty() { "(root)" } else { &self.pointer };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+/// Arguments struct with a JSON Schema, see [`tool_args!`](crate::tool_args)
+pub trait ToolArgs: DeserializeOwned + Send {
+    fn schema() -> Value;
+}
+
+/// Result struct with a JSON Schema (the tool's `outputSchema`), see
+/// [`tool_output!`](crate::tool_output)
+pub trait ToolOutput: Serialize {
+    fn schema() -> Value;
+}
+
+/// Validate `instance` against `schema`, collecting every violation
+pub fn validate(schema: &Value, instance: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    validate_at(schema, instance, String::new(), &mut violations);
+    violations
+}
+
+fn validate_at(schema: &Value, instance: &Value, pointer: String, out: &mut Vec<Violation>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    let mut fail = |message: String| {
+        out.push(Violation {
+            pointer: pointer.clone(),
+            message,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(instance, t)) {
+            fail(format!("expected {}, got {}", types.join(" or "), type_name(instance)));
+            // Nothing else is meaningful on the wrong type
+            return;
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(instance) {
+            fail(format!("must be one of {}", Value::Array(allowed.clone())));
+        }
+    }
+
+    if let Some(constant) = schema.get("const") {
+        if constant != instance {
+            fail(format!("must be {}", constant));
+        }
+    }
+
+    if let Some(Value::Array(branches)) = schema.get("anyOf") {
+        if !branches.iter().any(|branch| validate(branch, instance).is_empty()) {
+            fail(format!("does not match any of the {} allowed schemas", branches.len()));
+        }
+    }
+
+    match instance {
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|v| v.as_u64()) {
+                if len < min {
+                    fail(format!("must be at least {} characters", min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|v| v.as_u64()) {
+                if len > max {
+                    fail(format!("must be at most {} characters", max));
+                }
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(|v| v.as_str()) {
+                match Regex::new(pattern) {
+                    Ok(re) if !re.is_match(s) => fail(format!("must match pattern {}", pattern)),
+                    Ok(_) => {}
+                    Err(_) => fail(format!("schema pattern {} is invalid", pattern)),
+                }
+            }
+            if schema.get("format").and_then(|v| v.as_str()) == Some("uri")
+                && reqwest::Url::parse(s).is_err()
+            {
+                fail("must be an absolute URI".to_string());
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = schema.get("minimum").and_then(|v| v.as_f64()) {
+                if n < min {
+                    fail(format!("must be >= {}", min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|v| v.as_f64()) {
+                if n > max {
+                    fail(format!("must be <= {}", max));
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) < min {
+                    fail(format!("must have at least {} items", min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) > max {
+                    fail(format!("must have at most {} items", max));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, format!("{}/{}", pointer, i), out);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            let properties = schema.get("properties").and_then(|v| v.as_object());
+
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(|v| v.as_str()) {
+                    if !fields.contains_key(name) {
+                        out.push(Violation {
+                            pointer: child_pointer(&pointer, name),
+                            message: "required property is missing".to_string(),
+                        });
+                    }
+                }
+            }
+
+            for (name, value) in fields {
+                let child = child_pointer(&pointer, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(property_schema) => validate_at(property_schema, value, child, out),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => out.push(Violation {
+                            pointer: child,
+                            message: "unknown property".to_string(),
+                        }),
+                        Some(extra @ Value::Object(_)) => validate_at(extra, value, child, out),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(instance: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        "number" => instance.is_number(),
+        "integer" => instance.is_i64() || instance.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// RFC 6901 escaping of a property name
+fn child_pointer(parent: &str, name: &str) -> String {
+    format!("{}/{}", parent, name.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Object schema from `(name, property schema, required)` entries
+pub fn object_schema(properties: Vec<(&str, Value, bool)>) -> Value {
+    let required: Vec<&str> = properties
+        .iter()
+        .filter(|(_, _, required)| *required)
+        .map(|(name, _, _)| *name)
+        .collect();
+    let properties: serde_json::Map<String, Value> = properties
+        .into_iter()
+        .map(|(name, schema, _)| (name.to_string(), schema))
+        .collect();
+    json!({"type": "object", "properties": properties, "required": required})
+}
+
+/// Property name of a struct field: serde drops the `r#` of raw identifiers
+#[doc(hidden)]
+pub fn field_name(ident: &'static str) -> &'static str {
+    ident.strip_prefix("r#").unwrap_or(ident)
+}
+
+/// Declare a typed tool-arguments struct together with its JSON Schema.
+///
+/// Fields marked `required` must be present; `optional` fields should be
+/// `Option<T>` so serde treats a missing key as `None`.
+///
+/// ```ignore
+/// tool_args! {
+///     pub struct ScrapeArgs {
+///         required url: String = {"type": "string", "format": "uri"},
+///         optional selectors: Option<Vec<String>> = {"type": "array", "items": {"type": "string"}},
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! tool_args {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( $(#[$fmeta:meta])* $presence:ident $field:ident : $ty:ty = $schema:tt ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, serde::Deserialize)]
+        $vis struct $name {
+            $( $(#[$fmeta])* pub $field: $ty, )*
+        }
+
+        impl $crate::mcp::schema::ToolArgs for $name {
+            fn schema() -> serde_json::Value {
+                $crate::mcp::schema::object_schema(vec![
+                    $( (
+                        $crate::mcp::schema::field_name(stringify!($field)),
+                        serde_json::json!($schema),
+                        $crate::tool_args!(@required $presence),
+                    ), )*
+                ])
+            }
+        }
+    };
+    (@required required) => { true };
+    (@required optional) => { false };
+}
+
+/// Declare a tool result struct together with its JSON Schema, advertised as
+/// the tool's `outputSchema` and returned as `structuredContent`.
+///
+/// Same field syntax as [`tool_args!`](crate::tool_args); schemas of nested
+/// result structs are expressions, parenthesized when they are the whole
+/// field schema (`(LinkData::schema())`). Raw
+/// identifiers map to their bare name, so `r#type` is the `type` property.
+///
+/// ```ignore
+/// tool_output! {
+///     pub struct LinkData {
+///         required href: String = {"type": "string"},
+///         required text: String = {"type": "string"},
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! tool_output {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( $(#[$fmeta:meta])* $presence:ident $field:ident : $ty:ty = $schema:tt ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        $vis struct $name {
+            $( $(#[$fmeta])* pub $field: $ty, )*
+        }
+
+        impl $crate::mcp::schema::ToolOutput for $name {
+            fn schema() -> serde_json::Value {
+                $crate::mcp::schema::object_schema(vec![
+                    $( (
+                        $crate::mcp::schema::field_name(stringify!($field)),
+                        serde_json::json!($schema),
+                        $crate::tool_args!(@required $presence),
+                    ), )*
+                ])
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::tool_args! {
+        struct DemoArgs {
+            required url: String = {"type": "string", "format": "uri"},
+            optional depth: Option<u32> = {"type": "integer", "minimum": 1, "maximum": 5},
+        }
+    }
+
+    fn pointers(violations: &[Violation]) -> Vec<&str> {
+        violations.iter().map(|v| v.pointer.as_str()).collect()
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let schema = DemoArgs::schema();
+        assert!(validate(&schema, &json!({"url": "https://example.com", "depth": 2})).is_empty());
+
+        let args: DemoArgs = serde_json::from_value(json!({"url": "https://example.com"})).unwrap();
+        assert_eq!(args.url, "https://example.com");
+        assert!(args.depth.is_none());
+    }
+
+    #[test]
+    fn test_every_violation_is_reported() {
+        let schema = DemoArgs::schema();
+        let violations = validate(&schema, &json!({"depth": 9}));
+        assert_eq!(pointers(&violations), vec!["/url", "/depth"]);
+
+        let violations = validate(&schema, &json!({"url": "not a url", "depth": "deep"}));
+        assert_eq!(pointers(&violations), vec!["/depth", "/url"]);
+        assert!(violations[0].message.contains("expected integer"));
+    }
+
+    #[test]
+    fn test_nested_pointers() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "selectors": {"type": "array", "items": {"type": "string"}},
+                "mode": {"type": "string", "enum": ["fast", "full"]},
+                "a/b": {"type": "boolean"}
+            },
+            "additionalProperties": false
+        });
+        let violations = validate(
+            &schema,
+            &json!({"selectors": ["h1", 3], "mode": "slow", "a/b": 1, "extra": true}),
+        );
+        let mut found = pointers(&violations);
+        found.sort();
+        assert_eq!(found, vec!["/a~1b", "/extra", "/mode", "/selectors/1"]);
+    }
+
+    #[test]
+    fn test_root_type_mismatch() {
+        let violations = validate(&json!({"type": "object"}), &json!([1]));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].to_string(), "(root): expected object, got array");
+    }
+
+    crate::tool_output! {
+        struct DemoField {
+            required name: String = {"type": "string"},
+            required r#type: String = {"type": "string"},
+        }
+    }
+
+    crate::tool_output! {
+        struct DemoOutput {
+            required fields: Vec<DemoField> = {"type": "array", "items": DemoField::schema()},
+            optional note: Option<String> = {"type": "string"},
+        }
+    }
+
+    #[test]
+    fn test_output_schema_matches_serialized_struct() {
+        let output = DemoOutput {
+            fields: vec![DemoField {
+                name: "q".to_string(),
+                r#type: "text".to_string(),
+            }],
+            note: Some("ok".to_string()),
+        };
+        let value = serde_json::to_value(&output).unwrap();
+        assert_eq!(value["fields"][0]["type"], "text");
+        assert!(validate(&DemoOutput::schema(), &value).is_empty());
+        assert_eq!(DemoOutput::schema()["properties"]["fields"]["items"]["required"], json!(["name", "type"]));
+
+        let either = json!({"anyOf": [DemoField::schema(), {"type": "string"}]});
+        assert!(validate(&either, &json!("plain")).is_empty());
+        assert_eq!(validate(&either, &json!(3)).len(), 1);
+    }
+}
@@ -0,0 +1,530 @@
+//! MCP method dispatcher shared by every transport
+
+use serde_json::{json, Value};
+use std::time::Instant;
+
+use super::audit::{AuditEntry, AuditStatus};
+use super::context::ServerContext;
+use super::jsonrpc::{self, Request, RpcError};
+use super::metrics;
+use super::monitor;
+use super::progress::{CallContext, InFlightRequests, Peer};
+use super::registry::ToolRegistry;
+use super::resources;
+
+pub const PROTOCOL_VERSION: &str = "2025-11-28";
+pub const SERVER_NAME: &str = "BrowserMCP-PRO";
+pub const SERVER_VERSION: &str = "3.0.0";
+
+/// Tool a client must be allowed to read or subscribe to page resources,
+/// which fetch the page the same way
+const PAGE_TOOL: &str = "web_scrape";
+
+pub struct McpServer {
+    registry: ToolRegistry,
+    context: ServerContext,
+    in_flight: InFlightRequests,
+}
+
+impl McpServer {
+    pub fn new(registry: ToolRegistry, context: ServerContext) -> Self {
+        Self {
+            registry,
+            context,
+            in_flight: InFlightRequests::new(),
+        }
+    }
+
+    pub fn registry(&self) -> &ToolRegistry {
+        &self.registry
+    }
+
+    pub fn context(&self) -> &ServerContext {
+        &self.context
+    }
+
+    /// Requests currently running (tracked for `notifications/cancelled`)
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Ask every running request to stop, as `notifications/cancelled` would;
+    /// returns how many were running
+    pub fn cancel_all(&self) -> usize {
+        self.in_flight.cancel_all()
+    }
+
+    /// Handle a decoded frame (single message or batch). `None` means
+    /// nothing must be written back.
+    pub async fn handle_message(&self, message: Value) -> Option<Value> {
+        self.handle_message_from(message, &Peer::detached()).await
+    }
+
+    /// Like [`handle_message`](Self::handle_message), for a client that can
+    /// receive progress notifications and cancel its requests
+    pub async fn handle_message_from(&self, message: Value, peer: &Peer) -> Option<Value> {
+        jsonrpc::handle_message(message, |request| self.dispatch(request, peer)).await
+    }
+
+    pub async fn dispatch(&self, request: Request, peer: &Peer) -> Result<Value, RpcError> {
+        let result = match request.method.as_str() {
+            // ==================== INITIALIZE ====================
+            "initialize" => json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {
+                    "tools": {},
+                    "resources": {"subscribe": true},
+                    "prompts": {"listChanged": true}
+                },
+                "serverInfo": {
+                    "name": SERVER_NAME,
+                    "version": SERVER_VERSION
+                }
+            }),
+
+            "notifications/initialized" => Value::Null,
+
+            "notifications/cancelled" => {
+                if let Some(id) = request.params.as_ref().and_then(|p| p.get("requestId")) {
+                    if self.in_flight.cancel(peer, id) {
+                        tracing::info!("Request {} cancelled by client", id);
+                    }
+                }
+                Value::Null
+            }
+            "ping" => json!({}),
+
+            // ==================== TOOLS ====================
+            "tools/list" => self.registry.list_filtered(|name| peer.allows_tool(name)),
+
+            "tools/call" => {
+                let params = request.params.unwrap_or_else(|| json!({}));
+                let tool_name = params
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .ok_or_else(|| RpcError::invalid_params("tools/call requires a string `name`"))?;
+                let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+                let started = Instant::now();
+                let outcome = self.call_tool(tool_name, &params, args.clone(), request.id.as_ref(), peer).await;
+                let elapsed = started.elapsed();
+                let label = match self.registry.get(tool_name) {
+                    Some(_) => tool_name,
+                    None => metrics::UNKNOWN_TOOL,
+                };
+                metrics::global().record_tool_call(label, AuditStatus::of(&outcome), elapsed);
+                if let Some(audit) = self.context.audit() {
+                    let entry = AuditEntry::new(Some(peer.id()), peer.client(), tool_name, &args, elapsed, &outcome);
+                    if let Err(e) = audit.append(&entry) {
+                        tracing::error!("Could not write audit entry for {}: {}", tool_name, e);
+                    }
+                }
+                outcome?
+            }
+
+            // ==================== RESOURCES ====================
+            "resources/list" => resources::list(&self.context, peer)?,
+
+            "resources/templates/list" => resources::templates(),
+
+            "resources/read" | "resources/subscribe" => {
+                let uri = resource_uri(&request)?;
+                match monitor::page_url(uri) {
+                    Some(url) => self.page_resource(&request.method, uri, url, peer).await?,
+                    None if request.method == "resources/read" => resources::read(&self.context, uri, peer).await?,
+                    None => resources::subscribe(&self.context, uri, peer).await?,
+                }
+            }
+
+            "resources/unsubscribe" => resources::unsubscribe(&self.context, resource_uri(&request)?, peer)?,
+
+            // ==================== PROMPTS ====================
+            "prompts/list" => self.context.prompts().list(),
+
+            "prompts/get" => {
+                let params = request.params.unwrap_or_else(|| json!({}));
+                let name = params
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .ok_or_else(|| RpcError::invalid_params("prompts/get requires a string `name`"))?;
+                let arguments = match params.get("arguments") {
+                    None | Some(Value::Null) => serde_json::Map::new(),
+                    Some(Value::Object(arguments)) => arguments.clone(),
+                    Some(_) => return Err(RpcError::invalid_params("`arguments` must be an object")),
+                };
+
+                self.context.prompts().get(name, &arguments)?
+            }
+
+            unknown => return Err(RpcError::method_not_found(unknown)),
+        };
+
+        Ok(result)
+    }
+
+    async fn call_tool(
+        &self,
+        tool_name: &str,
+        params: &Value,
+        args: Value,
+        id: Option<&Value>,
+        peer: &Peer,
+    ) -> Result<Value, RpcError> {
+        if !peer.allows_tool(tool_name) {
+            return Err(RpcError::invalid_params(format!("Tool not allowed for this client: {}", tool_name)));
+        }
+        if !args.is_object() {
+            return Err(RpcError::invalid_params("`arguments` must be an object"));
+        }
+
+        let progress_token = params.get("_meta").and_then(|m| m.get("progressToken")).cloned();
+        let call = CallContext::new(peer.clone(), progress_token);
+        let _in_flight = id.map(|id| self.in_flight.track(peer, id, call.cancellation().clone()));
+
+        self.registry.call_with_context(tool_name, args, &call).await
+    }
+
+    /// Reads and subscribes of `browser://page/{url}` fetch `url`, so they
+    /// are held to the `web_scrape` allowlist entry and audited like a call
+    async fn page_resource(&self, method: &str, uri: &str, url: &str, peer: &Peer) -> Result<Value, RpcError> {
+        let started = Instant::now();
+        let outcome = if !peer.allows_tool(PAGE_TOOL) {
+            Err(RpcError::invalid_params(format!(
+                "{} of pages needs the {} tool, not allowed for this client",
+                method, PAGE_TOOL
+            )))
+        } else if method == "resources/read" {
+            resources::read(&self.context, uri, peer).await
+        } else {
+            resources::subscribe(&self.context, uri, peer).await
+        };
+        if let Some(audit) = self.context.audit() {
+            let arguments = json!({"url": url});
+            let entry = AuditEntry::new(Some(peer.id()), peer.client(), method, &arguments, started.elapsed(), &outcome);
+            if let Err(e) = audit.append(&entry) {
+                tracing::error!("Could not write audit entry for {} of {}: {}", method, url, e);
+            }
+        }
+        outcome
+    }
+}
+
+/// `params.uri` of the resources/* methods
+fn resource_uri(request: &Request) -> Result<&str, RpcError> {
+    request
+        .params
+        .as_ref()
+        .and_then(|p| p.get("uri"))
+        .and_then(|u| u.as_str())
+        .ok_or_else(|| RpcError::invalid_params(format!("{} requires a string `uri`", request.method)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::registry::{FnTool, ToolError};
+
+    fn server() -> McpServer {
+        let dir = std::env::temp_dir().join(format!("browsermcp_server_{}", uuid::Uuid::new_v4()));
+        let context = ServerContext::new(dir.to_str().unwrap()).unwrap();
+        let mut registry = ToolRegistry::new();
+        registry.register(FnTool::new(
+            "echo",
+            "Echo arguments",
+            json!({"type": "object"}),
+            |args| async move { Ok(args) },
+        ));
+        registry.register(FnTool::new(
+            "broken",
+            "Always fails",
+            json!({"type": "object"}),
+            |_| async { Err(ToolError::Execution("nope".to_string())) },
+        ));
+        McpServer::new(registry, context)
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_comes_from_registry() {
+        let response = server()
+            .handle_message(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+            .await
+            .unwrap();
+        let tools = response["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0]["name"], "echo");
+    }
+
+    #[tokio::test]
+    async fn test_tools_call_dispatch() {
+        let server = server();
+        let ok = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "echo", "arguments": {"x": 1}}}))
+            .await
+            .unwrap();
+        assert_eq!(ok["result"]["isError"], false);
+
+        let failed = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "broken"}}))
+            .await
+            .unwrap();
+        assert_eq!(failed["result"]["isError"], true);
+
+        let unknown = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "nope"}}))
+            .await
+            .unwrap();
+        assert_eq!(unknown["error"]["code"], jsonrpc::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tools_share_one_metrics_label() {
+        let server = server();
+        let name = format!("nope_{}", uuid::Uuid::new_v4().simple());
+        let before = metrics::global().tool_calls(metrics::UNKNOWN_TOOL, AuditStatus::Rejected);
+        server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name}}))
+            .await
+            .unwrap();
+
+        assert_eq!(metrics::global().tool_calls(&name, AuditStatus::Rejected), 0);
+        assert!(metrics::global().tool_calls(metrics::UNKNOWN_TOOL, AuditStatus::Rejected) > before);
+        assert!(!metrics::global().render(&[]).contains(&name));
+    }
+
+    #[tokio::test]
+    async fn test_resources_read_and_templates() {
+        let server = server();
+        let templates = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 1, "method": "resources/templates/list"}))
+            .await
+            .unwrap();
+        assert_eq!(templates["result"]["resourceTemplates"][0]["uriTemplate"], "browser://analysis/{id}");
+
+        let docs = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {"uri": "browser://docs/usage"}}))
+            .await
+            .unwrap();
+        assert_eq!(docs["result"]["contents"][0]["uri"], "browser://docs/usage");
+
+        let missing = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": {"uri": "browser://analysis/none"}}))
+            .await
+            .unwrap();
+        assert_eq!(missing["error"]["code"], resources::RESOURCE_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_resources_subscribe() {
+        let dir = std::env::temp_dir().join(format!("browsermcp_server_{}", uuid::Uuid::new_v4()));
+        let context = ServerContext::new(dir.to_str().unwrap())
+            .unwrap()
+            .with_page_fetcher(std::sync::Arc::new(|url: String| {
+                Box::pin(async move { Ok(json!({"url": url, "title": "Prices"})) })
+            }));
+        let server = McpServer::new(ToolRegistry::new(), context);
+        let session = Peer::new("session-1", |_| {});
+
+        let uri = "browser://page/https://example.com/prices";
+        let subscribe = json!({"jsonrpc": "2.0", "id": 1, "method": "resources/subscribe", "params": {"uri": uri}});
+        // Nobody could be told about changes
+        let detached = server.handle_message(subscribe.clone()).await.unwrap();
+        assert_eq!(detached["error"]["code"], jsonrpc::INVALID_PARAMS);
+
+        let subscribed = server.handle_message_from(subscribe, &session).await.unwrap();
+        assert_eq!(subscribed["result"], json!({}));
+        assert_eq!(server.context().pages().subscribed_by("session-1"), vec![uri]);
+
+        // Each client lists only the pages it subscribed to
+        let list = json!({"jsonrpc": "2.0", "id": 4, "method": "resources/list"});
+        let listed = server.handle_message_from(list.clone(), &session).await.unwrap();
+        assert!(listed["result"]["resources"].as_array().unwrap().iter().any(|r| r["uri"] == uri));
+        let other = server.handle_message_from(list, &Peer::new("session-2", |_| {})).await.unwrap();
+        assert!(!other["result"]["resources"].as_array().unwrap().iter().any(|r| r["uri"] == uri));
+
+        let snapshot = server
+            .handle_message(json!({"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {"uri": uri}}))
+            .await
+            .unwrap();
+        assert!(snapshot["result"]["contents"][0]["text"].as_str().unwrap().contains("Prices"));
+
+        let rejected = server
+            .handle_message_from(
+                json!({"jsonrpc": "2.0", "id": 3, "method": "resources/subscribe", "params": {"uri": "browser://docs/usage"}}),
+                &session,
+            )
+            .await
+            .unwrap();
+        assert_eq!(rejected["error"]["code"], jsonrpc::INVALID_PARAMS);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_page_resources_need_web_scrape() {
+        let dir = std::env::temp_dir().join(format!("browsermcp_server_{}", uuid::Uuid::new_v4()));
+        let log = crate::mcp::audit::AuditLog::open(dir.join("audit"), 1024 * 1024).unwrap();
+        let context = ServerContext::new(dir.join("db").to_str().unwrap())
+            .unwrap()
+            .with_audit_log(log)
+            .with_page_fetcher(std::sync::Arc::new(|url: String| {
+                Box::pin(async move { Ok(json!({"url": url, "title": "Prices"})) })
+            }));
+        let server = McpServer::new(ToolRegistry::new(), context);
+        let peer = |tool: &str| {
+            let allowed: std::collections::HashSet<String> = [tool.to_string()].into();
+            Peer::new("session-1", |_| {})
+                .with_client(Some("ci".to_string()))
+                .with_tool_allowlist(Some(std::sync::Arc::new(allowed)))
+        };
+        let read = |id: u64| {
+            json!({"jsonrpc": "2.0", "id": id, "method": "resources/read",
+                "params": {"uri": "browser://page/https://example.com/prices"}})
+        };
+
+        let denied = server.handle_message_from(read(1), &peer("audit_query")).await.unwrap();
+        assert_eq!(denied["error"]["code"], jsonrpc::INVALID_PARAMS);
+        assert!(denied["error"]["message"].as_str().unwrap().contains("web_scrape"));
+        let subscribe = json!({"jsonrpc": "2.0", "id": 2, "method": "resources/subscribe",
+            "params": {"uri": "browser://page/https://example.com/prices"}});
+        let denied = server.handle_message_from(subscribe, &peer("audit_query")).await.unwrap();
+        assert_eq!(denied["error"]["code"], jsonrpc::INVALID_PARAMS);
+        assert!(server.context().pages().subscriptions().is_empty());
+
+        let allowed = server.handle_message_from(read(3), &peer("web_scrape")).await.unwrap();
+        assert!(allowed["result"]["contents"][0]["text"].as_str().unwrap().contains("Prices"));
+
+        let query = crate::mcp::audit::AuditQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        let (entries, _) = server.context().audit().unwrap().query(&query).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|e| e.hosts == vec!["example.com"] && e.client.as_deref() == Some("ci")));
+        let statuses: Vec<_> = entries.iter().map(|e| (e.tool.as_str(), e.status)).collect();
+        assert!(statuses.contains(&("resources/read", crate::mcp::audit::AuditStatus::Ok)));
+        assert!(statuses.contains(&("resources/subscribe", crate::mcp::audit::AuditStatus::Rejected)));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    struct WaitForCancel;
+
+    #[async_trait::async_trait]
+    impl crate::mcp::McpTool for WaitForCancel {
+        fn name(&self) -> &str {
+            "wait"
+        }
+
+        fn description(&self) -> &str {
+            "Runs until cancelled"
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        async fn call(&self, _args: Value) -> crate::mcp::ToolResult {
+            Ok(Value::Null)
+        }
+
+        async fn call_with_context(&self, _args: Value, ctx: &CallContext) -> crate::mcp::ToolResult {
+            ctx.progress(1.0, None, Some("started"));
+            ctx.cancellation().cancelled().await;
+            Ok(json!({"cancelled": true}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_and_cancellation() {
+        let mut server = server();
+        server.registry.register(WaitForCancel);
+        let server = std::sync::Arc::new(server);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let peer = Peer::new("stdio", move |message| {
+            let _ = tx.send(message);
+        });
+
+        let call = {
+            let (server, peer) = (std::sync::Arc::clone(&server), peer.clone());
+            tokio::spawn(async move {
+                server
+                    .handle_message_from(
+                        json!({"jsonrpc": "2.0", "id": 9, "method": "tools/call", "params": {"name": "wait", "_meta": {"progressToken": "p1"}}}),
+                        &peer,
+                    )
+                    .await
+            })
+        };
+
+        let progress = rx.recv().await.unwrap();
+        assert_eq!(progress["method"], "notifications/progress");
+        assert_eq!(progress["params"]["progressToken"], "p1");
+
+        let ack = server
+            .handle_message_from(
+                json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 9, "reason": "user"}}),
+                &peer,
+            )
+            .await;
+        assert!(ack.is_none());
+
+        let response = call.await.unwrap().unwrap();
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("cancelled"));
+        assert!(server.in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tool_allowlist() {
+        let server = server();
+        let allowed: std::collections::HashSet<String> = ["echo".to_string()].into();
+        let peer = Peer::new("key", |_| {}).with_tool_allowlist(Some(std::sync::Arc::new(allowed)));
+
+        let listed = server
+            .handle_message_from(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}), &peer)
+            .await
+            .unwrap();
+        assert_eq!(listed["result"]["tools"].as_array().unwrap().len(), 1);
+
+        let denied = server
+            .handle_message_from(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "broken"}}),
+                &peer,
+            )
+            .await
+            .unwrap();
+        assert_eq!(denied["error"]["code"], jsonrpc::INVALID_PARAMS);
+        assert!(denied["error"]["message"].as_str().unwrap().contains("not allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_audited() {
+        let dir = std::env::temp_dir().join(format!("browsermcp_server_{}", uuid::Uuid::new_v4()));
+        let log = crate::mcp::audit::AuditLog::open(dir.join("audit"), 1024 * 1024).unwrap();
+        let context = ServerContext::new(dir.join("db").to_str().unwrap()).unwrap().with_audit_log(log);
+        let mut registry = ToolRegistry::new();
+        registry.register(FnTool::new("echo", "Echo", json!({"type": "object"}), |args| async move { Ok(args) }));
+        let server = McpServer::new(registry, context);
+        let peer = Peer::new("session-1", |_| {}).with_client(Some("ci".to_string()));
+
+        let call = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "echo", "arguments": {"url": "https://example.com", "password": "hunter2"}}});
+        server.handle_message_from(call, &peer).await.unwrap();
+        let unknown = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "nope"}});
+        server.handle_message_from(unknown, &peer).await.unwrap();
+
+        let query = crate::mcp::audit::AuditQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        let (entries, _) = server.context().audit().unwrap().query(&query).unwrap();
+        assert_eq!(entries.len(), 2);
+        let echo = entries.iter().find(|e| e.tool == "echo").unwrap();
+        assert_eq!(echo.session.as_deref(), Some("session-1"));
+        assert_eq!(echo.client.as_deref(), Some("ci"));
+        assert_eq!(echo.hosts, vec!["example.com"]);
+        assert_eq!(echo.arguments["password"], "[REDACTED]");
+        assert!(echo.result_bytes > 0);
+        let unknown = entries.iter().find(|e| e.tool == "nope").unwrap();
+        assert_eq!(unknown.status, crate::mcp::audit::AuditStatus::Rejected);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
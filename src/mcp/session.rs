@@ -0,0 +1,152 @@
+//! Streamable HTTP sessions (`Mcp-Session-Id`)
+//!
+//! A session is created by `initialize` and ends with `DELETE /mcp` or after
+//! being idle too long. Each session has its own outbound channel, streamed
+//! to the client on `GET /mcp`, for messages aimed at that client only
+//! (e.g. progress of a long scrape).
+
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// JSON-RPC error code sent with the 404 for an unknown or ended session
+pub const SESSION_NOT_FOUND: i64 = -32001;
+
+/// Session-directed messages buffered per session
+const SESSION_BUFFER: usize = 64;
+
+pub struct Session {
+    pub id: String,
+    /// API key name that created the session; only it may use the session
+    pub owner: Option<String>,
+    pub created_at: String,
+    last_seen: RwLock<Instant>,
+    outbound: broadcast::Sender<Value>,
+}
+
+impl Session {
+    fn new(owner: Option<String>) -> Self {
+        let (outbound, _) = broadcast::channel(SESSION_BUFFER);
+        Self {
+            // Visible ASCII only, as the transport spec requires
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            owner,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            last_seen: RwLock::new(Instant::now()),
+            outbound,
+        }
+    }
+
+    /// Send a message to this session's stream; false if nobody listens
+    pub fn send(&self, message: Value) -> bool {
+        self.outbound.send(message).is_ok()
+    }
+
+    pub fn stream(&self) -> broadcast::Receiver<Value> {
+        self.outbound.subscribe()
+    }
+
+    pub fn touch(&self) {
+        *self.last_seen.write() = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_seen.read().elapsed()
+    }
+}
+
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: RwLock<HashMap<String, Arc<Session>>>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create(&self, owner: Option<String>) -> Arc<Session> {
+        let session = Arc::new(Session::new(owner));
+        self.sessions.write().insert(session.id.clone(), Arc::clone(&session));
+        session
+    }
+
+    /// Look up a session of `owner` and mark it as used; another owner's
+    /// session is reported as missing
+    pub fn get(&self, id: &str, owner: Option<&str>) -> Option<Arc<Session>> {
+        let session = self.sessions.read().get(id).cloned()?;
+        if session.owner.as_deref() != owner {
+            return None;
+        }
+        session.touch();
+        Some(session)
+    }
+
+    /// End a session; its streams close once the last handle is dropped
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.write().remove(id).is_some()
+    }
+
+    /// Drop sessions idle for longer than `max_idle`, returning their ids
+    pub fn prune(&self, max_idle: Duration) -> Vec<String> {
+        let mut sessions = self.sessions.write();
+        let idle: Vec<String> = sessions
+            .values()
+            .filter(|session| session.idle_for() > max_idle)
+            .map(|session| session.id.clone())
+            .collect();
+        for id in &idle {
+            sessions.remove(id);
+        }
+        idle
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.read().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_session_lifecycle() {
+        let store = SessionStore::new();
+        let session = store.create(Some("ci".to_string()));
+        assert!(session.id.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert!(store.get(&session.id, Some("ci")).is_some());
+        assert!(store.get(&session.id, Some("other")).is_none());
+        assert!(store.get(&session.id, None).is_none());
+
+        assert!(store.remove(&session.id));
+        assert!(store.get(&session.id, Some("ci")).is_none());
+        assert!(!store.remove(&session.id));
+    }
+
+    #[test]
+    fn test_session_stream_and_prune() {
+        let store = SessionStore::new();
+        let session = store.create(None);
+
+        assert!(!session.send(json!({"n": 0})));
+        let mut stream = session.stream();
+        assert!(session.send(json!({"n": 1})));
+        assert_eq!(stream.try_recv().unwrap()["n"], 1);
+
+        assert!(store.prune(Duration::from_secs(60)).is_empty());
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(store.prune(Duration::ZERO), vec![session.id.clone()]);
+        assert!(store.is_empty());
+    }
+}
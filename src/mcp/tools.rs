@@ -0,0 +1,696 @@
+//! Built-in BrowserMCP tools
+//!
+//! Every tool here calls library code; tools without an implementation are
+//! not registered. Each one declares its annotations and an `outputSchema`
+//! generated from the result struct it returns.
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::audit::{AuditEntry, AuditQuery};
+use super::context::ServerContext;
+use super::progress::CallContext;
+use super::registry::{McpTool, ToolAnnotations, ToolError, ToolRegistry, ToolResult, TypedTool};
+use super::schema::{self, ToolArgs, ToolOutput};
+use crate::intelligent_content_extractor::{IntelligentContentExtractor, ValuableContent};
+use crate::models::ScrapedData;
+use crate::runtime::AsyncMutex;
+use crate::scraper_marketing;
+use crate::vulnerability_scanner::{VulnerabilityScan, VulnerabilityScanner};
+use crate::{tool_args, tool_output};
+use crate::workflow_orchestrator::{Schedule, WorkflowOrchestrator, WorkflowStep, WorkflowTask};
+use std::time::Instant;
+use crate::StealthMode;
+
+tool_args! {
+    /// Arguments of `web_scrape`
+    pub struct WebScrapeArgs {
+        required url: String = {"type": "string", "format": "uri"},
+        optional selectors: Option<Vec<String>> = {"type": "array", "items": {"type": "string"}},
+    }
+}
+
+tool_args! {
+    /// Arguments of `web_scrape_batch`
+    pub struct WebScrapeBatchArgs {
+        required urls: Vec<String> = {
+            "type": "array",
+            "items": {"type": "string", "format": "uri"},
+            "minItems": 1,
+            "maxItems": 100
+        },
+        optional selectors: Option<Vec<String>> = {"type": "array", "items": {"type": "string"}},
+    }
+}
+
+tool_args! {
+    /// Arguments of `dom_extraction`
+    pub struct DomExtractionArgs {
+        required html: String = {"type": "string"},
+        required selectors: HashMap<String, String> = {
+            "type": "object",
+            "description": "Field name -> CSS selector",
+            "additionalProperties": {"type": "string"}
+        },
+    }
+}
+
+tool_args! {
+    /// Arguments of `vulnerability_scanner` and `intelligent_content_extractor`:
+    /// the page is fetched from `url` unless `html` is given
+    pub struct PageArgs {
+        required url: String = {"type": "string", "format": "uri"},
+        optional html: Option<String> = {"type": "string"},
+    }
+}
+
+tool_args! {
+    /// Arguments of `audit_query`
+    pub struct AuditQueryArgs {
+        optional since: Option<String> = {"type": "string", "format": "date-time", "description": "RFC 3339, inclusive"},
+        optional until: Option<String> = {"type": "string", "format": "date-time", "description": "RFC 3339, inclusive"},
+        optional tool: Option<String> = {"type": "string"},
+        optional client: Option<String> = {"type": "string", "description": "API key name"},
+        optional limit: Option<usize> = {"type": "integer", "minimum": 1, "maximum": 1000},
+    }
+}
+
+tool_args! {
+    /// Arguments of `workflow_orchestrator`
+    pub struct WorkflowArgs {
+        required action: String = {"type": "string", "enum": ["create", "execute", "list", "delete"]},
+        optional workflow_id: Option<String> = {"type": "string"},
+        optional workflow: Option<Value> = {
+            "type": "object",
+            "description": "Workflow definition for `create`",
+            "properties": {
+                "name": {"type": "string"},
+                "description": {"type": "string"},
+                "steps": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "action": {"type": "string", "enum": ["scrape", "analyze", "store", "alert"]},
+                            "on_error": {"type": "string", "enum": ["continue", "stop"]}
+                        },
+                        "required": ["action"]
+                    }
+                },
+                "schedule": {"type": "string", "enum": ["hourly", "daily", "weekly", "once"]}
+            },
+            "required": ["steps"]
+        },
+    }
+}
+
+tool_output! {
+    /// Result of `web_scrape_batch`: a [`ScrapedData`] or an error per URL
+    pub struct WebScrapeBatchOutput {
+        required results: Vec<Value> = {
+            "type": "array",
+            "items": {"anyOf": [
+                ScrapedData::schema(),
+                {
+                    "type": "object",
+                    "properties": {"url": {"type": "string"}, "error": {"type": "string"}},
+                    "required": ["url", "error"]
+                }
+            ]}
+        },
+        required completed: usize = {"type": "integer", "minimum": 0},
+        required total: usize = {"type": "integer", "minimum": 1},
+        required cancelled: bool = {"type": "boolean"},
+    }
+}
+
+tool_output! {
+    /// Result of `dom_extraction`
+    pub struct DomExtractionOutput {
+        required fields: HashMap<String, Vec<String>> = {
+            "type": "object",
+            "description": "Field name -> matched elements",
+            "additionalProperties": {"type": "array", "items": {"type": "string"}}
+        },
+        required total_elements_extracted: usize = {"type": "integer", "minimum": 0},
+    }
+}
+
+tool_output! {
+    /// Result of `audit_query`
+    pub struct AuditQueryOutput {
+        required entries: Vec<AuditEntry> = {"type": "array", "items": AuditEntry::schema()},
+        required count: usize = {"type": "integer", "minimum": 0},
+        required truncated: bool = {"type": "boolean"},
+    }
+}
+
+/// `O`'s schema plus the `analysis_uri` added by [`record`]
+fn recorded<O: ToolOutput>() -> Value {
+    let mut schema = O::schema();
+    schema["properties"]["analysis_uri"] = json!({
+        "type": "string",
+        "description": "resources/read URI of the stored analysis"
+    });
+    schema
+}
+
+/// JSON value of a result struct
+fn to_output<O: ToolOutput>(output: O) -> ToolResult {
+    serde_json::to_value(output).map_err(|e| ToolError::Execution(e.to_string()))
+}
+
+/// Registry with every built-in tool. Page analyses are stored in the
+/// context and returned with an `analysis_uri` for `resources/read`.
+pub fn builtin_registry(ctx: &ServerContext) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+
+    let store = ctx.clone();
+    registry.register(TypedTool::with_context(
+        "web_scrape",
+        "Full web scraping with email/phone extraction",
+        move |args: WebScrapeArgs, call: CallContext| {
+            let store = store.clone();
+            async move {
+                let started = Instant::now();
+                let selectors = args
+                    .selectors
+                    .unwrap_or_else(|| vec!["title".to_string(), "h1".to_string(), "p".to_string()]);
+
+                // Llamar función real de scraping
+                let output = scraper_marketing::scrape_url_full(&args.url, &selectors)
+                    .await
+                    .map_err(|e| ToolError::Execution(format!("Scrape of {} failed: {}", args.url, e)))?;
+                Ok(record(&store, &call, "scrape", "web_scrape", &args.url, output, started))
+            }
+        },
+    )
+    .with_output_schema(recorded::<ScrapedData>())
+    .with_annotations(ToolAnnotations::read_only().open_world()));
+
+    registry.register(BatchScrapeTool);
+
+    registry.register(TypedTool::new(
+        "dom_extraction",
+        "Extract data from DOM with CSS selectors",
+        |args: DomExtractionArgs| async move {
+            let fields = scraper_marketing::extract_selectors(&args.html, &args.selectors)
+                .map_err(ToolError::InvalidArguments)?;
+            let total_elements_extracted: usize = fields.values().map(|v| v.len()).sum();
+            to_output(DomExtractionOutput {
+                fields,
+                total_elements_extracted,
+            })
+        },
+    )
+    .with_output::<DomExtractionOutput>()
+    .with_annotations(ToolAnnotations::read_only()));
+
+    let store = ctx.clone();
+    registry.register(TypedTool::with_context(
+        "vulnerability_scanner",
+        "OWASP Top 10 vulnerability detection",
+        move |args: PageArgs, call: CallContext| {
+            let store = store.clone();
+            async move {
+                let started = Instant::now();
+                let html = page_html(&args).await?;
+                let output = VulnerabilityScanner::new().scan_html(&args.url, &html).to_json();
+                Ok(record(&store, &call, "vulnerability", "vulnerability_scanner", &args.url, output, started))
+            }
+        },
+    )
+    .with_output_schema(recorded::<VulnerabilityScan>())
+    .with_annotations(ToolAnnotations::read_only().open_world()));
+
+    let store = ctx.clone();
+    registry.register(TypedTool::with_context(
+        "intelligent_content_extractor",
+        "Extract valuable data from HTML (ignore noise)",
+        move |args: PageArgs, call: CallContext| {
+            let store = store.clone();
+            async move {
+                let started = Instant::now();
+                let html = page_html(&args).await?;
+                let content = IntelligentContentExtractor::new().extract_valuable_content(&args.url, &html);
+                let output = serde_json::to_value(content).map_err(|e| ToolError::Execution(e.to_string()))?;
+                Ok(record(&store, &call, "content", "intelligent_content_extractor", &args.url, output, started))
+            }
+        },
+    )
+    .with_output_schema(recorded::<ValuableContent>())
+    .with_annotations(ToolAnnotations::read_only().open_world()));
+
+    registry.register(WorkflowTool::new(ctx.workflows()));
+
+    registry.register(TypedTool::new(
+        "get_stealth_headers",
+        "Get anti-detection headers",
+        |_args: NoArgs| async move { Ok(json!(StealthMode::fake_headers())) },
+    )
+    .with_output_schema(json!({
+        "type": "object",
+        "description": "Header name -> value",
+        "additionalProperties": {"type": "string"}
+    }))
+    .with_annotations(ToolAnnotations::read_only()));
+
+    if let Some(audit) = ctx.audit() {
+        registry.register(TypedTool::new(
+            "audit_query",
+            "Query the tool invocation audit log by time range, tool and client",
+            move |args: AuditQueryArgs| {
+                let audit = Arc::clone(&audit);
+                async move {
+                    let query = AuditQuery {
+                        since: args.since.as_deref().map(parse_time).transpose()?,
+                        until: args.until.as_deref().map(parse_time).transpose()?,
+                        tool: args.tool,
+                        client: args.client,
+                        limit: args.limit.unwrap_or(100),
+                    };
+                    let (entries, truncated) = audit.query(&query).map_err(ToolError::Execution)?;
+                    to_output(AuditQueryOutput {
+                        count: entries.len(),
+                        entries,
+                        truncated,
+                    })
+                }
+            },
+        )
+        .with_output::<AuditQueryOutput>()
+        .with_annotations(ToolAnnotations::read_only()));
+    }
+
+    registry
+}
+
+fn parse_time(value: &str) -> Result<chrono::DateTime<chrono::Utc>, ToolError> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|at| at.with_timezone(&chrono::Utc))
+        .map_err(|e| ToolError::InvalidArguments(format!("{} is not an RFC 3339 time: {}", value, e)))
+}
+
+/// Store `output` as an analysis of the caller's and add its
+/// `analysis_uri`. A storage failure is logged and does not fail the tool call.
+fn record(
+    ctx: &ServerContext,
+    call: &CallContext,
+    analysis_type: &str,
+    tool: &str,
+    query: &str,
+    mut output: Value,
+    started: Instant,
+) -> Value {
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match ctx.record_analysis(call.peer().client(), analysis_type, tool, query, &output, duration_ms) {
+        Ok(uri) => {
+            if let Some(fields) = output.as_object_mut() {
+                fields.insert("analysis_uri".to_string(), json!(uri));
+            }
+        }
+        Err(e) => tracing::warn!("Could not store {} analysis of {}: {}", tool, query, e),
+    }
+    output
+}
+
+tool_args! {
+    /// Tools without arguments
+    pub struct NoArgs {}
+}
+
+/// HTML given inline, or fetched from the URL
+async fn page_html(args: &PageArgs) -> Result<String, ToolError> {
+    match &args.html {
+        Some(html) => Ok(html.clone()),
+        None => scraper_marketing::fetch_html(&args.url)
+            .await
+            .map(|(_, html)| html)
+            .map_err(|e| ToolError::Execution(format!("Fetch of {} failed: {}", args.url, e))),
+    }
+}
+
+/// `web_scrape_batch`: reports `notifications/progress` per finished URL and
+/// returns the partial results when the request is cancelled
+pub struct BatchScrapeTool;
+
+#[async_trait]
+impl McpTool for BatchScrapeTool {
+    fn name(&self) -> &str {
+        "web_scrape_batch"
+    }
+
+    fn description(&self) -> &str {
+        "Scrape several URLs in parallel with progress and cancellation"
+    }
+
+    fn input_schema(&self) -> Value {
+        WebScrapeBatchArgs::schema()
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(WebScrapeBatchOutput::schema())
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::read_only().open_world()
+    }
+
+    async fn call(&self, args: Value) -> ToolResult {
+        self.call_with_context(args, &CallContext::detached()).await
+    }
+
+    async fn call_with_context(&self, args: Value, ctx: &CallContext) -> ToolResult {
+        let args: WebScrapeBatchArgs = serde_json::from_value(args)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        let selectors = args
+            .selectors
+            .unwrap_or_else(|| vec!["title".to_string(), "h1".to_string(), "p".to_string()]);
+        let total = args.urls.len();
+
+        let (results, cancelled) =
+            scraper_marketing::scrape_batch_with(args.urls, selectors, ctx.cancellation(), |done, total, url| {
+                ctx.progress(done as f64, Some(total as f64), Some(url))
+            })
+            .await;
+
+        to_output(WebScrapeBatchOutput {
+            completed: results.len(),
+            results,
+            total,
+            cancelled,
+        })
+    }
+}
+
+/// `workflow_orchestrator`: workflows live for the lifetime of the server
+pub struct WorkflowTool {
+    orchestrator: Arc<AsyncMutex<WorkflowOrchestrator>>,
+}
+
+impl WorkflowTool {
+    pub fn new(orchestrator: Arc<AsyncMutex<WorkflowOrchestrator>>) -> Self {
+        Self { orchestrator }
+    }
+
+    fn build_task(id: String, definition: &Value) -> Result<WorkflowTask, ToolError> {
+        let steps = definition
+            .get("steps")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| ToolError::InvalidArguments("workflow.steps is required".to_string()))?
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                let mut params: Map<String, Value> = step.as_object().cloned().unwrap_or_default();
+                let action = params.remove("action").and_then(|v| v.as_str().map(String::from));
+                let on_error = params
+                    .remove("on_error")
+                    .and_then(|v| v.as_str().map(String::from))
+                    .unwrap_or_else(|| "continue".to_string());
+                // Nested `params` and inline fields are both accepted
+                if let Some(Value::Object(nested)) = params.remove("params") {
+                    params.extend(nested);
+                }
+                WorkflowStep {
+                    step_id: format!("step{}", i + 1),
+                    action: action.unwrap_or_default(),
+                    params: params.into_iter().collect(),
+                    on_error,
+                }
+            })
+            .collect();
+
+        let text = |key: &str| definition.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        Ok(WorkflowTask {
+            name: text("name").unwrap_or_else(|| id.clone()),
+            description: text("description").unwrap_or_default(),
+            id,
+            steps,
+            schedule: text("schedule").map(|frequency| Schedule {
+                frequency,
+                time: None,
+                day_of_week: None,
+            }),
+            enabled: true,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            last_run: None,
+        })
+    }
+}
+
+#[async_trait]
+impl McpTool for WorkflowTool {
+    fn name(&self) -> &str {
+        "workflow_orchestrator"
+    }
+
+    fn description(&self) -> &str {
+        "Task scheduling + automation (scrape→analyze→store→alert)"
+    }
+
+    fn input_schema(&self) -> Value {
+        WorkflowArgs::schema()
+    }
+
+    /// One shape per action, so every field is optional
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "description": "create/delete: workflow_id, action, status (create adds steps); list: workflows; execute: the execution record",
+            "properties": {
+                "workflow_id": {"type": "string"},
+                "action": {"type": "string", "enum": ["create", "delete"]},
+                "status": {"type": "string"},
+                "steps": {"type": "integer", "minimum": 0},
+                "workflows": {"type": "array", "items": {"type": "object"}},
+                "execution_id": {"type": "string"},
+                "start_time": {"type": "string"},
+                "end_time": {"type": ["string", "null"]},
+                "steps_completed": {"type": "integer", "minimum": 0},
+                "results": {"type": "array", "items": {"type": "object"}}
+            }
+        }))
+    }
+
+    /// `delete` removes workflows and `execute` scrapes
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::default()
+    }
+
+    async fn call(&self, args: Value) -> ToolResult {
+        let args: WorkflowArgs = serde_json::from_value(args)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        let require_id = || {
+            args.workflow_id.clone().ok_or_else(|| {
+                ToolError::InvalidArguments(format!("workflow_id is required for {}", args.action))
+            })
+        };
+
+        let mut orchestrator = self.orchestrator.lock().await;
+        match args.action.as_str() {
+            "create" => {
+                let definition = args.workflow.as_ref().ok_or_else(|| {
+                    ToolError::InvalidArguments("workflow is required for create".to_string())
+                })?;
+                let violations = schema::validate(&WorkflowArgs::schema()["properties"]["workflow"], definition);
+                if let Some(violation) = violations.first() {
+                    return Err(ToolError::InvalidArguments(format!("workflow{}", violation)));
+                }
+                let id = args
+                    .workflow_id
+                    .clone()
+                    .unwrap_or_else(|| format!("wf_{}", uuid::Uuid::new_v4()));
+                let task = Self::build_task(id, definition)?;
+                let steps = task.steps.len();
+                let id = orchestrator.create_workflow(task);
+                Ok(json!({"workflow_id": id, "action": "create", "steps": steps, "status": "ready"}))
+            }
+            "execute" => {
+                let id = require_id()?;
+                let workflow = orchestrator
+                    .get_workflow(&id)
+                    .ok_or_else(|| ToolError::Execution(format!("{}: Workflow not found", id)))?;
+                // Other calls and execution reads go on while it runs
+                drop(orchestrator);
+                let execution = WorkflowOrchestrator::run_workflow(&workflow).await;
+                self.orchestrator.lock().await.record_execution(execution.clone());
+                serde_json::to_value(execution).map_err(|e| ToolError::Execution(e.to_string()))
+            }
+            "list" => Ok(json!({"workflows": orchestrator.list_workflows()})),
+            "delete" => {
+                let id = require_id()?;
+                if orchestrator.delete_workflow(&id) {
+                    Ok(json!({"workflow_id": id, "action": "delete", "status": "deleted"}))
+                } else {
+                    Err(ToolError::Execution(format!("Workflow not found: {}", id)))
+                }
+            }
+            other => Err(ToolError::InvalidArguments(format!("Unknown action: {}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> (ToolRegistry, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("browsermcp_tools_{}", uuid::Uuid::new_v4()));
+        let ctx = ServerContext::new(dir.to_str().unwrap()).unwrap();
+        (builtin_registry(&ctx), dir)
+    }
+
+    fn output(result: &Value) -> Value {
+        serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap()
+    }
+
+    /// `structuredContent` of a `tool` result, checked against its `outputSchema`
+    fn structured(registry: &ToolRegistry, tool: &str, result: &Value) -> Value {
+        let output_schema = registry.get(tool).unwrap().output_schema().unwrap();
+        let content = result["structuredContent"].clone();
+        assert_eq!(schema::validate(&output_schema, &content), vec![], "{} result", tool);
+        assert_eq!(content, output(result));
+        content
+    }
+
+    #[tokio::test]
+    async fn test_only_implemented_tools_are_listed() {
+        let (registry, dir) = registry();
+        assert_eq!(
+            registry.names(),
+            vec![
+                "web_scrape",
+                "web_scrape_batch",
+                "dom_extraction",
+                "vulnerability_scanner",
+                "intelligent_content_extractor",
+                "workflow_orchestrator",
+                "get_stealth_headers"
+            ]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_every_tool_declares_annotations_and_output_schema() {
+        let (registry, dir) = registry();
+        for tool in registry.list()["tools"].as_array().unwrap() {
+            let hints = tool["annotations"].as_object().unwrap();
+            for hint in ["readOnlyHint", "destructiveHint", "idempotentHint", "openWorldHint"] {
+                assert!(hints[hint].is_boolean(), "{} {}", tool["name"], hint);
+            }
+            assert_eq!(tool["outputSchema"]["type"], "object", "{}", tool["name"]);
+        }
+
+        let hints = |name: &str| registry.get(name).unwrap().annotations();
+        assert_eq!(hints("dom_extraction"), ToolAnnotations::read_only());
+        assert!(hints("web_scrape").read_only_hint && hints("web_scrape").open_world_hint);
+        assert!(!hints("workflow_orchestrator").read_only_hint);
+        assert!(hints("workflow_orchestrator").destructive_hint);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_dom_extraction_uses_selectors() {
+        let (registry, dir) = registry();
+        let result = registry
+            .call(
+                "dom_extraction",
+                json!({"html": "<h1>Hi</h1><p class='price'>$5</p>", "selectors": {"price": ".price"}}),
+            )
+            .await
+            .unwrap();
+        let out = structured(&registry, "dom_extraction", &result);
+        assert_eq!(out["fields"]["price"], json!(["$5"]));
+        assert_eq!(out["total_elements_extracted"], 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_scanner_and_extractor_use_inline_html() {
+        let (registry, dir) = registry();
+        let scan = registry
+            .call("vulnerability_scanner", json!({"url": "http://example.com", "html": "<div onclick=\"x()\">"}))
+            .await
+            .unwrap();
+        assert_eq!(structured(&registry, "vulnerability_scanner", &scan)["vulnerabilities"][0]["cwe_id"], "CWE-79");
+        assert!(output(&scan)["analysis_uri"].as_str().unwrap().starts_with("browser://analysis/analysis_"));
+
+        let content = registry
+            .call(
+                "intelligent_content_extractor",
+                json!({"url": "http://example.com", "html": "<h1>Title</h1><article>Some article body text here.</article>"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(structured(&registry, "intelligent_content_extractor", &content)["title"], "Title");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_workflow_lifecycle() {
+        let (registry, dir) = registry();
+        let created = registry
+            .call(
+                "workflow_orchestrator",
+                json!({
+                    "action": "create",
+                    "workflow_id": "wf_test",
+                    "workflow": {"name": "Alerts", "steps": [{"action": "alert", "alert_type": "email"}]}
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output(&created)["steps"], 1);
+
+        let executed = registry
+            .call("workflow_orchestrator", json!({"action": "execute", "workflow_id": "wf_test"}))
+            .await
+            .unwrap();
+        assert_eq!(structured(&registry, "workflow_orchestrator", &executed)["status"], "success");
+
+        let listed = registry.call("workflow_orchestrator", json!({"action": "list"})).await.unwrap();
+        assert_eq!(output(&listed)["workflows"][0]["name"], "Alerts");
+
+        let deleted = registry
+            .call("workflow_orchestrator", json!({"action": "delete", "workflow_id": "wf_test"}))
+            .await
+            .unwrap();
+        assert_eq!(deleted["isError"], false);
+
+        let missing = registry
+            .call("workflow_orchestrator", json!({"action": "delete", "workflow_id": "wf_test"}))
+            .await
+            .unwrap();
+        assert_eq!(missing["isError"], true);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_audit_query_filters_entries() {
+        let dir = std::env::temp_dir().join(format!("browsermcp_tools_{}", uuid::Uuid::new_v4()));
+        let log = crate::mcp::audit::AuditLog::open(dir.join("audit"), 1024 * 1024).unwrap();
+        let ctx = ServerContext::new(dir.join("db").to_str().unwrap()).unwrap().with_audit_log(log);
+        let registry = builtin_registry(&ctx);
+        assert_eq!(registry.names().last().copied(), Some("audit_query"));
+
+        let audit = ctx.audit().unwrap();
+        for tool in ["web_scrape", "dom_extraction", "web_scrape"] {
+            let outcome = Ok(json!({"isError": false}));
+            let elapsed = std::time::Duration::ZERO;
+            let entry = crate::mcp::audit::AuditEntry::new(None, Some("ci"), tool, &json!({}), elapsed, &outcome);
+            audit.append(&entry).unwrap();
+        }
+
+        let result = registry.call("audit_query", json!({"tool": "web_scrape"})).await.unwrap();
+        assert_eq!(structured(&registry, "audit_query", &result)["count"], 2);
+        let future = registry.call("audit_query", json!({"since": "2999-01-01T00:00:00Z"})).await.unwrap();
+        assert_eq!(output(&future)["count"], 0);
+        let invalid = registry.call("audit_query", json!({"since": "yesterday"})).await.unwrap_err();
+        assert_eq!(invalid.code, crate::mcp::jsonrpc::INVALID_PARAMS);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
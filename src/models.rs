@@ -1,7 +1,13 @@
-/// Data models for BrowserMCP
+//! Data models for BrowserMCP
+//!
+//! Tool results are declared with [`tool_output!`](crate::tool_output), which
+//! also generates the `outputSchema` advertised in `tools/list`.
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::http_cache::CacheStatus;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrowserInstance {
     pub id: String,
@@ -35,72 +41,115 @@ pub struct BrowserSession {
     pub url: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ScrapedData {
-    pub url: String,
-    pub status_code: u16,
-    pub title: String,
-    pub meta_tags: HashMap<String, String>,
-    pub links: Vec<LinkData>,
-    pub images: Vec<ImageData>,
-    pub emails: Vec<String>,
-    pub phones: Vec<String>,
-    pub scripts: Vec<String>,
-    pub stylesheets: Vec<String>,
-    pub headings: Vec<HeadingData>,
-    pub forms: Vec<FormData>,
-    pub body_text: String,
-    pub scraped_data: HashMap<String, Vec<String>>,
+crate::tool_output! {
+    /// `web_scrape` result, one per URL in `web_scrape_batch`
+    pub struct ScrapedData {
+        required url: String = {"type": "string"},
+        required status_code: u16 = {"type": "integer", "minimum": 100, "maximum": 599},
+        required title: String = {"type": "string"},
+        required meta_tags: HashMap<String, String> = {
+            "type": "object",
+            "description": "Meta name or property -> content",
+            "additionalProperties": {"type": "string"}
+        },
+        required headings: Vec<HeadingData> = {"type": "array", "items": HeadingData::schema()},
+        required links: Vec<LinkData> = {"type": "array", "items": LinkData::schema()},
+        required images: Vec<ImageData> = {"type": "array", "items": ImageData::schema()},
+        required scripts: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required stylesheets: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required forms: Vec<FormData> = {"type": "array", "items": FormData::schema()},
+        required emails: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required phones: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required custom_selectors: HashMap<String, Vec<String>> = {
+            "type": "object",
+            "description": "Requested selector -> matched elements",
+            "additionalProperties": {"type": "array", "items": {"type": "string"}}
+        },
+        required body_preview: String = {"type": "string", "description": "First 500 characters of the body text, followed by ... when cut"},
+        required total_elements: usize = {"type": "integer", "minimum": 0, "description": "Links + images + forms"},
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        optional tls: Option<CertificateSummary> = (CertificateSummary::schema()),
+        required cache: CacheStatus = {
+            "type": "string",
+            "enum": ["miss", "hit", "revalidated"],
+            "description": "`hit` and `revalidated` pages were served from the HTTP cache"
+        },
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LinkData {
-    pub href: String,
-    pub text: String,
+crate::tool_output! {
+    /// The certificate an https server presented, for auditing
+    pub struct CertificateSummary {
+        required subject: String = {"type": "string"},
+        required issuer: String = {"type": "string"},
+        required serial: String = {"type": "string", "description": "Hex"},
+        required not_before: String = {"type": "string", "format": "date-time"},
+        required not_after: String = {"type": "string", "format": "date-time"},
+        required subject_alt_names: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required verified: bool = {
+            "type": "boolean",
+            "description": "False when the host is exempt from verification"
+        },
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ImageData {
-    pub src: String,
-    pub alt: String,
+crate::tool_output! {
+    pub struct LinkData {
+        required href: String = {"type": "string"},
+        required text: String = {"type": "string"},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HeadingData {
-    pub level: String,
-    pub text: String,
+crate::tool_output! {
+    pub struct ImageData {
+        required src: String = {"type": "string"},
+        required alt: String = {"type": "string"},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FormData {
-    pub action: String,
-    pub method: String,
-    pub inputs: Vec<InputField>,
+crate::tool_output! {
+    pub struct HeadingData {
+        required level: String = {"type": "string", "enum": ["h1", "h2", "h3", "h4", "h5", "h6"]},
+        required text: String = {"type": "string"},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InputField {
-    pub name: String,
-    pub field_type: String,
-    pub value: String,
+crate::tool_output! {
+    pub struct FormData {
+        required action: String = {"type": "string"},
+        required method: String = {"type": "string"},
+        required inputs: Vec<InputField> = {"type": "array", "items": InputField::schema()},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CodeAnalysis {
-    pub language: String,
-    pub lines_of_code: usize,
-    pub functions: usize,
-    pub imports: Vec<String>,
-    pub complexity: f64,
-    pub security_issues: usize,
-    pub functions_list: Vec<String>,
+crate::tool_output! {
+    pub struct InputField {
+        required name: String = {"type": "string"},
+        required r#type: String = {"type": "string", "description": "`type` attribute, `text` when absent"},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProjectAnalysis {
-    pub path: String,
-    pub total_files: usize,
-    pub languages: Vec<String>,
-    pub size_mb: f64,
-    pub structure: HashMap<String, usize>,
+crate::tool_output! {
+    pub struct CodeAnalysis {
+        required language: String = {"type": "string"},
+        required lines_of_code: usize = {"type": "integer", "minimum": 0},
+        required functions: usize = {"type": "integer", "minimum": 0},
+        required imports: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required complexity: f64 = {"type": "number"},
+        required security_issues: usize = {"type": "integer", "minimum": 0},
+        required functions_list: Vec<String> = {"type": "array", "items": {"type": "string"}},
+    }
+}
+
+crate::tool_output! {
+    pub struct ProjectAnalysis {
+        required path: String = {"type": "string"},
+        required total_files: usize = {"type": "integer", "minimum": 0},
+        required languages: Vec<String> = {"type": "array", "items": {"type": "string"}},
+        required size_mb: f64 = {"type": "number", "minimum": 0},
+        required structure: HashMap<String, usize> = {
+            "type": "object",
+            "additionalProperties": {"type": "integer", "minimum": 0}
+        },
+    }
 }
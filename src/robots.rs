@@ -0,0 +1,600 @@
+//! robots.txt (RFC 9309) compliance for every outbound fetch
+//!
+//! [`crate::fetch::Fetcher`] calls [`Robots::check`] on the [`global`]
+//! instance before requesting a URL. Each origin's robots.txt is fetched
+//! once, through the proxy the request itself will use, and cached for a
+//! day (per origin and proxy, at most [`MAX_ORIGINS`] of them); the group
+//! is picked by `http.robots_agent`, and the most specific `Allow`/`Disallow`
+//! rule (longest pattern, `Allow` on ties) decides, with `*` matching any
+//! run of characters and a trailing `$` anchoring the end.
+//!
+//! A robots.txt answered with 4xx allows everything; a 5xx answer or no
+//! answer at all refuses the whole origin until it can be fetched again.
+
+use parking_lot::Mutex;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+use crate::config;
+use crate::fetch::{self, FetchRequest};
+
+/// How long a fetched robots.txt is trusted
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long an unreachable robots.txt blocks its origin before the next try
+const UNREACHABLE_TTL: Duration = Duration::from_secs(60);
+
+/// Bytes of a robots.txt that are read and parsed
+const MAX_BYTES: usize = 500 * 1024;
+
+/// Cached robots.txt files; expired ones go first, then the oldest
+pub const MAX_ORIGINS: usize = 1024;
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: Robots = Robots::new();
+}
+
+/// The cache every fetch path consults
+pub fn global() -> &'static Robots {
+    &GLOBAL
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RobotsError {
+    #[error("{url} is disallowed by {robots_url} for {agent} ({rule})")]
+    Disallowed {
+        url: String,
+        robots_url: String,
+        agent: String,
+        rule: Rule,
+    },
+
+    #[error("{robots_url} could not be fetched ({reason}), not fetching {url}")]
+    Unreachable {
+        url: String,
+        robots_url: String,
+        reason: String,
+    },
+}
+
+/// One `Allow` or `Disallow` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub allow: bool,
+    /// Normalized path pattern
+    pub pattern: String,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let directive = if self.allow { "Allow" } else { "Disallow" };
+        write!(f, "{}: {}", directive, self.pattern)
+    }
+}
+
+/// `User-agent` lines and the records that follow them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Group {
+    /// Lowercase product tokens, `*` for everyone
+    pub agents: Vec<String>,
+    pub rules: Vec<Rule>,
+    pub crawl_delay: Option<Duration>,
+}
+
+/// A parsed robots.txt; the default one allows everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsTxt {
+    pub groups: Vec<Group>,
+    pub sitemaps: Vec<String>,
+}
+
+impl RobotsTxt {
+    /// Parse the first 500 KiB of `text`; unknown and malformed lines are skipped
+    pub fn parse(text: &str) -> Self {
+        let mut end = text.len().min(MAX_BYTES);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let text = text[..end].trim_start_matches('\u{feff}');
+
+        let mut robots = RobotsTxt::default();
+        // Consecutive User-agent lines share one group
+        let mut reading_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            if key == "user-agent" {
+                if !reading_agents {
+                    robots.groups.push(Group::default());
+                    reading_agents = true;
+                }
+                if let Some(group) = robots.groups.last_mut() {
+                    group.agents.push(agent_token(value));
+                }
+                continue;
+            }
+            if key == "sitemap" {
+                if !value.is_empty() {
+                    robots.sitemaps.push(value.to_string());
+                }
+                continue;
+            }
+
+            reading_agents = false;
+            // Records before the first User-agent belong to no group
+            let Some(group) = robots.groups.last_mut() else {
+                continue;
+            };
+            match key.as_str() {
+                // An empty Disallow allows everything: no rule to add
+                "allow" | "disallow" if !value.is_empty() => group.rules.push(Rule {
+                    allow: key == "allow",
+                    pattern: normalize(value),
+                }),
+                "crawl-delay" => {
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                        .map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+        robots
+    }
+
+    /// The groups for `agent` merged into one: those naming it, or the `*`
+    /// groups when none does
+    pub fn group(&self, agent: &str) -> Group {
+        let agent = agent_token(agent);
+        let mut chosen = self.groups_naming(&agent);
+        if chosen.is_empty() {
+            chosen = self.groups_naming("*");
+        }
+
+        let mut merged = Group {
+            agents: vec![agent],
+            ..Group::default()
+        };
+        for group in chosen {
+            merged.rules.extend(group.rules.iter().cloned());
+            merged.crawl_delay = merged.crawl_delay.or(group.crawl_delay);
+        }
+        merged
+    }
+
+    fn groups_naming(&self, token: &str) -> Vec<&Group> {
+        self.groups.iter().filter(|g| g.agents.iter().any(|a| a == token)).collect()
+    }
+
+    /// The rule deciding `path` (with its query) for `agent`, if any matches
+    pub fn matching_rule(&self, agent: &str, path: &str) -> Option<Rule> {
+        let path = normalize(path);
+        self.group(agent)
+            .rules
+            .into_iter()
+            .filter(|rule| matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+    }
+
+    pub fn is_allowed(&self, agent: &str, path: &str) -> bool {
+        path == "/robots.txt" || self.matching_rule(agent, path).is_none_or(|rule| rule.allow)
+    }
+
+    pub fn crawl_delay(&self, agent: &str) -> Option<Duration> {
+        self.group(agent).crawl_delay
+    }
+}
+
+/// Characters allowed in a robots.txt product token
+pub fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '-'
+}
+
+/// `Googlebot/2.1 (+http://...)` → `googlebot`; `*` stays `*`
+fn agent_token(value: &str) -> String {
+    if value.starts_with('*') {
+        return "*".to_string();
+    }
+    value.chars().take_while(|c| is_token_char(*c)).collect::<String>().to_ascii_lowercase()
+}
+
+/// Percent-encode non-ASCII bytes and uppercase existing escapes, so a
+/// pattern written in UTF-8 matches the encoded request path
+fn normalize(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = String::with_capacity(value.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        let escape = byte == b'%' && bytes.get(i + 1..i + 3).is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        if escape {
+            out.push('%');
+            out.push(bytes[i + 1].to_ascii_uppercase() as char);
+            out.push(bytes[i + 2].to_ascii_uppercase() as char);
+            i += 3;
+            continue;
+        }
+        if byte.is_ascii() && byte != b' ' {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Whether `pattern` matches `path` from its start: `*` is any run of
+/// characters, a final `$` requires the path to end there
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern.as_bytes(), true),
+        None => (pattern.as_bytes(), false),
+    };
+    let path = path.as_bytes();
+
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the path position it is matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    loop {
+        if p == pattern.len() {
+            if !anchored || t == path.len() {
+                return true;
+            }
+        } else if pattern[p] == b'*' {
+            p += 1;
+            backtrack = Some((p, t));
+            continue;
+        } else if t < path.len() && pattern[p] == path[t] {
+            p += 1;
+            t += 1;
+            continue;
+        }
+        // Let the last `*` swallow one more character
+        match backtrack {
+            Some((star_p, star_t)) if star_t < path.len() => {
+                backtrack = Some((star_p, star_t + 1));
+                p = star_p;
+                t = star_t + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Fetched {
+    Parsed(Arc<RobotsTxt>),
+    Unreachable(String),
+}
+
+struct CacheEntry {
+    fetched: Arc<OnceCell<Fetched>>,
+    created: Instant,
+}
+
+impl CacheEntry {
+    fn expired(&self) -> bool {
+        match self.fetched.get() {
+            Some(Fetched::Parsed(_)) => self.created.elapsed() > CACHE_TTL,
+            Some(Fetched::Unreachable(_)) => self.created.elapsed() > UNREACHABLE_TTL,
+            // Still being fetched
+            None => false,
+        }
+    }
+}
+
+/// Per-origin robots.txt cache; concurrent checks of one origin share a fetch
+#[derive(Default)]
+pub struct Robots {
+    /// By origin and proxy
+    cache: Mutex<HashMap<(String, Option<String>), CacheEntry>>,
+}
+
+impl Robots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `Ok` when `url` may be fetched through `proxy`. Always `Ok` with
+    /// `http.respect_robots_txt` off and for URLs that are not http(s),
+    /// which the fetch itself rejects.
+    pub async fn check(&self, url: &str, proxy: Option<&str>) -> Result<(), RobotsError> {
+        let http = &config::current().http;
+        if !http.respect_robots_txt {
+            return Ok(());
+        }
+        let Some((parsed, robots_url)) = robots_url(url) else {
+            return Ok(());
+        };
+
+        match self.fetched(&parsed, &robots_url, proxy).await {
+            Fetched::Parsed(robots) => {
+                let mut path = parsed.path().to_string();
+                if let Some(query) = parsed.query() {
+                    path = format!("{}?{}", path, query);
+                }
+                match robots.matching_rule(&http.robots_agent, &path) {
+                    Some(rule) if !rule.allow && path != "/robots.txt" => Err(RobotsError::Disallowed {
+                        url: url.to_string(),
+                        robots_url,
+                        agent: http.robots_agent.clone(),
+                        rule,
+                    }),
+                    _ => Ok(()),
+                }
+            }
+            Fetched::Unreachable(reason) => Err(RobotsError::Unreachable {
+                url: url.to_string(),
+                robots_url,
+                reason,
+            }),
+        }
+    }
+
+    /// The robots.txt of `url`'s origin as seen through `proxy` (`None` if
+    /// it cannot be fetched), for its Crawl-delay and Sitemap lines
+    pub async fn get(&self, url: &str, proxy: Option<&str>) -> Option<Arc<RobotsTxt>> {
+        let (parsed, robots_url) = robots_url(url)?;
+        match self.fetched(&parsed, &robots_url, proxy).await {
+            Fetched::Parsed(robots) => Some(robots),
+            Fetched::Unreachable(_) => None,
+        }
+    }
+
+    async fn fetched(&self, url: &Url, robots_url: &str, proxy: Option<&str>) -> Fetched {
+        let fetched = {
+            let mut cache = self.cache.lock();
+            let key = (url.origin().ascii_serialization(), proxy.map(String::from));
+            match cache.get(&key) {
+                Some(entry) if !entry.expired() => Arc::clone(&entry.fetched),
+                _ => {
+                    if cache.len() >= MAX_ORIGINS && !cache.contains_key(&key) {
+                        prune(&mut cache);
+                    }
+                    let fetched = Arc::new(OnceCell::new());
+                    cache.insert(
+                        key,
+                        CacheEntry {
+                            fetched: Arc::clone(&fetched),
+                            created: Instant::now(),
+                        },
+                    );
+                    fetched
+                }
+            }
+        };
+        fetched.get_or_init(|| fetch(robots_url, proxy)).await.clone()
+    }
+}
+
+/// Make room for one more entry: drop the expired ones, else the oldest
+fn prune(cache: &mut HashMap<(String, Option<String>), CacheEntry>) {
+    cache.retain(|_, entry| !entry.expired());
+    while cache.len() >= MAX_ORIGINS {
+        let oldest = cache.iter().min_by_key(|(_, entry)| entry.created).map(|(key, _)| key.clone());
+        match oldest {
+            Some(key) => cache.remove(&key),
+            None => break,
+        };
+    }
+}
+
+/// `url` parsed, with its origin's `/robots.txt`; `None` unless http(s)
+fn robots_url(url: &str) -> Option<(Url, String)> {
+    let parsed = Url::parse(url).ok()?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return None;
+    }
+    let robots_url = parsed.join("/robots.txt").ok()?.to_string();
+    Some((parsed, robots_url))
+}
+
+async fn fetch(robots_url: &str, proxy: Option<&str>) -> Fetched {
+    let mut request = FetchRequest::get(robots_url).max_retries(1).skip_robots();
+    if let Some(proxy) = proxy {
+        request = request.proxy(proxy);
+    }
+    // Boxed: the fetcher itself awaits robots.txt checks
+    let response = match Box::pin(fetch::global().fetch(request)).await {
+        Ok(response) => response,
+        Err(e) => return Fetched::Unreachable(e.to_string()),
+    };
+    if (400..500).contains(&response.status) {
+        // No robots.txt: no restrictions
+        return Fetched::Parsed(Arc::new(RobotsTxt::default()));
+    }
+    if !(200..300).contains(&response.status) {
+        return Fetched::Unreachable(format!("HTTP {}", response.status));
+    }
+    Fetched::Parsed(Arc::new(RobotsTxt::parse(&String::from_utf8_lossy(&response.body))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const EXAMPLE: &str = "\
+# Example
+User-agent: BrowserMCP/1.0
+User-agent: otherbot
+Disallow: /private
+Allow: /private/public$
+Disallow: /*.pdf$
+Crawl-delay: 2.5
+
+User-agent: *
+Disallow: /
+
+Sitemap: https://example.com/sitemap.xml
+User-agent: browsermcp
+Disallow: /tmp/ # merged into the first group
+";
+
+    #[test]
+    fn test_groups_are_chosen_and_merged_by_agent() {
+        let robots = RobotsTxt::parse(EXAMPLE);
+        assert_eq!(robots.sitemaps, vec!["https://example.com/sitemap.xml"]);
+
+        let group = robots.group("BrowserMCP");
+        assert_eq!(group.rules.len(), 4);
+        assert_eq!(group.crawl_delay, Some(Duration::from_millis(2500)));
+        assert!(robots.is_allowed("BrowserMCP", "/"));
+        assert!(!robots.is_allowed("BrowserMCP", "/tmp/x"));
+
+        // Unnamed agents get the `*` group
+        assert!(!robots.is_allowed("somebot", "/anything"));
+        assert!(robots.is_allowed("somebot", "/robots.txt"));
+        assert_eq!(robots.crawl_delay("somebot"), None);
+
+        // Nothing applies without a matching or `*` group
+        assert!(RobotsTxt::parse("User-agent: a\nDisallow: /").is_allowed("b", "/x"));
+    }
+
+    #[test]
+    fn test_longest_match_wins_and_allow_breaks_ties() {
+        let robots = RobotsTxt::parse(EXAMPLE);
+        assert!(!robots.is_allowed("otherbot", "/private/page"));
+        assert!(robots.is_allowed("otherbot", "/private/public"));
+        // `$` anchors: a longer path falls back to the Disallow
+        assert!(!robots.is_allowed("otherbot", "/private/public/more"));
+        assert!(!robots.is_allowed("otherbot", "/docs/a.pdf"));
+        assert!(robots.is_allowed("otherbot", "/docs/a.pdf?download=1"));
+
+        let tie = RobotsTxt::parse("User-agent: *\nDisallow: /page\nAllow: /page\n");
+        assert!(tie.is_allowed("x", "/page"));
+        let rule = RobotsTxt::parse("User-agent: *\nDisallow: /a\nDisallow: /a*b\n").matching_rule("x", "/a-to-b");
+        assert_eq!(rule.unwrap().to_string(), "Disallow: /a*b");
+    }
+
+    #[test]
+    fn test_wildcards_and_encoding() {
+        assert!(matches("/", "/anything"));
+        assert!(matches("/*/edit", "/users/1/edit"));
+        assert!(matches("/*.php$", "/a/b.php"));
+        assert!(!matches("/*.php$", "/a/b.php5"));
+        assert!(matches("/a**b$", "/axxb"));
+        assert!(!matches("/a", "/"));
+
+        // UTF-8 in the file matches the percent-encoded path, escapes ignore case
+        let robots = RobotsTxt::parse("User-agent: *\nDisallow: /café\nDisallow: /x%2f\n");
+        assert!(!robots.is_allowed("x", "/caf%C3%A9/menu"));
+        assert!(!robots.is_allowed("x", "/x%2F"));
+        assert!(robots.is_allowed("x", "/cafe"));
+    }
+
+    /// A host answering `/robots.txt` with `status` and `body`, counting requests
+    async fn robots_host(status: u16, body: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (base, requests)
+    }
+
+    #[tokio::test]
+    async fn test_check_caches_per_origin_and_types_refusals() {
+        let robots = Robots::new();
+        let (base, requests) = robots_host(200, "User-agent: *\nDisallow: /admin\n").await;
+        robots.check(&format!("{}/", base), None).await.unwrap();
+        let refused = robots.check(&format!("{}/admin?x=1", base), None).await.unwrap_err();
+        match refused {
+            RobotsError::Disallowed { robots_url, rule, .. } => {
+                assert_eq!(robots_url, format!("{}/robots.txt", base));
+                assert_eq!(rule.pattern, "/admin");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let (missing, _) = robots_host(404, "").await;
+        robots.check(&format!("{}/admin", missing), None).await.unwrap();
+
+        let (failing, _) = robots_host(503, "").await;
+        let unreachable = robots.check(&format!("{}/", failing), None).await.unwrap_err();
+        assert!(matches!(unreachable, RobotsError::Unreachable { .. }));
+        assert!(unreachable.to_string().contains("HTTP 503"));
+
+        // Not http(s): left to the fetch
+        robots.check("file:///etc/passwd", None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_robots_go_through_the_request_proxy() {
+        let robots = Robots::new();
+        // Answers every proxied request, whatever host it names
+        let (proxy, requests) = robots_host(200, "User-agent: *\nDisallow: /admin\n").await;
+        // Nothing listens there: only the proxy can reach it
+        let target = "http://127.0.0.1:1";
+
+        let refused = robots.check(&format!("{}/admin", target), Some(&proxy)).await.unwrap_err();
+        assert!(matches!(refused, RobotsError::Disallowed { .. }));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Direct requests have their own entry
+        let direct = robots.check(&format!("{}/admin", target), None).await.unwrap_err();
+        assert!(matches!(direct, RobotsError::Unreachable { .. }));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let parsed = || {
+            Fetched::Parsed(Arc::new(RobotsTxt {
+                groups: Vec::new(),
+                sitemaps: Vec::new(),
+            }))
+        };
+        let entry = |age: Duration, fetched: Fetched| CacheEntry {
+            fetched: Arc::new(OnceCell::new_with(Some(fetched))),
+            created: Instant::now() - age,
+        };
+        let mut cache = HashMap::new();
+        for n in 0..MAX_ORIGINS - 1 {
+            let age = Duration::from_millis(n as u64);
+            cache.insert((format!("http://{}.example", n), None), entry(age, parsed()));
+        }
+        let unreachable = Fetched::Unreachable("refused".to_string());
+        let expired = ("http://expired.example".to_string(), None);
+        cache.insert(expired.clone(), entry(UNREACHABLE_TTL * 2, unreachable));
+
+        prune(&mut cache);
+        assert_eq!(cache.len(), MAX_ORIGINS - 1);
+        assert!(!cache.contains_key(&expired));
+
+        cache.insert(("http://fresh.example".to_string(), None), entry(Duration::ZERO, parsed()));
+        prune(&mut cache);
+        assert_eq!(cache.len(), MAX_ORIGINS - 1);
+        // The oldest goes
+        let oldest = (format!("http://{}.example", MAX_ORIGINS - 2), None);
+        assert!(!cache.contains_key(&oldest));
+    }
+}
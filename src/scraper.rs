@@ -1,26 +1,16 @@
 /// Marketing Scraper - Real Data Extraction
 /// Extrae: emails, teléfonos, links, metadata, contenido HTML completo
 
-use reqwest::Client;
 use scraper::{Html, Selector};
 use regex::Regex;
 use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
 
-pub struct MarketingScraper {
-    client: Client,
-}
+pub struct MarketingScraper;
 
 impl MarketingScraper {
     pub fn new() -> Self {
-        Self {
-            client: Client::builder()
-                .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
-                .danger_accept_invalid_certs(true)
-                .timeout(std::time::Duration::from_secs(30))
-                .build()
-                .unwrap_or_else(|_| Client::new()),
-        }
+        Self
     }
 
     /// Scrape URL completa con extracción de datos para marketing
@@ -29,9 +19,7 @@ impl MarketingScraper {
         url: &str,
         custom_selectors: Option<Vec<String>>,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let response = self.client.get(url).send().await?;
-        let status_code = response.status().as_u16();
-        let html_text = response.text().await?;
+        let (status_code, html_text) = crate::fetch::global().get_text(url).await?;
         let document = Html::parse_document(&html_text);
 
         // Metadata básico
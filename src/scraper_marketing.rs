@@ -2,26 +2,71 @@
 /// Extrae datos REALES con emails, phones, links, imágenes, meta tags, etc.
 /// Production-ready para DB y análisis
 
-use reqwest::Client;
 use scraper::{Html, Selector};
 use regex::Regex;
 use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::config;
+use crate::fetch::{self, FetchRequest};
+use crate::mcp::metrics;
+use crate::models::{FormData, HeadingData, ImageData, InputField, LinkData, ScrapedData};
+
+/// Descargar HTML crudo de una URL (status + body) con el fetcher compartido
+pub async fn fetch_html(url: &str) -> Result<(u16, String), Box<dyn std::error::Error + Send + Sync>> {
+    Ok(fetch::global().get_text(url).await?)
+}
+
+/// Texto de un elemento + href/src si los tiene
+fn element_summary(el: &scraper::ElementRef) -> String {
+    let text = el.text().collect::<Vec<_>>().join(" ").trim().to_string();
+    let href = el
+        .value()
+        .attr("href")
+        .map(|h| format!(" [href={}]", h))
+        .unwrap_or_default();
+    let src = el
+        .value()
+        .attr("src")
+        .map(|s| format!(" [src={}]", s))
+        .unwrap_or_default();
+    format!("{}{}{}", text, href, src)
+}
+
+/// Extraer campos nombrados de HTML: `{campo: selector CSS}` → `{campo: [valores]}`
+pub fn extract_selectors(
+    html: &str,
+    selectors: &HashMap<String, String>,
+) -> Result<HashMap<String, Vec<String>>, String> {
+    let document = Html::parse_document(html);
+    let mut fields = HashMap::new();
+
+    for (field, selector_str) in selectors {
+        let selector = Selector::parse(selector_str)
+            .map_err(|_| format!("Invalid CSS selector for {}: {}", field, selector_str))?;
+        let values: Vec<String> = document
+            .select(&selector)
+            .map(|el| element_summary(&el))
+            .filter(|s| !s.is_empty())
+            .collect();
+        fields.insert(field.clone(), values);
+    }
+
+    Ok(fields)
+}
 
 /// Scrape URL completa con extracción profunda
 pub async fn scrape_url_full(
     url: &str,
     selectors: &[String],
 ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
-        .danger_accept_invalid_certs(true)
-        .timeout(std::time::Duration::from_secs(30))
-        .build()?;
-
-    let response = client.get(url).send().await?;
-    let status_code = response.status().as_u16();
-    let html = response.text().await?;
+    let response = fetch::global().fetch(FetchRequest::get(url)).await?;
+    let (status_code, html) = (response.status, response.text());
     let document = Html::parse_document(&html);
 
     // Extraer por selectores custom
@@ -30,20 +75,7 @@ pub async fn scrape_url_full(
         if let Ok(selector) = Selector::parse(selector_str) {
             let elements: Vec<String> = document
                 .select(&selector)
-                .map(|el| {
-                    let text = el.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                    let href = el
-                        .value()
-                        .attr("href")
-                        .map(|h| format!(" [href={}]", h))
-                        .unwrap_or_default();
-                    let src = el
-                        .value()
-                        .attr("src")
-                        .map(|s| format!(" [src={}]", s))
-                        .unwrap_or_default();
-                    format!("{}{}{}", text, href, src)
-                })
+                .map(|el| element_summary(&el))
                 .filter(|s| !s.is_empty())
                 .collect();
             if !elements.is_empty() {
@@ -68,24 +100,24 @@ pub async fn scrape_url_full(
     }
 
     // Links
-    let mut links: Vec<Value> = Vec::new();
+    let mut links: Vec<LinkData> = Vec::new();
     if let Ok(link_selector) = Selector::parse("a[href]") {
         for element in document.select(&link_selector) {
             if let Some(href) = element.value().attr("href") {
                 let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
-                links.push(json!({"href": href, "text": text}));
+                links.push(LinkData { href: href.to_string(), text });
             }
         }
     }
 
     // Imágenes
-    let mut images: Vec<Value> = Vec::new();
+    let mut images: Vec<ImageData> = Vec::new();
     if let Ok(img_selector) = Selector::parse("img") {
         for element in document.select(&img_selector) {
             let src = element.value().attr("src").unwrap_or("");
             let alt = element.value().attr("alt").unwrap_or("");
             if !src.is_empty() {
-                images.push(json!({"src": src, "alt": alt}));
+                images.push(ImageData { src: src.to_string(), alt: alt.to_string() });
             }
         }
     }
@@ -160,59 +192,75 @@ pub async fn scrape_url_full(
     };
 
     // Headings
-    let mut headings: Vec<Value> = Vec::new();
+    let mut headings: Vec<HeadingData> = Vec::new();
     for h in &["h1", "h2", "h3", "h4", "h5", "h6"] {
         if let Ok(h_selector) = Selector::parse(h) {
             for element in document.select(&h_selector) {
                 let text = element.text().collect::<Vec<_>>().join(" ").trim().to_string();
                 if !text.is_empty() {
-                    headings.push(json!({"level": h, "text": text}));
+                    headings.push(HeadingData { level: h.to_string(), text });
                 }
             }
         }
     }
 
     // Forms
-    let mut forms: Vec<Value> = Vec::new();
+    let mut forms: Vec<FormData> = Vec::new();
     if let Ok(form_selector) = Selector::parse("form") {
         for form in document.select(&form_selector) {
             let action = form.value().attr("action").unwrap_or("");
             let method = form.value().attr("method").unwrap_or("get");
 
-            let mut inputs: Vec<Value> = Vec::new();
+            let mut inputs: Vec<InputField> = Vec::new();
             if let Ok(input_selector) = Selector::parse("input, select, textarea") {
                 for input in form.select(&input_selector) {
                     let name = input.value().attr("name").unwrap_or("");
                     let type_attr = input.value().attr("type").unwrap_or("text");
-                    inputs.push(json!({"name": name, "type": type_attr}));
+                    inputs.push(InputField { name: name.to_string(), r#type: type_attr.to_string() });
                 }
             }
 
-            forms.push(json!({
-                "action": action,
-                "method": method,
-                "inputs": inputs
-            }));
+            forms.push(FormData {
+                action: action.to_string(),
+                method: method.to_string(),
+                inputs,
+            });
         }
     }
 
-    Ok(json!({
-        "url": url,
-        "status_code": status_code,
-        "title": title,
-        "meta_tags": meta_tags,
-        "headings": headings,
-        "links": links,
-        "images": images,
-        "scripts": scripts,
-        "stylesheets": stylesheets,
-        "forms": forms,
-        "emails": emails,
-        "phones": phones,
-        "custom_selectors": scraped_data,
-        "body_preview": if body_text.len() > 500 { format!("{}...", &body_text[..500]) } else { body_text },
-        "total_elements": links.len() + images.len() + forms.len()
-    }))
+    let total_elements = links.len() + images.len() + forms.len();
+    let data = ScrapedData {
+        url: url.to_string(),
+        status_code,
+        title,
+        meta_tags,
+        headings,
+        links,
+        images,
+        scripts,
+        stylesheets,
+        forms,
+        emails,
+        phones,
+        custom_selectors: scraped_data,
+        body_preview: preview(body_text),
+        total_elements,
+        tls: response.certificate,
+        cache: response.cache,
+    };
+    Ok(serde_json::to_value(data)?)
+}
+
+/// Characters of body text kept in `body_preview`
+const PREVIEW_CHARS: usize = 500;
+
+/// The first `PREVIEW_CHARS` characters of the body text, cut on a
+/// character boundary
+fn preview(text: String) -> String {
+    match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
 }
 
 /// Scrape múltiples URLs en paralelo
@@ -220,12 +268,136 @@ pub async fn scrape_batch(
     urls: Vec<String>,
     selectors: Vec<String>,
 ) -> Vec<Value> {
-    let mut results = Vec::new();
-    for url in urls {
-        match scrape_url_full(&url, &selectors).await {
-            Ok(data) => results.push(data),
-            Err(e) => results.push(json!({"url": url, "error": e.to_string()})),
+    scrape_batch_with(urls, selectors, &CancellationToken::new(), |_, _, _| {}).await.0
+}
+
+/// [`scrape_batch`] que reporta cada URL terminada con `on_done(done, total, url)`
+/// y se detiene al cancelar `cancel`, abortando las descargas pendientes.
+/// Devuelve los resultados obtenidos hasta entonces (en el orden de `urls`)
+/// y si fue cancelado.
+pub async fn scrape_batch_with<F>(
+    urls: Vec<String>,
+    selectors: Vec<String>,
+    cancel: &CancellationToken,
+    on_done: F,
+) -> (Vec<Value>, bool)
+where
+    F: Fn(usize, usize, &str),
+{
+    let selectors = Arc::new(selectors);
+    run_batch(urls, cancel, on_done, move |url| {
+        let selectors = Arc::clone(&selectors);
+        async move {
+            match scrape_url_full(&url, &selectors).await {
+                Ok(data) => data,
+                Err(e) => json!({"url": url, "error": e.to_string()}),
+            }
+        }
+    })
+    .await
+}
+
+async fn run_batch<F, W, Fut>(urls: Vec<String>, cancel: &CancellationToken, on_done: F, work: W) -> (Vec<Value>, bool)
+where
+    F: Fn(usize, usize, &str),
+    W: Fn(String) -> Fut,
+    Fut: Future<Output = Value> + Send + 'static,
+{
+    let total = urls.len();
+    let semaphore = Arc::new(Semaphore::new(config::current().batch.concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, url) in urls.into_iter().enumerate() {
+        let semaphore = Arc::clone(&semaphore);
+        let job = work(url.clone());
+        let queued = metrics::global().batch_queued();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            drop(queued);
+            (index, url, job.await)
+        });
+    }
+
+    let mut results = Vec::with_capacity(total);
+    let mut cancelled = false;
+    loop {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                tasks.abort_all();
+                cancelled = true;
+                break;
+            }
+            joined = tasks.join_next() => match joined {
+                Some(Ok((index, url, value))) => {
+                    results.push((index, value));
+                    on_done(results.len(), total, &url);
+                }
+                Some(Err(e)) => tracing::error!("Batch scrape task failed: {}", e),
+                None => break,
+            },
         }
     }
-    results
+
+    results.sort_by_key(|(index, _)| *index);
+    (results.into_iter().map(|(_, value)| value).collect(), cancelled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_selectors() {
+        let html = r#"<html><body><h1>Deals</h1><span class="price">$10</span><a class="price" href="/p">$12</a></body></html>"#;
+        let selectors = HashMap::from([
+            ("title".to_string(), "h1".to_string()),
+            ("prices".to_string(), ".price".to_string()),
+            ("missing".to_string(), ".nope".to_string()),
+        ]);
+
+        let fields = extract_selectors(html, &selectors).unwrap();
+        assert_eq!(fields["title"], vec!["Deals"]);
+        assert_eq!(fields["prices"], vec!["$10", "$12 [href=/p]"]);
+        assert!(fields["missing"].is_empty());
+
+        let bad = HashMap::from([("x".to_string(), "[[".to_string())]);
+        assert!(extract_selectors(html, &bad).is_err());
+    }
+
+    #[test]
+    fn test_preview_cuts_on_characters() {
+        let text = "ñ".repeat(300) + &"€".repeat(300);
+        let cut = preview(text);
+        assert_eq!(cut.chars().count(), PREVIEW_CHARS + 3);
+        assert!(cut.starts_with(&"ñ".repeat(300)) && cut.ends_with("€..."));
+        assert_eq!(preview("corto".to_string()), "corto");
+    }
+
+    #[tokio::test]
+    async fn test_run_batch_progress_and_cancel() {
+        let urls: Vec<String> = (0..8).map(|i| format!("https://example.com/{}", i)).collect();
+        let slow = |url: String| async move {
+            // The first five finish at once, the rest never do
+            if url.ends_with('5') || url.ends_with('6') || url.ends_with('7') {
+                tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+            }
+            json!({"url": url})
+        };
+
+        let cancel = CancellationToken::new();
+        let done = std::sync::Mutex::new(Vec::new());
+        let canceller = cancel.clone();
+        let (results, cancelled) = run_batch(urls, &cancel, |n, total, _| {
+            done.lock().unwrap().push((n, total));
+            if n == 5 {
+                canceller.cancel();
+            }
+        }, slow)
+        .await;
+
+        assert!(cancelled);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0]["url"], "https://example.com/0");
+        assert_eq!(done.lock().unwrap().last(), Some(&(5, 8)));
+    }
 }
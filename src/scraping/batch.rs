@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, span, Level};
 
 #[derive(Error, Debug)]
@@ -38,16 +41,19 @@ pub struct RateLimit {
     pub burst_size: usize,
 }
 
+/// Concurrency and timeout follow `batch.concurrency` and `http.timeout_secs`
+/// from the [server configuration](crate::config)
 impl Default for BatchConfig {
     fn default() -> Self {
+        let config = crate::config::current();
         Self {
             batch_size: 100,
-            max_concurrent: 5,
+            max_concurrent: config.batch.concurrency,
             rate_limit: Some(RateLimit {
                 requests_per_second: 10.0,
                 burst_size: 20,
             }),
-            timeout: Duration::from_secs(30),
+            timeout: config.http.timeout(),
             retry_count: 3,
             retry_delay: Duration::from_millis(100),
             continue_on_error: true,
@@ -81,6 +87,9 @@ pub struct BatchResult {
     pub success_rate: f64,
     pub processing_time_ms: u128,
     pub results: Vec<ProcessingItem>,
+    /// Stopped early by cancellation; unfinished items are `Skipped`
+    #[serde(default)]
+    pub cancelled: bool,
 }
 
 #[derive(Debug)]
@@ -151,6 +160,40 @@ impl BatchProcessor {
             + Sync
             + Send
             + 'static,
+    {
+        self.process_batch_with(items, processor_fn, &CancellationToken::new(), |_, _| {})
+            .await
+    }
+
+    /// Processes items in batches, calling `on_progress(done, total)` as each
+    /// item finishes. Cancelling `cancel` aborts running and queued items,
+    /// which come back as `Skipped`, and returns the partial result.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let cancel = CancellationToken::new();
+    /// let result = processor
+    ///     .process_batch_with(items, processor_fn, &cancel, |done, total| {
+    ///         println!("{}/{}", done, total);
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn process_batch_with<F, P>(
+        &self,
+        items: Vec<ProcessingItem>,
+        processor_fn: F,
+        cancel: &CancellationToken,
+        on_progress: P,
+    ) -> Result<BatchResult, BatchError>
+    where
+        F: Fn(
+                ProcessingItem,
+            )
+                -> futures::future::BoxFuture<'static, Result<ProcessingItem, BatchError>>
+            + Sync
+            + Send
+            + 'static,
+        P: Fn(usize, usize),
     {
         let span = span!(Level::DEBUG, "process_batch", item_count = items.len());
         let _guard = span.enter();
@@ -160,40 +203,57 @@ impl BatchProcessor {
         let start_time = std::time::Instant::now();
         let processor_fn = Arc::new(processor_fn);
 
-        let mut handles = Vec::new();
+        // Dropping the set (early return) aborts every task still running
+        let mut tasks = JoinSet::new();
+        let mut pending: HashMap<usize, ProcessingItem> = HashMap::new();
         let total_items = items.len();
 
-        for item in items {
+        for (index, item) in items.into_iter().enumerate() {
+            pending.insert(index, item.clone());
             let semaphore = Arc::clone(&self.semaphore);
             let processor_fn = Arc::clone(&processor_fn);
             let config = self.config.clone();
 
-            let handle = tokio::spawn(async move {
-                let _permit = semaphore
-                    .acquire()
-                    .await
-                    .map_err(|e| BatchError::ChannelError(e.to_string()))?;
-
-                Self::process_item_with_retry(
-                    item,
-                    &processor_fn,
-                    config.retry_count,
-                    config.retry_delay,
-                )
-                .await
+            tasks.spawn(async move {
+                let result = match semaphore.acquire().await {
+                    Ok(_permit) => {
+                        Self::process_item_with_retry(
+                            item,
+                            &processor_fn,
+                            config.retry_count,
+                            config.retry_delay,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(BatchError::ChannelError(e.to_string())),
+                };
+                (index, result)
             });
-
-            handles.push(handle);
         }
 
         let mut results = Vec::new();
         let mut processed = 0;
         let mut failed = 0;
         let mut skipped = 0;
+        let mut done = 0;
+        let mut cancelled = false;
 
-        for handle in handles {
-            match handle.await {
-                Ok(Ok(item)) => {
+        loop {
+            let joined = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => {
+                    tasks.abort_all();
+                    cancelled = true;
+                    break;
+                }
+                joined = tasks.join_next() => joined,
+            };
+            let Some(joined) = joined else { break };
+            done += 1;
+
+            match joined {
+                Ok((index, Ok(item))) => {
+                    pending.remove(&index);
                     if item.status == ProcessingStatus::Success {
                         processed += 1;
                     } else if item.status == ProcessingStatus::Failed {
@@ -207,9 +267,10 @@ impl BatchProcessor {
                     } else {
                         skipped += 1;
                     }
-                    results.push(item);
+                    results.push((index, item));
                 }
-                Ok(Err(e)) => {
+                Ok((index, Err(e))) => {
+                    pending.remove(&index);
                     failed += 1;
                     if !self.config.continue_on_error {
                         return Err(e);
@@ -224,7 +285,21 @@ impl BatchProcessor {
                     error!("Task join error: {}", e);
                 }
             }
+
+            on_progress(done, total_items);
+        }
+
+        if cancelled {
+            debug!("Batch cancelled after {} of {} items", done, total_items);
+            for (index, mut item) in pending {
+                item.status = ProcessingStatus::Skipped;
+                item.error = Some("cancelled".to_string());
+                skipped += 1;
+                results.push((index, item));
+            }
         }
+        results.sort_by_key(|(index, _)| *index);
+        let results = results.into_iter().map(|(_, item)| item).collect();
 
         let processing_time_ms = start_time.elapsed().as_millis();
         let success_rate = if total_items > 0 {
@@ -246,6 +321,7 @@ impl BatchProcessor {
             success_rate,
             processing_time_ms,
             results,
+            cancelled,
         })
     }
 
@@ -324,6 +400,7 @@ impl BatchProcessor {
             success_rate,
             processing_time_ms,
             results,
+            cancelled: false,
         })
     }
 
@@ -478,4 +555,55 @@ mod tests {
         assert_eq!(chunks[1].len(), 100);
         assert_eq!(chunks[2].len(), 50);
     }
+
+    fn items(count: usize) -> Vec<ProcessingItem> {
+        (0..count)
+            .map(|i| ProcessingItem {
+                id: i.to_string(),
+                data: Value::Null,
+                status: ProcessingStatus::Pending,
+                error: None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_cancel_returns_partial() {
+        let processor = BatchProcessor::with_config(BatchConfig {
+            max_concurrent: 2,
+            ..BatchConfig::default()
+        });
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+
+        let result = processor
+            .process_batch_with(
+                items(6),
+                |mut item| {
+                    Box::pin(async move {
+                        // Items 0 and 1 finish, the rest hang until aborted
+                        if item.id != "0" && item.id != "1" {
+                            tokio::time::sleep(Duration::from_secs(3600)).await;
+                        }
+                        item.status = ProcessingStatus::Success;
+                        Ok(item)
+                    })
+                },
+                &cancel,
+                move |done, total| {
+                    assert_eq!(total, 6);
+                    if done == 2 {
+                        canceller.cancel();
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(result.cancelled);
+        assert_eq!(result.processed_items, 2);
+        assert_eq!(result.skipped_items, 4);
+        assert_eq!(result.results.len(), 6);
+        assert_eq!(result.results[5].status, ProcessingStatus::Skipped);
+    }
 }
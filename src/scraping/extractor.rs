@@ -456,7 +456,7 @@ mod tests {
     #[test]
     fn test_extractor_creation() {
         let extractor = DataExtractor::new();
-        assert_eq!(extractor.config.strict_mode, true);
+        assert!(extractor.config.strict_mode);
     }
 
     #[test]
@@ -0,0 +1,172 @@
+//! TLS verification policy and certificate summaries
+//!
+//! Server certificates are verified against the system roots plus the PEM
+//! bundles in `http.ca_bundles`. Verification is skipped only for hosts
+//! listed in `http.insecure_hosts` (`*.staging.example` covers its
+//! subdomains), or everywhere with `http.accept_invalid_certs`, which is
+//! meant for local development.
+//!
+//! Each https response carries a [`CertificateSummary`] of the certificate
+//! the server presented, whether or not it was verified.
+
+use reqwest::Certificate;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use x509_parser::extensions::GeneralName;
+use x509_parser::pem::Pem;
+
+use crate::config::HttpSettings;
+use crate::models::CertificateSummary;
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("cannot read {path}: {source}")]
+    Read { path: PathBuf, source: std::io::Error },
+
+    #[error("{path}: {message}")]
+    Bundle { path: PathBuf, message: String },
+}
+
+/// The certificates of the PEM bundle at `path`; other PEM blocks are ignored
+pub fn load_bundle(path: &Path) -> Result<Vec<Certificate>, TlsError> {
+    let pem = std::fs::read(path).map_err(|source| TlsError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let invalid = |message: String| TlsError::Bundle {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let mut certificates = Vec::new();
+    for block in Pem::iter_from_buffer(&pem) {
+        let block = block.map_err(|e| invalid(e.to_string()))?;
+        if block.label != "CERTIFICATE" {
+            continue;
+        }
+        certificates.push(Certificate::from_der(&block.contents).map_err(|e| invalid(e.to_string()))?);
+    }
+    if certificates.is_empty() {
+        return Err(invalid("no PEM certificates".to_string()));
+    }
+    Ok(certificates)
+}
+
+/// Whether requests to `host` skip certificate verification
+pub fn is_exempt(http: &HttpSettings, host: &str) -> bool {
+    http.accept_invalid_certs || http.insecure_hosts.iter().any(|pattern| host_matches(pattern, host))
+}
+
+/// `pattern` is a host name or IP address, or `*.domain` for any subdomain
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|label| label.len() > 1 && label.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+/// An `http.insecure_hosts` entry is a host, not a URL
+pub fn is_host_pattern(pattern: &str) -> bool {
+    let host = pattern.strip_prefix("*.").unwrap_or(pattern);
+    !host.is_empty()
+        && (host.parse::<IpAddr>().is_ok()
+            || host.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+}
+
+/// What a DER certificate says about itself; `None` if it does not parse
+pub fn summarize(der: &[u8], verified: bool) -> Option<CertificateSummary> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    let time = |t: x509_parser::time::ASN1Time| {
+        chrono::DateTime::from_timestamp(t.timestamp(), 0).map(|t| t.to_rfc3339()).unwrap_or_default()
+    };
+    let validity = certificate.validity();
+
+    let subject_alt_names = match certificate.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                GeneralName::IPAddress(bytes) => match bytes.len() {
+                    4 => Some(IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?).to_string()),
+                    16 => Some(IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?).to_string()),
+                    _ => None,
+                },
+                GeneralName::RFC822Name(email) => Some(format!("email:{}", email)),
+                GeneralName::URI(uri) => Some(format!("uri:{}", uri)),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Some(CertificateSummary {
+        subject: certificate.subject().to_string(),
+        issuer: certificate.issuer().to_string(),
+        serial: certificate.raw_serial_as_string(),
+        not_before: time(validity.not_before),
+        not_after: time(validity.not_after),
+        subject_alt_names,
+        verified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAF: &str = include_str!("../tests/fixtures/tls/localhost.pem");
+
+    #[test]
+    fn test_exemptions_match_hosts_and_subdomains() {
+        let http = HttpSettings {
+            insecure_hosts: vec!["staging.example.com".to_string(), "*.internal.example".to_string()],
+            ..HttpSettings::default()
+        };
+        assert!(is_exempt(&http, "staging.example.com"));
+        assert!(is_exempt(&http, "STAGING.example.com."));
+        assert!(is_exempt(&http, "a.b.internal.example"));
+        assert!(!is_exempt(&http, "internal.example"));
+        assert!(!is_exempt(&http, "evilinternal.example"));
+        assert!(!is_exempt(&http, "example.com"));
+        assert!(is_exempt(&HttpSettings { accept_invalid_certs: true, ..HttpSettings::default() }, "example.com"));
+
+        assert!(is_host_pattern("*.internal.example"));
+        assert!(is_host_pattern("10.0.0.1"));
+        assert!(!is_host_pattern("https://staging.example.com"));
+        assert!(!is_host_pattern("*."));
+    }
+
+    #[test]
+    fn test_summary_has_issuer_expiry_and_names() {
+        let der = Pem::iter_from_buffer(LEAF.as_bytes()).next().unwrap().unwrap().contents;
+        let summary = summarize(&der, true).unwrap();
+        assert_eq!(summary.subject, "CN=localhost");
+        assert_eq!(summary.issuer, "CN=BrowserMCP Test CA");
+        assert_eq!(summary.subject_alt_names, vec!["localhost", "127.0.0.1"]);
+        assert!(summary.not_after.starts_with("2126-"));
+        assert!(summary.verified);
+        assert!(summarize(b"not a certificate", true).is_none());
+    }
+
+    #[test]
+    fn test_bundles_need_a_certificate() {
+        let dir = std::env::temp_dir().join(format!("browsermcp-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("bundle.pem");
+        std::fs::write(&bundle, format!("# staging\n{}", LEAF)).unwrap();
+        assert_eq!(load_bundle(&bundle).unwrap().len(), 1);
+
+        std::fs::write(&bundle, "nothing here\n").unwrap();
+        let empty = load_bundle(&bundle).unwrap_err();
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(empty.to_string().contains("no PEM certificates"));
+        assert!(matches!(load_bundle(&dir.join("missing.pem")), Err(TlsError::Read { .. })));
+    }
+}
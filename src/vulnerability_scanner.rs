@@ -3,41 +3,43 @@
 /// Analiza: formularios, endpoints, cookies, headers
 
 use regex::Regex;
-use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VulnerabilityScan {
-    pub scan_id: String,
-    pub url: String,
-    pub timestamp: String,
-    pub vulnerabilities: Vec<Vulnerability>,
-    pub severity_summary: SeveritySummary,
-    pub owasp_violations: Vec<String>,
+crate::tool_output! {
+    pub struct VulnerabilityScan {
+        required scan_id: String = {"type": "string"},
+        required url: String = {"type": "string"},
+        required timestamp: String = {"type": "string", "format": "date-time"},
+        required vulnerabilities: Vec<Vulnerability> = {"type": "array", "items": Vulnerability::schema()},
+        required severity_summary: SeveritySummary = (SeveritySummary::schema()),
+        required owasp_violations: Vec<String> = {"type": "array", "items": {"type": "string"}},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Vulnerability {
-    pub id: String,
-    pub cwe_id: String, // CWE identifier
-    pub title: String,
-    pub description: String,
-    pub severity: String, // "critical", "high", "medium", "low"
-    pub cvss_score: f32,
-    pub location: String, // HTML element/endpoint
-    pub evidence: String, // What was found
-    pub remediation: String,
+crate::tool_output! {
+    pub struct Vulnerability {
+        required id: String = {"type": "string"},
+        required cwe_id: String = {"type": "string"}, // CWE identifier
+        required title: String = {"type": "string"},
+        required description: String = {"type": "string"},
+        required severity: String = {"type": "string", "enum": ["critical", "high", "medium", "low"]},
+        required cvss_score: f32 = {"type": "number", "minimum": 0, "maximum": 10},
+        required location: String = {"type": "string"}, // HTML element/endpoint
+        required evidence: String = {"type": "string"}, // What was found
+        required remediation: String = {"type": "string"},
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SeveritySummary {
-    pub critical: u32,
-    pub high: u32,
-    pub medium: u32,
-    pub low: u32,
-    pub total: u32,
+crate::tool_output! {
+    pub struct SeveritySummary {
+        required critical: u32 = {"type": "integer", "minimum": 0},
+        required high: u32 = {"type": "integer", "minimum": 0},
+        required medium: u32 = {"type": "integer", "minimum": 0},
+        required low: u32 = {"type": "integer", "minimum": 0},
+        required total: u32 = {"type": "integer", "minimum": 0},
+    }
 }
 
 /// Vulnerability Scanner
@@ -62,7 +64,7 @@ impl VulnerabilityScanner {
         patterns.insert(
             "sql_injection".to_string(),
             VulnPattern {
-                regex: Regex::new(r"(?i)(union|select|insert|update|delete|drop|create|alter)\s*\(").unwrap(),
+                regex: Regex::new(r"(?i)(\b(union\s+(all\s+)?select|drop\s+table|insert\s+into|delete\s+from)\b|(union|select|insert|update|delete|drop|create|alter)\s*\()").unwrap(),
                 vuln_type: "SQL Injection".to_string(),
                 severity: "critical".to_string(),
                 cwe: "CWE-89".to_string(),
@@ -158,7 +160,7 @@ impl VulnerabilityScanner {
         patterns.insert(
             "path_traversal".to_string(),
             VulnPattern {
-                regex: Regex::new(r"(?i)(\.\.\/|\.\.\\|\.\./|\.\.\)").unwrap(),
+                regex: Regex::new(r"(\.\./|\.\.\\)").unwrap(),
                 vuln_type: "Path Traversal".to_string(),
                 severity: "high".to_string(),
                 cwe: "CWE-22".to_string(),
@@ -181,7 +183,7 @@ impl VulnerabilityScanner {
         // Scan each pattern
         for (key, pattern) in self.patterns.iter() {
             if let Some(mat) = pattern.regex.find(html) {
-                let evidence = &html[mat.start().saturating_sub(50)..mat.end().saturating_add(50)];
+                let evidence = evidence_window(html, mat.start(), mat.end(), 50);
                 
                 vulns.push(Vulnerability {
                     id: format!("{}_{}", key, chrono::Utc::now().timestamp()),
@@ -307,6 +309,19 @@ impl VulnerabilityScanner {
     }
 }
 
+/// Contexto alrededor del match, recortado a límites de char válidos
+fn evidence_window(html: &str, start: usize, end: usize, context: usize) -> &str {
+    let mut from = start.saturating_sub(context);
+    while !html.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = end.saturating_add(context).min(html.len());
+    while !html.is_char_boundary(to) {
+        to += 1;
+    }
+    &html[from..to]
+}
+
 impl VulnerabilityScan {
     /// Obtener conteo de vulnerabilidades
     pub fn vulnerability_count(&self) -> usize {
@@ -355,6 +370,15 @@ mod tests {
         assert!(!xss_vulns.is_empty());
     }
 
+    #[test]
+    fn test_evidence_window_respects_bounds() {
+        let scanner = VulnerabilityScanner::new();
+        let html = "ñ<script>";
+        let result = scanner.scan_html("http://example.com", html);
+        let xss = result.vulnerabilities.iter().find(|v| v.cwe_id == "CWE-79").unwrap();
+        assert_eq!(xss.evidence, html);
+    }
+
     #[test]
     fn test_severity_summary() {
         let scanner = VulnerabilityScanner::new();
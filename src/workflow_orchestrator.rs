@@ -97,10 +97,19 @@ impl WorkflowOrchestrator {
             .ok_or_else(|| "Workflow not found".to_string())?
             .clone();
 
+        let execution = Self::run_workflow(&workflow).await;
+        self.record_execution(execution.clone());
+        Ok(execution)
+    }
+
+    /// Ejecutar los steps de `workflow` sin tocar el orchestrator, para no
+    /// tenerlo bloqueado mientras dura; el resultado se guarda con
+    /// `record_execution`
+    pub async fn run_workflow(workflow: &WorkflowTask) -> WorkflowExecution {
         let execution_id = format!("exec_{}", uuid::Uuid::new_v4());
         let mut execution = WorkflowExecution {
             execution_id: execution_id.clone(),
-            workflow_id: workflow_id.to_string(),
+            workflow_id: workflow.id.clone(),
             start_time: Utc::now().to_rfc3339(),
             end_time: None,
             status: "running".to_string(),
@@ -113,10 +122,10 @@ impl WorkflowOrchestrator {
             
             // Ejecutar step basado en action
             let result = match step.action.as_str() {
-                "scrape" => self.execute_scrape_step(step).await,
-                "analyze" => self.execute_analyze_step(step).await,
-                "store" => self.execute_custom_step(step).await,
-                "alert" => self.execute_alert_step(step).await,
+                "scrape" => Self::execute_scrape_step(step).await,
+                "analyze" => Self::execute_analyze_step(step).await,
+                "store" => Self::execute_custom_step(step).await,
+                "alert" => Self::execute_alert_step(step).await,
                 _ => Err("Unknown action".to_string()),
             };
 
@@ -159,13 +168,16 @@ impl WorkflowOrchestrator {
         }
 
         execution.end_time = Some(Utc::now().to_rfc3339());
-        self.executions.insert(execution_id, execution.clone());
+        execution
+    }
 
-        Ok(execution)
+    /// Guardar una ejecución en el historial
+    pub fn record_execution(&mut self, execution: WorkflowExecution) {
+        self.executions.insert(execution.execution_id.clone(), execution);
     }
 
     /// Helper: Ejecutar scrape step - REAL implementation
-    async fn execute_scrape_step(&self, step: &WorkflowStep) -> Result<Value, String> {
+    async fn execute_scrape_step(step: &WorkflowStep) -> Result<Value, String> {
         let url = step.params.get("url")
             .and_then(|v| v.as_str())
             .ok_or_else(|| "URL required for scrape".to_string())?;
@@ -213,7 +225,7 @@ impl WorkflowOrchestrator {
     }
 
     /// Helper: Ejecutar analyze step - REAL implementation  
-    async fn execute_analyze_step(&self, step: &WorkflowStep) -> Result<Value, String> {
+    async fn execute_analyze_step(step: &WorkflowStep) -> Result<Value, String> {
         let analysis_start = Utc::now();
         
         // REAL: Usar parámetros reales del step
@@ -263,7 +275,7 @@ impl WorkflowOrchestrator {
     }
 
     /// Helper: Ejecutar store step
-    async fn execute_custom_step(&self, _step: &WorkflowStep) -> Result<Value, String> {
+    async fn execute_custom_step(_step: &WorkflowStep) -> Result<Value, String> {
         Ok(json!({
             "action": "store",
             "status": "completed",
@@ -276,7 +288,7 @@ impl WorkflowOrchestrator {
     }
 
     /// Helper: Ejecutar alert step
-    async fn execute_alert_step(&self, step: &WorkflowStep) -> Result<Value, String> {
+    async fn execute_alert_step(step: &WorkflowStep) -> Result<Value, String> {
         let alert_type = step.params.get("alert_type")
             .and_then(|v| v.as_str())
             .unwrap_or("email");
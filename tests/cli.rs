@@ -0,0 +1,121 @@
+//! `call`, `list-tools` and `describe`: the server's tools from the shell
+
+use extreme_browser_mcp::mcp::schema;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+mod common;
+use common::{serve_site, BIN};
+
+/// Run a tool command in a scratch data directory, feeding `stdin`
+fn run(args: &[&str], stdin: &str) -> Output {
+    let dir = std::env::temp_dir().join(format!("browsermcp_it_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut child = Command::new(BIN)
+        .args(args)
+        .current_dir(&dir)
+        .env("BROWSERMCP_DATA_DIR", dir.join("data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    output
+}
+
+fn tool_output(output: &Output) -> Value {
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap()
+}
+
+#[test]
+fn test_list_and_describe_share_the_server_registry() {
+    let listed = run(&["list-tools"], "");
+    assert!(listed.status.success());
+    let table = String::from_utf8(listed.stdout).unwrap();
+    assert!(table.lines().any(|line| line.starts_with("web_scrape ")));
+
+    let listed = run(&["list-tools", "--json"], "");
+    let tools: Value = serde_json::from_slice(&listed.stdout).unwrap();
+    assert!(tools["tools"].as_array().unwrap().iter().any(|t| t["name"] == "dom_extraction"));
+
+    let described = run(&["describe", "web_scrape"], "");
+    let text = String::from_utf8(described.stdout).unwrap();
+    assert!(text.contains("call web_scrape"));
+    assert!(text.contains("--url <string>"));
+}
+
+#[test]
+fn test_call_prints_the_tool_result() {
+    let html = "<h1>Deals</h1><span class=\"price\">$10</span>";
+    let output = run(
+        &["call", "dom_extraction", "--html", html, "--selectors", r#"{"price": ".price"}"#],
+        "",
+    );
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(tool_output(&output)["fields"], json!({"price": ["$10"]}));
+
+    // The whole arguments object from stdin
+    let arguments = json!({"html": html, "selectors": {"title": "h1"}}).to_string();
+    let output = run(&["call", "dom_extraction", "--json", "-"], &arguments);
+    assert_eq!(tool_output(&output)["fields"], json!({"title": ["Deals"]}));
+}
+
+#[test]
+fn test_web_scrape_result_matches_its_output_schema() {
+    let base = serve_site(&[(
+        "/",
+        "<html><head><title>Shop</title></head><body><h2>Offers</h2>\
+         <a href=\"/a\">A</a><form action=\"/q\"><input name=\"q\"></form></body></html>",
+    )]);
+    let output = run(&["call", "web_scrape", "--url", &format!("{}/", base)], "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let data = &result["structuredContent"];
+    assert_eq!(data["title"], "Shop");
+    assert_eq!(data["headings"], json!([{"level": "h2", "text": "Offers"}]));
+    assert_eq!(data["forms"][0]["inputs"], json!([{"name": "q", "type": "text"}]));
+    assert_eq!(data, &tool_output(&output));
+
+    let listed = run(&["list-tools", "--json"], "");
+    let tools: Value = serde_json::from_slice(&listed.stdout).unwrap();
+    let web_scrape = tools["tools"].as_array().unwrap().iter().find(|t| t["name"] == "web_scrape").unwrap();
+    assert_eq!(web_scrape["annotations"]["readOnlyHint"], true);
+    assert_eq!(schema::validate(&web_scrape["outputSchema"], data), vec![]);
+}
+
+#[test]
+fn test_robots_txt_refuses_disallowed_urls() {
+    let base = serve_site(&[
+        ("/robots.txt", "User-agent: *\nDisallow: /private\n\nUser-agent: OtherBot\nDisallow: /\n"),
+        ("/private/page", "<title>Secret</title>"),
+        ("/open", "<title>Open</title>"),
+    ]);
+
+    let refused = run(&["call", "web_scrape", "--url", &format!("{}/private/page", base)], "");
+    assert_eq!(refused.status.code(), Some(1));
+    let result: Value = serde_json::from_slice(&refused.stdout).unwrap();
+    assert_eq!(result["isError"], true);
+    let message = result["content"][0]["text"].as_str().unwrap();
+    assert!(message.contains("disallowed by") && message.contains("Disallow: /private"), "{}", message);
+
+    let allowed = run(&["call", "web_scrape", "--url", &format!("{}/open", base)], "");
+    assert_eq!(tool_output(&allowed)["title"], "Open");
+}
+
+#[test]
+fn test_bad_arguments_exit_with_usage_error() {
+    let unknown = run(&["call", "web_scrape", "--nope", "1"], "");
+    assert_eq!(unknown.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("no argument `nope`"));
+
+    let invalid = run(&["call", "web_scrape", "--url", "not a url"], "");
+    assert_eq!(invalid.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("-32602"));
+
+    assert_eq!(run(&["call", "no_such_tool"], "").status.code(), Some(2));
+}
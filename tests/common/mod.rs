@@ -0,0 +1,156 @@
+//! Shared harness for integration tests: runs the real `browsermcp-server`
+//! binary on a free port with its own data directory.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+pub const BIN: &str = env!("CARGO_BIN_EXE_browsermcp-server");
+
+pub struct TestServer {
+    child: Child,
+    pub dir: PathBuf,
+    pub base: String,
+    pub api_key: String,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl TestServer {
+    /// Send SIGTERM, as a container runtime stopping the server does
+    pub fn terminate(&self) {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Exit status once the process ends, or `None` after `timeout`
+    pub async fn wait_exit(&mut self, timeout: Duration) -> Option<std::process::ExitStatus> {
+        let deadline = std::time::Instant::now() + timeout;
+        while std::time::Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return Some(status);
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        None
+    }
+}
+
+/// A site on a local port serving `pages` (`(path, html)`), 404 elsewhere;
+/// returns its base URL without the trailing slash
+pub fn serve_site(pages: &[(&'static str, &'static str)]) -> String {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let pages = pages.to_vec();
+    std::thread::spawn(move || {
+        for socket in listener.incoming() {
+            let Ok(mut socket) = socket else { continue };
+            let mut request = [0u8; 4096];
+            let read = socket.read(&mut request).unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (status, body) = match pages.iter().find(|(page, _)| *page == path) {
+                Some((_, body)) => ("200 OK", *body),
+                None => ("404 Not Found", ""),
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes());
+        }
+    });
+    base
+}
+
+/// New API key and its argon2 hash, from `browsermcp-server hash-key`
+pub fn generate_key() -> (String, String) {
+    let output = Command::new(BIN).arg("hash-key").output().unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(|value| value.trim().to_string())
+            .unwrap()
+    };
+    (field("key:"), field("hash:"))
+}
+
+/// Start the server with one API key named `team`; `key` holds extra fields
+/// of its auth file entry (tool allowlist, quotas)
+pub async fn start_server(key: Value, args: &[&str], env: &[(&str, &str)]) -> TestServer {
+    let dir = std::env::temp_dir().join(format!("browsermcp_it_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let (api_key, hash) = generate_key();
+    let mut entry = json!({"name": "team", "hash": hash});
+    if let (Some(entry), Some(extra)) = (entry.as_object_mut(), key.as_object()) {
+        entry.extend(extra.clone());
+    }
+    std::fs::write(dir.join("auth.json"), json!({"api_keys": [entry]}).to_string()).unwrap();
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let base = format!("http://127.0.0.1:{}", port);
+    let child = Command::new(BIN)
+        .arg(port.to_string())
+        .args(["--public-url", &base, "--auth-file"])
+        .arg(dir.join("auth.json"))
+        .args(args)
+        .envs(env.iter().copied())
+        .env("BROWSERMCP_DATA_DIR", dir.join("data"))
+        .env("BROWSERMCP_PROMPTS_DIR", dir.join("prompts"))
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let server = TestServer {
+        child,
+        dir,
+        base,
+        api_key,
+    };
+
+    for _ in 0..100 {
+        if reqwest::get(format!("{}/health", server.base)).await.is_ok() {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("server did not start");
+}
+
+/// HTTP client that does not follow redirects
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// POST a JSON-RPC message to `/mcp` with `token` as bearer credential
+pub async fn mcp(server: &TestServer, http: &reqwest::Client, token: &str, message: Value) -> reqwest::Response {
+    http.post(format!("{}/mcp", server.base))
+        .bearer_auth(token)
+        .json(&message)
+        .send()
+        .await
+        .unwrap()
+}
@@ -0,0 +1,49 @@
+//! `browsermcp-server config show`: the effective layered configuration
+
+use std::process::Command;
+
+mod common;
+use common::BIN;
+
+#[test]
+fn test_config_show_layers_file_env_and_flags() {
+    let dir = std::env::temp_dir().join(format!("browsermcp_it_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("browsermcp.toml"),
+        "[server]\nport = 8080\n\n[http]\ntimeout_secs = 10\n\n[batch]\nconcurrency = 2\n",
+    )
+    .unwrap();
+
+    let output = Command::new(BIN)
+        .args(["config", "show", "--bind", "0.0.0.0", "--set", "batch.concurrency=3"])
+        .current_dir(&dir)
+        .env("BROWSERMCP_HTTP_TIMEOUT_SECS", "20")
+        .env("GITHUB_TOKEN", "ghp_do_not_print")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert!(output.status.success());
+    let shown = String::from_utf8(output.stdout).unwrap();
+    assert!(shown.contains("port = 8080"));
+    assert!(shown.contains("bind = \"0.0.0.0\""));
+    assert!(shown.contains("timeout_secs = 20"));
+    assert!(shown.contains("concurrency = 3"));
+    assert!(!shown.contains("ghp_do_not_print"));
+}
+
+#[test]
+fn test_invalid_config_is_reported_at_startup() {
+    let output = Command::new(BIN)
+        .args(["--set", "http.timeout_secs=0"])
+        .env("BROWSERMCP_BIND", "everywhere")
+        .current_dir(std::env::temp_dir())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("http.timeout_secs must be at least 1"));
+    assert!(stderr.contains("server.bind"));
+}
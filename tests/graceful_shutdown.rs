@@ -0,0 +1,55 @@
+//! SIGTERM drains `browsermcp-server`: open streams end, running tool calls
+//! get the grace period and are then cancelled, and the process exits cleanly
+
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{client, start_server};
+
+#[tokio::test]
+async fn test_sigterm_cancels_calls_past_the_grace_period() {
+    let mut server = start_server(json!({}), &[], &[("BROWSERMCP_SHUTDOWN_GRACE_SECS", "1")]).await;
+    let http = client();
+
+    // A host that accepts connections and never answers
+    let stalled = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let stalled_url = format!("http://{}/", stalled.local_addr().unwrap());
+    let _hold = tokio::spawn(async move {
+        let mut open = Vec::new();
+        while let Ok((socket, _)) = stalled.accept().await {
+            open.push(socket);
+        }
+    });
+
+    // An SSE stream stays open until shutdown
+    let events = http
+        .get(format!("{}/mcp", server.base))
+        .bearer_auth(&server.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(events.status(), 200);
+
+    let call = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {"name": "web_scrape_batch", "arguments": {"urls": [stalled_url]}}
+    });
+    let request = http.post(format!("{}/mcp", server.base)).bearer_auth(&server.api_key).json(&call).send();
+    let running = tokio::spawn(async move { request.await.unwrap().json::<Value>().await.unwrap() });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let signalled = Instant::now();
+    server.terminate();
+    let response: Value = running.await.unwrap();
+    let output: Value = serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(output["cancelled"], true);
+    assert!(signalled.elapsed() >= Duration::from_secs(1), "calls get the grace period");
+
+    // The SSE body ends instead of holding the shutdown
+    assert!(events.text().await.is_ok());
+    let status = server.wait_exit(Duration::from_secs(10)).await.expect("server exits");
+    assert!(status.success());
+}
@@ -0,0 +1,204 @@
+//! End-to-end OAuth 2.1 flow against a running `browsermcp-server --oauth`:
+//! discovery, dynamic registration, PKCE authorization, token exchange,
+//! authenticated MCP calls and refresh.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+mod common;
+use common::{client, start_server, TestServer};
+
+const REDIRECT: &str = "http://127.0.0.1:8765/callback";
+
+/// OAuth server whose only API key may call `web_scrape`
+async fn start_oauth_server() -> TestServer {
+    start_server(json!({"tools": ["web_scrape"]}), &["--oauth"], &[]).await
+}
+
+fn query_param(url: &str, name: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .unwrap()
+        .query_pairs()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned())
+}
+
+/// Discovery and registration as an MCP client does it, starting from the 401
+async fn discover_and_register(server: &TestServer, http: &reqwest::Client) -> (Value, String) {
+    let unauthorized = http
+        .post(format!("{}/mcp", server.base))
+        .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unauthorized.status(), 401);
+    let challenge = unauthorized.headers()["www-authenticate"].to_str().unwrap().to_string();
+    let resource_metadata = challenge
+        .split("resource_metadata=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_string();
+
+    let resource: Value = http.get(&resource_metadata).send().await.unwrap().json().await.unwrap();
+    assert_eq!(resource["resource"], format!("{}/mcp", server.base));
+    let issuer = resource["authorization_servers"][0].as_str().unwrap();
+    let metadata: Value = http
+        .get(format!("{}/.well-known/oauth-authorization-server", issuer))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(metadata["code_challenge_methods_supported"], json!(["S256"]));
+
+    let registration = http
+        .post(metadata["registration_endpoint"].as_str().unwrap())
+        .json(&json!({"client_name": "integration test", "redirect_uris": [REDIRECT]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(registration.status(), 201);
+    let client_id = registration.json::<Value>().await.unwrap()["client_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    (metadata, client_id)
+}
+
+fn authorization_params<'a>(client_id: &'a str, challenge: &'a str, resource: &'a str) -> Vec<(&'static str, &'a str)> {
+    vec![
+        ("response_type", "code"),
+        ("client_id", client_id),
+        ("redirect_uri", REDIRECT),
+        ("code_challenge", challenge),
+        ("code_challenge_method", "S256"),
+        ("state", "state-123"),
+        ("resource", resource),
+    ]
+}
+
+async fn mcp(server: &TestServer, http: &reqwest::Client, token: &str, method: &str) -> reqwest::Response {
+    let message = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": {}});
+    common::mcp(server, http, token, message).await
+}
+
+#[tokio::test]
+async fn test_authorization_code_flow_with_pkce() {
+    let server = start_oauth_server().await;
+    let http = client();
+    let (metadata, client_id) = discover_and_register(&server, &http).await;
+    let authorize = metadata["authorization_endpoint"].as_str().unwrap();
+    let token_endpoint = metadata["token_endpoint"].as_str().unwrap();
+
+    let verifier = URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4().as_bytes().repeat(2));
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let resource = format!("{}/mcp", server.base);
+    let params = authorization_params(&client_id, &challenge, &resource);
+
+    let consent = http.get(authorize).query(&params).send().await.unwrap();
+    assert_eq!(consent.status(), 200);
+    assert!(consent.text().await.unwrap().contains("integration test"));
+
+    let mut form = params.clone();
+    form.extend([("api_key", "bmcp_wrong"), ("decision", "approve")]);
+    assert_eq!(http.post(authorize).form(&form).send().await.unwrap().status(), 401);
+
+    let mut form = params.clone();
+    form.extend([("api_key", server.api_key.as_str()), ("decision", "approve")]);
+    let approved = http.post(authorize).form(&form).send().await.unwrap();
+    assert_eq!(approved.status(), 303);
+    let location = approved.headers()["location"].to_str().unwrap().to_string();
+    assert!(location.starts_with(REDIRECT));
+    assert_eq!(query_param(&location, "state").as_deref(), Some("state-123"));
+    let code = query_param(&location, "code").unwrap();
+
+    let exchange = [
+        ("grant_type", "authorization_code"),
+        ("client_id", client_id.as_str()),
+        ("code", code.as_str()),
+        ("redirect_uri", REDIRECT),
+        ("code_verifier", verifier.as_str()),
+        ("resource", resource.as_str()),
+    ];
+    let tokens: Value = http.post(token_endpoint).form(&exchange).send().await.unwrap().json().await.unwrap();
+    let access_token = tokens["access_token"].as_str().unwrap();
+    assert_eq!(tokens["token_type"], "Bearer");
+
+    // The code cannot be replayed
+    let replay = http.post(token_endpoint).form(&exchange).send().await.unwrap();
+    assert_eq!(replay.status(), 400);
+    assert_eq!(replay.json::<Value>().await.unwrap()["error"], "invalid_grant");
+
+    // The token carries the approving key's tool allowlist
+    let tools: Value = mcp(&server, &http, access_token, "tools/list").await.json().await.unwrap();
+    let names: Vec<&str> = tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["web_scrape"]);
+
+    let refresh = [
+        ("grant_type", "refresh_token"),
+        ("client_id", client_id.as_str()),
+        ("refresh_token", tokens["refresh_token"].as_str().unwrap()),
+    ];
+    let rotated: Value = http.post(token_endpoint).form(&refresh).send().await.unwrap().json().await.unwrap();
+    let rotated_token = rotated["access_token"].as_str().unwrap();
+    assert_eq!(mcp(&server, &http, rotated_token, "ping").await.status(), 200);
+    assert_eq!(http.post(token_endpoint).form(&refresh).send().await.unwrap().status(), 400);
+
+    let bogus = mcp(&server, &http, "bmcp_at_bogus", "ping").await;
+    assert_eq!(bogus.status(), 401);
+    assert!(bogus.headers()["www-authenticate"].to_str().unwrap().contains("invalid_token"));
+}
+
+#[tokio::test]
+async fn test_pkce_and_redirect_checks() {
+    let server = start_oauth_server().await;
+    let http = client();
+    let (metadata, client_id) = discover_and_register(&server, &http).await;
+    let authorize = metadata["authorization_endpoint"].as_str().unwrap();
+    let token_endpoint = metadata["token_endpoint"].as_str().unwrap();
+
+    let verifier = "a".repeat(64);
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    let resource = format!("{}/mcp", server.base);
+    let params = authorization_params(&client_id, &challenge, &resource);
+
+    // An unregistered redirect URI is never redirected to
+    let mut foreign = params.clone();
+    foreign[2] = ("redirect_uri", "https://evil.example.com/cb");
+    assert_eq!(http.get(authorize).query(&foreign).send().await.unwrap().status(), 400);
+
+    // Missing PKCE is reported back to the client
+    let no_pkce: Vec<_> = params.iter().filter(|(k, _)| !k.starts_with("code_challenge")).cloned().collect();
+    let response = http.get(authorize).query(&no_pkce).send().await.unwrap();
+    let location = response.headers()["location"].to_str().unwrap();
+    assert_eq!(query_param(location, "error").as_deref(), Some("invalid_request"));
+
+    let mut form = params.clone();
+    form.extend([("api_key", server.api_key.as_str()), ("decision", "approve")]);
+    let approved = http.post(authorize).form(&form).send().await.unwrap();
+    let code = query_param(approved.headers()["location"].to_str().unwrap(), "code").unwrap();
+
+    let wrong_verifier = "b".repeat(64);
+    let exchange = [
+        ("grant_type", "authorization_code"),
+        ("client_id", client_id.as_str()),
+        ("code", code.as_str()),
+        ("redirect_uri", REDIRECT),
+        ("code_verifier", wrong_verifier.as_str()),
+    ];
+    let rejected = http.post(token_endpoint).form(&exchange).send().await.unwrap();
+    assert_eq!(rejected.status(), 400);
+    assert_eq!(rejected.json::<Value>().await.unwrap()["error"], "invalid_grant");
+
+    // Plain API keys keep working next to OAuth
+    assert_eq!(mcp(&server, &http, &server.api_key, "ping").await.status(), 200);
+}
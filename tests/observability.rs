@@ -0,0 +1,41 @@
+//! `/metrics` and `/ready` on a running `browsermcp-server`
+
+use serde_json::{json, Value};
+
+mod common;
+use common::{client, mcp, start_server};
+
+#[tokio::test]
+async fn test_metrics_count_tool_calls() {
+    let server = start_server(json!({}), &[], &[]).await;
+    let http = client();
+
+    let call = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {"name": "get_stealth_headers", "arguments": {}}
+    });
+    let response: Value = mcp(&server, &http, &server.api_key, call).await.json().await.unwrap();
+    assert!(response["result"].is_object());
+
+    let metrics = http.get(format!("{}/metrics", server.base)).send().await.unwrap();
+    assert_eq!(metrics.status(), 200);
+    assert!(metrics.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+    let text = metrics.text().await.unwrap();
+    assert!(text.contains("browsermcp_tool_calls_total{tool=\"get_stealth_headers\",status=\"ok\"} 1"));
+    assert!(text.contains("browsermcp_tool_call_duration_seconds_count{tool=\"get_stealth_headers\"} 1"));
+    assert!(text.contains("# TYPE browsermcp_batch_queue_depth gauge"));
+    assert!(text.contains("browsermcp_sessions 0"));
+}
+
+#[tokio::test]
+async fn test_ready_checks_persistence_and_wasm() {
+    let server = start_server(json!({}), &[], &[]).await;
+    let response = client().get(format!("{}/ready", server.base)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["checks"]["persistence"]["status"], "ok");
+    assert_eq!(body["checks"]["wasm_runtime"]["status"], "ok");
+}
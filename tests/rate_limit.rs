@@ -0,0 +1,77 @@
+//! Per-key quotas on `tools/call` and page reads against a running `browsermcp-server`
+
+use serde_json::{json, Value};
+
+mod common;
+use common::{client, mcp, start_server};
+
+fn stealth_headers(id: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": {"name": "get_stealth_headers", "arguments": {}}
+    })
+}
+
+fn rate_limited(response: &Value) -> Option<Value> {
+    let result = &response["result"];
+    if result["isError"] != true {
+        return None;
+    }
+    serde_json::from_str(result["content"][0]["text"].as_str()?).ok()
+}
+
+#[tokio::test]
+async fn test_tool_calls_over_burst_get_retry_after() {
+    let quota = json!({"rate_limit": {"requests_per_second": 0.5, "burst_size": 2}});
+    let server = start_server(quota, &[], &[]).await;
+    let http = client();
+
+    for id in 1..=2 {
+        let response: Value = mcp(&server, &http, &server.api_key, stealth_headers(id)).await.json().await.unwrap();
+        assert!(rate_limited(&response).is_none(), "call {} should pass: {}", id, response);
+    }
+
+    let response: Value = mcp(&server, &http, &server.api_key, stealth_headers(3)).await.json().await.unwrap();
+    assert_eq!(response["id"], 3);
+    let limited = rate_limited(&response).expect("third call is over the burst");
+    assert_eq!(limited["error"], "rate_limited");
+    assert_eq!(limited["scope"], "api_key");
+    let retry_after_ms = limited["retry_after_ms"].as_u64().unwrap();
+    assert!(retry_after_ms > 0 && retry_after_ms <= 2000);
+    assert!(limited["message"].as_str().unwrap().contains(&format!("retry after {} ms", retry_after_ms)));
+
+    // Only tool calls are charged
+    let ping = json!({"jsonrpc": "2.0", "id": 4, "method": "ping"});
+    let response: Value = mcp(&server, &http, &server.api_key, ping).await.json().await.unwrap();
+    assert_eq!(response["result"], json!({}));
+
+    // In a batch, calls over quota are answered in place and the rest run
+    let batch = json!([{"jsonrpc": "2.0", "id": 5, "method": "ping"}, stealth_headers(6)]);
+    let responses: Value = mcp(&server, &http, &server.api_key, batch).await.json().await.unwrap();
+    let responses = responses.as_array().unwrap();
+    assert_eq!(responses.len(), 2);
+    let limited = responses.iter().find(|r| r["id"] == 6).unwrap();
+    assert!(rate_limited(limited).is_some());
+}
+
+#[tokio::test]
+async fn test_server_defaults_apply_without_key_override() {
+    let env = [("BROWSERMCP_RATE_LIMIT_RPS", "0.1"), ("BROWSERMCP_RATE_LIMIT_BURST", "1")];
+    let server = start_server(json!({}), &[], &env).await;
+    let http = client();
+
+    let first: Value = mcp(&server, &http, &server.api_key, stealth_headers(1)).await.json().await.unwrap();
+    assert!(rate_limited(&first).is_none());
+    let second: Value = mcp(&server, &http, &server.api_key, stealth_headers(2)).await.json().await.unwrap();
+    let limited = rate_limited(&second).unwrap();
+    assert!(limited["retry_after_ms"].as_u64().unwrap() > 5000);
+
+    // Page resources fetch like web_scrape and are charged the same
+    let read = json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read",
+        "params": {"uri": "browser://page/https://example.com/"}});
+    let refused: Value = mcp(&server, &http, &server.api_key, read).await.json().await.unwrap();
+    assert_eq!(refused["error"]["code"], -32029);
+    assert_eq!(refused["error"]["data"]["error"], "rate_limited");
+}